@@ -0,0 +1,132 @@
+use crate::*;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Ord, PartialOrd, Hash)]
+pub enum BlendMode {
+    #[default]
+    None,
+    // TODO: Rename to Add
+    Additive,
+    Alpha,
+    /// Multiplies the destination by the source color, which can only darken
+    /// it. Useful for shadows and tinted glass. Fully transparent pixels
+    /// leave the destination unchanged.
+    ///
+    /// The color returned by the fragment shader is premultiplied by its
+    /// alpha before blending, shaders should keep returning straight alpha.
+    Multiply,
+    /// Inverse of `Multiply`, `1 - (1 - src) * (1 - dst)`, which can only
+    /// lighten the destination. Premultiplies the shader's output the same
+    /// way as `Multiply`.
+    Screen,
+    /// Subtracts the source color weighted by its alpha from the
+    /// destination.
+    Subtract,
+    /// Alpha blending for textures whose color was already multiplied by
+    /// their alpha, which avoids dark fringes around texture edges.
+    PremultipliedAlpha,
+    /// Escape hatch for blend states not covered by the other variants.
+    Custom(CustomBlendState),
+}
+
+/// Mirrors `wgpu::BlendState` so that it can be used from `comfy-core` and
+/// as part of `MeshGroupKey`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Ord, PartialOrd, Hash)]
+pub struct CustomBlendState {
+    pub color: BlendComponent,
+    pub alpha: BlendComponent,
+}
+
+impl CustomBlendState {
+    pub fn new(color: BlendComponent, alpha: BlendComponent) -> Self {
+        Self { color, alpha }
+    }
+}
+
+impl From<CustomBlendState> for BlendMode {
+    fn from(state: CustomBlendState) -> Self {
+        BlendMode::Custom(state)
+    }
+}
+
+/// Blend equation of a single channel group, the result is
+/// `operation(src * src_factor, dst * dst_factor)`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Ord, PartialOrd, Hash)]
+pub struct BlendComponent {
+    pub src_factor: BlendFactor,
+    pub dst_factor: BlendFactor,
+    pub operation: BlendOperation,
+}
+
+impl BlendComponent {
+    pub const REPLACE: Self =
+        Self::new(BlendFactor::One, BlendFactor::Zero, BlendOperation::Add);
+
+    pub const OVER: Self = Self::new(
+        BlendFactor::One,
+        BlendFactor::OneMinusSrcAlpha,
+        BlendOperation::Add,
+    );
+
+    pub const fn new(
+        src_factor: BlendFactor,
+        dst_factor: BlendFactor,
+        operation: BlendOperation,
+    ) -> Self {
+        Self { src_factor, dst_factor, operation }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Ord, PartialOrd, Hash)]
+pub enum BlendFactor {
+    Zero,
+    One,
+    Src,
+    OneMinusSrc,
+    SrcAlpha,
+    OneMinusSrcAlpha,
+    Dst,
+    OneMinusDst,
+    DstAlpha,
+    OneMinusDstAlpha,
+    SrcAlphaSaturated,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Ord, PartialOrd, Hash)]
+pub enum BlendOperation {
+    Add,
+    Subtract,
+    ReverseSubtract,
+    Min,
+    Max,
+}
+
+#[test]
+fn test_custom_blend_modes_are_distinct_group_keys() {
+    let key = |blend_mode| {
+        MeshGroupKey {
+            z_index: 0,
+            blend_mode,
+            texture_id: texture_id_unchecked("1px"),
+            shader: ShaderInstanceId::default(),
+            render_target: RenderTargetId::default(),
+            clip: ClipId::default(),
+            layers: RenderLayers::DEFAULT,
+        }
+    };
+
+    let multiply = CustomBlendState::new(
+        BlendComponent::new(
+            BlendFactor::Dst,
+            BlendFactor::Zero,
+            BlendOperation::Add,
+        ),
+        BlendComponent::OVER,
+    );
+
+    let mut keys = std::collections::BTreeSet::new();
+    keys.insert(key(BlendMode::Multiply));
+    keys.insert(key(multiply.into()));
+    keys.insert(key(BlendMode::Custom(multiply)));
+
+    assert_eq!(keys.len(), 2);
+}
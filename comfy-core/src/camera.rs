@@ -149,11 +149,21 @@ pub struct MainCamera {
     pub zoom: f32,
     pub desired_zoom: f32,
 
+    /// Part of the screen the main camera draws into, e.g. the left half for
+    /// the first player in split-screen. See `add_camera` for adding more
+    /// cameras.
+    pub viewport: Viewport,
+    /// Render layers the main camera draws, see `use_render_layers`.
+    pub layers: RenderLayers,
+
     /// Optional camera matrix function that allows the user to create their own projection matrix.
     ///
     /// See the implementation of `build_view_projection_matrix` for what is the default with
     /// `Mat4::orthographic_rh`. Note that this doesn't have to return an orthographic perspective
     /// matrix, it can be anything (perspective projection, etc.).
+    ///
+    /// Only the main camera uses it, cameras added with `add_camera` and the cameras of render
+    /// targets always use an orthographic projection.
     pub matrix_fn: Option<CameraMatrixFn>,
     /// Override config allowing to disable matrix_fn even when one is provided.
     /// Useful for debugging.
@@ -189,6 +199,9 @@ impl MainCamera {
             zoom,
             desired_zoom: zoom,
 
+            viewport: Viewport::FULL,
+            layers: RenderLayers::ALL,
+
             history_stack: Vec::new(),
 
             matrix_fn: None,
@@ -201,7 +214,9 @@ impl MainCamera {
         self.shake_timer = self.shake_timer.max(0.0);
         self.recoil = (self.recoil - delta).max(0.0);
 
-        set_px(self.zoom / screen_width());
+        let resolution =
+            render_resolution(vec2(screen_width(), screen_height()));
+        set_px(self.zoom / resolution.x);
 
         if let Some(player_position) = self.target {
             let deadzone_hw = self.deadzone_width / 2.0;
@@ -227,8 +242,29 @@ impl MainCamera {
             self.center = player_position;
         }
 
-        CAMERA_BOUNDS
-            .store(Rect { center: self.center, size: self.world_viewport() });
+        // Culling has to keep everything visible by any of the cameras.
+        let mut bounds =
+            AABB::from_center_size(self.center, self.world_viewport());
+
+        for (_, camera) in cameras() {
+            if camera.enabled {
+                bounds.expand_to_include_aabb(&AABB::from_center_size(
+                    camera.center,
+                    camera.world_viewport(),
+                ));
+            }
+        }
+
+        for (_, params) in render_targets() {
+            if let Some(camera) = params.camera {
+                bounds.expand_to_include_aabb(&AABB::from_center_size(
+                    camera.center,
+                    camera.world_viewport_in(params.size.as_vec2()),
+                ));
+            }
+        }
+
+        CAMERA_BOUNDS.store(Rect::from_min_max(bounds.min, bounds.max));
     }
 
     pub fn push_center(&mut self, new_center: Vec2, new_zoom: f32) {
@@ -294,7 +330,14 @@ impl MainCamera {
             (0.0, 0.0)
         };
 
-        let center = self.center + vec2(sx, sy);
+        let resolution =
+            render_resolution(vec2(screen_width(), screen_height()));
+
+        let center = snap_camera_center(
+            self.center + vec2(sx, sy),
+            self.world_viewport(),
+            self.viewport.to_pixels(resolution).size(),
+        );
 
         let ortho_camera = Mat4::orthographic_rh(
             center.x - hx,
@@ -317,35 +360,21 @@ impl MainCamera {
     }
 
     pub fn screen_to_world(&self, position: Vec2) -> Vec2 {
-        let viewport = self.world_viewport();
-        let camera_center = self.center;
-
-        let state = GLOBAL_STATE.borrow();
-
-        let normalized = position / state.screen_size;
-        let normalized = vec2(normalized.x, 1.0 - normalized.y);
-
-        let world_unoffset = (normalized - 0.5) * viewport;
-
-        world_unoffset + camera_center
+        viewport_screen_to_world(
+            self.viewport,
+            self.center,
+            self.world_viewport(),
+            position,
+        )
     }
 
     pub fn world_to_screen(&self, position: Vec2) -> Vec2 {
-        let viewport = self.world_viewport();
-
-        let position = position - self.center;
-
-        let state = GLOBAL_STATE.borrow();
-
-        let moved = position + viewport / 2.0;
-        let normalized = moved / viewport;
-        let normalized = vec2(normalized.x, 1.0 - normalized.y);
-
-        normalized * state.screen_size
-        // vec2(
-        //     normalized.x * state.screen_size.x / state.egui_scale_factor,
-        //     normalized.y * state.screen_size.y / state.egui_scale_factor,
-        // )
+        viewport_world_to_screen(
+            self.viewport,
+            self.center,
+            self.world_viewport(),
+            position,
+        )
     }
 
     pub fn world_to_render_px(
@@ -0,0 +1,259 @@
+use crate::*;
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+static CAMERAS: Lazy<AtomicRefCell<BTreeMap<CameraId, Camera>>> =
+    Lazy::new(|| AtomicRefCell::new(BTreeMap::new()));
+
+static NEXT_CAMERA_ID: AtomicU32 = AtomicU32::new(1);
+
+/// Id of an additional camera created with `add_camera`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct CameraId(pub u32);
+
+/// Part of the screen a camera draws into, in normalized coordinates with
+/// `(0, 0)` at the top left corner and `(1, 1)` at the bottom right.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Viewport {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Viewport {
+    pub const FULL: Self = Self::new(0.0, 0.0, 1.0, 1.0);
+
+    pub const LEFT_HALF: Self = Self::new(0.0, 0.0, 0.5, 1.0);
+    pub const RIGHT_HALF: Self = Self::new(0.5, 0.0, 0.5, 1.0);
+    pub const TOP_HALF: Self = Self::new(0.0, 0.0, 1.0, 0.5);
+    pub const BOTTOM_HALF: Self = Self::new(0.0, 0.5, 1.0, 0.5);
+
+    pub const fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self { x, y, width, height }
+    }
+
+    /// The viewport in pixels of a screen (or render target) of the given
+    /// size.
+    pub fn to_pixels(&self, screen_size: Vec2) -> AABB {
+        let min = vec2(self.x, self.y) * screen_size;
+        AABB::new(min, min + vec2(self.width, self.height) * screen_size)
+    }
+
+    /// Width divided by height of the viewport on a screen of the given
+    /// size.
+    pub fn aspect_ratio(&self, screen_size: Vec2) -> f32 {
+        let size = self.to_pixels(screen_size).size();
+        size.x / size.y.max(1.0)
+    }
+}
+
+/// World space rectangle visible through an orthographic view projection
+/// matrix, e.g. one built by `Camera::build_view_projection_matrix`.
+pub fn orthographic_world_bounds(view_proj: Mat4) -> AABB {
+    let inverse = view_proj.inverse();
+
+    [vec2(-1.0, -1.0), vec2(-1.0, 1.0), vec2(1.0, 1.0), vec2(1.0, -1.0)]
+        .into_iter()
+        .map(|ndc| inverse.project_point3(ndc.extend(0.0)).truncate())
+        .fold(
+            AABB::new(splat(f32::INFINITY), splat(f32::NEG_INFINITY)),
+            |aabb, point| AABB::new(aabb.min.min(point), aabb.max.max(point)),
+        )
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Self::FULL
+    }
+}
+
+/// An additional camera drawn after (or before) the main camera, e.g. the
+/// second player's half of the screen or a minimap in a corner.
+///
+/// Every camera draws the same scene, so things only have to be drawn once
+/// per frame no matter how many cameras there are. Render targets are drawn
+/// separately, see `RenderTargetParams::camera`.
+///
+/// The projection is always orthographic, `MainCamera::matrix_fn` is only
+/// used by the main camera.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Camera {
+    pub center: Vec2,
+    /// Width of the visible area in world units, the height follows from
+    /// the aspect ratio of the viewport.
+    pub zoom: f32,
+    pub viewport: Viewport,
+    /// Render layers the camera draws, see `use_render_layers`.
+    pub layers: RenderLayers,
+    /// Cameras are drawn in ascending order, later ones on top. The main
+    /// camera has order 0 and is drawn first among cameras of equal order.
+    pub order: i32,
+    pub enabled: bool,
+}
+
+impl Camera {
+    pub fn new(center: Vec2, zoom: f32, viewport: Viewport) -> Self {
+        Self {
+            center,
+            zoom,
+            viewport,
+            layers: RenderLayers::ALL,
+            order: 0,
+            enabled: true,
+        }
+    }
+
+    pub fn with_layers(self, layers: RenderLayers) -> Self {
+        Self { layers, ..self }
+    }
+
+    pub fn with_order(self, order: i32) -> Self {
+        Self { order, ..self }
+    }
+
+    pub fn world_viewport(&self) -> Vec2 {
+        self.world_viewport_in(render_size())
+    }
+
+    /// Size of the visible area in world units when drawing into a screen
+    /// or render target of the given size.
+    pub fn world_viewport_in(&self, target_size: Vec2) -> Vec2 {
+        let aspect_ratio = self.viewport.aspect_ratio(target_size);
+        vec2(self.zoom, self.zoom / aspect_ratio)
+    }
+
+    pub fn build_view_projection_matrix(&self) -> Mat4 {
+        self.build_view_projection_matrix_in(render_size())
+    }
+
+    pub fn build_view_projection_matrix_in(&self, target_size: Vec2) -> Mat4 {
+        let world_viewport = self.world_viewport_in(target_size);
+        let half = world_viewport / 2.0;
+        let range = 1000.0;
+
+        let center = snap_camera_center(
+            self.center,
+            world_viewport,
+            self.viewport.to_pixels(target_size).size(),
+        );
+
+        Mat4::orthographic_rh(
+            center.x - half.x,
+            center.x + half.x,
+            center.y - half.y,
+            center.y + half.y,
+            -range,
+            range,
+        )
+    }
+
+    pub fn screen_to_world(&self, position: Vec2) -> Vec2 {
+        viewport_screen_to_world(
+            self.viewport,
+            self.center,
+            self.world_viewport(),
+            position,
+        )
+    }
+
+    pub fn world_to_screen(&self, position: Vec2) -> Vec2 {
+        viewport_world_to_screen(
+            self.viewport,
+            self.center,
+            self.world_viewport(),
+            position,
+        )
+    }
+
+    /// True if the screen position (e.g. the mouse) is inside the camera's
+    /// viewport.
+    pub fn contains_screen_point(&self, position: Vec2) -> bool {
+        self.viewport
+            .to_pixels(render_size())
+            .contains(window_to_render(position, screen_size()))
+    }
+}
+
+/// Adds a camera that is drawn every frame until removed with
+/// `remove_camera`.
+pub fn add_camera(camera: Camera) -> CameraId {
+    let id = CameraId(NEXT_CAMERA_ID.fetch_add(1, Ordering::SeqCst));
+    CAMERAS.borrow_mut().insert(id, camera);
+    id
+}
+
+pub fn remove_camera(id: CameraId) {
+    CAMERAS.borrow_mut().remove(&id);
+}
+
+pub fn camera(id: CameraId) -> Option<Camera> {
+    CAMERAS.borrow().get(&id).copied()
+}
+
+/// Replaces a camera previously added with `add_camera`, e.g. to move it
+/// after the player it follows.
+pub fn set_camera(id: CameraId, camera: Camera) {
+    match CAMERAS.borrow_mut().get_mut(&id) {
+        Some(existing) => *existing = camera,
+        None => error!("Camera {:?} doesn't exist", id),
+    }
+}
+
+/// All additional cameras, not including the main camera.
+pub fn cameras() -> Vec<(CameraId, Camera)> {
+    CAMERAS.borrow().iter().map(|(id, camera)| (*id, *camera)).collect()
+}
+
+fn screen_size() -> Vec2 {
+    GLOBAL_STATE.borrow().screen_size
+}
+
+/// Size of what the cameras draw into, see `render_resolution`.
+fn render_size() -> Vec2 {
+    render_resolution(screen_size())
+}
+
+pub(crate) fn viewport_screen_to_world(
+    viewport: Viewport,
+    center: Vec2,
+    world_viewport: Vec2,
+    position: Vec2,
+) -> Vec2 {
+    let rect = viewport.to_pixels(render_size());
+    let position = window_to_render(position, screen_size());
+
+    let normalized = (position - rect.min) / rect.size().max(Vec2::ONE);
+    let normalized = vec2(normalized.x, 1.0 - normalized.y);
+
+    (normalized - 0.5) * world_viewport + center
+}
+
+pub(crate) fn viewport_world_to_screen(
+    viewport: Viewport,
+    center: Vec2,
+    world_viewport: Vec2,
+    position: Vec2,
+) -> Vec2 {
+    let rect = viewport.to_pixels(render_size());
+
+    let normalized =
+        (position - center + world_viewport / 2.0) / world_viewport;
+    let normalized = vec2(normalized.x, 1.0 - normalized.y);
+
+    render_to_window(rect.min + normalized * rect.size(), screen_size())
+}
+
+#[test]
+fn test_viewport_to_pixels() {
+    let screen = vec2(1920.0, 1080.0);
+
+    assert_eq!(
+        Viewport::RIGHT_HALF.to_pixels(screen),
+        AABB::new(vec2(960.0, 0.0), vec2(1920.0, 1080.0))
+    );
+
+    assert_eq!(Viewport::FULL.aspect_ratio(screen), 1920.0 / 1080.0);
+    assert_eq!(Viewport::LEFT_HALF.aspect_ratio(screen), 960.0 / 1080.0);
+}
@@ -0,0 +1,290 @@
+use crate::*;
+
+use std::sync::atomic::{AtomicU32, Ordering};
+
+static CLIP_STATES: Lazy<AtomicRefCell<Vec<ClipState>>> =
+    Lazy::new(|| AtomicRefCell::new(vec![ClipState::default()]));
+
+static CLIP_STACK: Lazy<AtomicRefCell<Vec<ClipId>>> =
+    Lazy::new(|| AtomicRefCell::new(Vec::new()));
+
+static NEXT_STENCIL_MASK: AtomicU32 = AtomicU32::new(1);
+
+/// Index of a `ClipState` used by draws of the current frame. The default
+/// `ClipId(0)` means nothing is clipped.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ClipId(pub u32);
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ClipRect {
+    /// Rectangle in world coordinates, moves together with the camera.
+    World(AABB),
+    /// Rectangle in screen pixels, with `(0, 0)` at the top left corner.
+    /// Cameras drawing into a part of the screen offset it by the top left
+    /// corner of their viewport.
+    Screen(AABB),
+}
+
+impl ClipRect {
+    /// Converts the rectangle into pixels of the image being drawn into.
+    /// World rects are projected with the `view_proj` matrix of the camera
+    /// drawing them into its `viewport` (in pixels of the image), screen
+    /// rects are converted with `screen_to_target`.
+    pub fn to_target(
+        &self,
+        view_proj: Mat4,
+        viewport: AABB,
+        screen_to_target: impl Fn(Vec2) -> Vec2,
+    ) -> AABB {
+        match *self {
+            ClipRect::World(rect) => {
+                let to_target = |point: Vec2| {
+                    let ndc = view_proj.project_point3(point.extend(0.0));
+                    let normalized = vec2(ndc.x + 1.0, 1.0 - ndc.y) / 2.0;
+
+                    viewport.min + normalized * viewport.size()
+                };
+
+                // All corners, since the camera could be rotated.
+                [
+                    rect.min,
+                    vec2(rect.min.x, rect.max.y),
+                    rect.max,
+                    vec2(rect.max.x, rect.min.y),
+                ]
+                .into_iter()
+                .map(to_target)
+                .fold(
+                    AABB::new(splat(f32::INFINITY), splat(f32::NEG_INFINITY)),
+                    |aabb, point| {
+                        AABB::new(aabb.min.min(point), aabb.max.max(point))
+                    },
+                )
+            }
+            ClipRect::Screen(rect) => {
+                AABB::new(
+                    screen_to_target(rect.min),
+                    screen_to_target(rect.max),
+                )
+            }
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum StencilMode {
+    #[default]
+    None,
+    /// Draws only into the stencil buffer, marking the covered pixels with
+    /// the mask's value. Nothing is visible on screen.
+    Write,
+    /// Draws only where the mask was drawn.
+    Inside,
+    /// Draws only where the mask wasn't drawn.
+    Outside,
+}
+
+/// Stencil mask created with `begin_stencil_mask`, valid until the end of
+/// the frame.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct StencilMask(pub u8);
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ClipState {
+    pub rects: Vec<ClipRect>,
+    pub stencil: StencilMode,
+    pub mask: u8,
+}
+
+impl ClipState {
+    /// Intersection of all clip rects in pixels of the image being drawn
+    /// into, see `ClipRect::to_target`. `None` if no clip rect is active,
+    /// rects that don't overlap result in an empty rect.
+    pub fn target_rect(
+        &self,
+        view_proj: Mat4,
+        viewport: AABB,
+        screen_to_target: impl Fn(Vec2) -> Vec2,
+    ) -> Option<AABB> {
+        self.rects
+            .iter()
+            .map(|rect| rect.to_target(view_proj, viewport, &screen_to_target))
+            .reduce(|a, b| {
+                let min = a.min.max(b.min);
+                AABB::new(min, a.max.min(b.max).max(min))
+            })
+    }
+}
+
+/// Restricts everything drawn until the matching `pop_clip_rect` to the
+/// given rectangle. Nested clip rects are intersected.
+pub fn push_clip_rect(rect: ClipRect) {
+    push_clip_state(|state| state.rects.push(rect));
+}
+
+pub fn pop_clip_rect() {
+    pop_clip_state("pop_clip_rect");
+}
+
+/// Starts drawing a stencil mask. Everything drawn until `end_stencil_mask`
+/// only marks its shape in the stencil buffer (mostly transparent pixels
+/// are ignored) and isn't visible.
+///
+/// The mask is then used with `push_stencil_mask` or
+/// `push_inverted_stencil_mask`. Masks have to be drawn with a z-index lower
+/// or equal to the content they mask, into the same render target. Every
+/// render target has its own stencil buffer, and targets with a camera also
+/// get the masks and masked draws from the screen.
+pub fn begin_stencil_mask() -> StencilMask {
+    let value = NEXT_STENCIL_MASK.fetch_add(1, Ordering::SeqCst);
+
+    let mask = if value > u8::MAX as u32 {
+        error!("Only 255 stencil masks can be used in a single frame");
+        u8::MAX
+    } else {
+        value as u8
+    };
+
+    push_clip_state(|state| {
+        state.stencil = StencilMode::Write;
+        state.mask = mask;
+    });
+
+    StencilMask(mask)
+}
+
+pub fn end_stencil_mask() {
+    pop_clip_state("end_stencil_mask");
+}
+
+/// Restricts everything drawn until `pop_stencil_mask` to the inside of the
+/// mask, e.g. for portholes or scrolling panels with irregular shapes.
+pub fn push_stencil_mask(mask: StencilMask) {
+    push_clip_state(|state| {
+        state.stencil = StencilMode::Inside;
+        state.mask = mask.0;
+    });
+}
+
+/// Restricts everything drawn until `pop_stencil_mask` to the outside of
+/// the mask, e.g. for fog that is revealed around the player.
+pub fn push_inverted_stencil_mask(mask: StencilMask) {
+    push_clip_state(|state| {
+        state.stencil = StencilMode::Outside;
+        state.mask = mask.0;
+    });
+}
+
+pub fn pop_stencil_mask() {
+    pop_clip_state("pop_stencil_mask");
+}
+
+pub fn get_current_clip() -> ClipId {
+    CLIP_STACK.borrow().last().copied().unwrap_or_default()
+}
+
+pub fn get_clip_state(id: ClipId) -> ClipState {
+    CLIP_STATES.borrow().get(id.0 as usize).cloned().unwrap_or_default()
+}
+
+/// Clip state of everything drawn right now, e.g. for things which are
+/// queued for drawing in later frames, like particles.
+pub fn get_current_clip_state() -> ClipState {
+    get_clip_state(get_current_clip())
+}
+
+/// `ClipId` of the clip state in the current frame, adding it if nothing
+/// used it yet. Stencil masks are matched by their value, which stays the
+/// same as long as masks are created in the same order every frame.
+pub fn clip_state_id(state: &ClipState) -> ClipId {
+    let mut states = CLIP_STATES.borrow_mut();
+
+    match states.iter().position(|x| x == state) {
+        Some(index) => ClipId(index as u32),
+        None => {
+            states.push(state.clone());
+            ClipId(states.len() as u32 - 1)
+        }
+    }
+}
+
+/// Resets clip states at the end of the frame. Intended for internal use.
+pub fn clear_clip_states() {
+    let mut stack = CLIP_STACK.borrow_mut();
+
+    if !stack.is_empty() {
+        warn!(
+            "{} clip rects or stencil masks weren't popped this frame",
+            stack.len()
+        );
+        stack.clear();
+    }
+
+    CLIP_STATES.borrow_mut().truncate(1);
+    NEXT_STENCIL_MASK.store(1, Ordering::SeqCst);
+}
+
+fn push_clip_state(f: impl FnOnce(&mut ClipState)) {
+    let mut state = get_current_clip_state();
+    f(&mut state);
+
+    let id = clip_state_id(&state);
+    CLIP_STACK.borrow_mut().push(id);
+}
+
+fn pop_clip_state(caller: &str) {
+    if CLIP_STACK.borrow_mut().pop().is_none() {
+        error!("{} called without a matching push", caller);
+    }
+}
+
+#[test]
+fn test_clip_rects_are_intersected() {
+    let size = vec2(400.0, 300.0);
+    let target_rect = |state: &ClipState| {
+        state.target_rect(Mat4::IDENTITY, AABB::new(Vec2::ZERO, size), |x| x)
+    };
+
+    let state = ClipState {
+        rects: vec![
+            ClipRect::Screen(AABB::new(vec2(0.0, 0.0), vec2(100.0, 50.0))),
+            ClipRect::Screen(AABB::new(vec2(20.0, 10.0), vec2(200.0, 200.0))),
+        ],
+        ..Default::default()
+    };
+
+    assert_eq!(
+        target_rect(&state),
+        Some(AABB::new(vec2(20.0, 10.0), vec2(100.0, 50.0)))
+    );
+
+    let disjoint = ClipState {
+        rects: vec![
+            ClipRect::Screen(AABB::new(vec2(0.0, 0.0), vec2(10.0, 10.0))),
+            ClipRect::Screen(AABB::new(vec2(20.0, 20.0), vec2(30.0, 30.0))),
+        ],
+        ..Default::default()
+    };
+
+    assert_eq!(target_rect(&disjoint).unwrap().size(), Vec2::ZERO);
+    assert_eq!(target_rect(&ClipState::default()), None);
+}
+
+#[test]
+fn test_world_clip_rect_uses_camera() {
+    let view_proj =
+        Mat4::orthographic_rh(-10.0, 10.0, -5.0, 5.0, -1000.0, 1000.0);
+    let rect = ClipRect::World(AABB::new(vec2(-10.0, -5.0), vec2(0.0, 0.0)));
+    let size = vec2(200.0, 100.0);
+
+    assert_eq!(
+        rect.to_target(view_proj, AABB::new(Vec2::ZERO, size), |x| x),
+        AABB::new(vec2(0.0, 50.0), vec2(100.0, 100.0))
+    );
+
+    // A camera drawing into the right half of the target.
+    assert_eq!(
+        rect.to_target(view_proj, AABB::new(vec2(100.0, 0.0), size), |x| x),
+        AABB::new(vec2(100.0, 50.0), vec2(150.0, 100.0))
+    );
+}
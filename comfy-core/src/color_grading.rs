@@ -0,0 +1,379 @@
+use crate::*;
+
+use std::sync::atomic::{AtomicU32, Ordering};
+
+static GENERATED_COLOR_LUT_IDS: AtomicU32 = AtomicU32::new(1);
+
+static COLOR_LUT_CREATIONS: Lazy<
+    AtomicRefCell<Vec<(ColorLutHandle, ColorLut)>>,
+> = Lazy::new(|| AtomicRefCell::new(Vec::new()));
+
+/// Largest `LUT_3D_SIZE` accepted by `ColorLut::from_cube`, common LUTs
+/// are 17, 33 or 65.
+pub const MAX_COLOR_LUT_SIZE: u32 = 256;
+
+static COLOR_GRADING: Lazy<AtomicRefCell<ColorGrading>> =
+    Lazy::new(|| AtomicRefCell::new(ColorGrading::default()));
+
+/// A 3D color lookup table used for color grading. LUTs map gamma encoded
+/// colors, like the images they're usually made from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColorLut {
+    pub size: u32,
+    /// RGBA colors with red changing fastest, then green, then blue, the same
+    /// order as `.cube` files use.
+    pub data: Vec<[u8; 4]>,
+    /// Range of the input colors the table covers, `0..1` unless a `.cube`
+    /// file sets a different one. Colors are remapped from it before the
+    /// lookup.
+    pub domain_min: Vec3,
+    pub domain_max: Vec3,
+}
+
+impl ColorLut {
+    /// A LUT which leaves all colors unchanged.
+    pub fn identity(size: u32) -> Self {
+        let size = size.max(2);
+        let max = (size - 1) as f32;
+
+        let data = (0..size * size * size)
+            .map(|i| {
+                let r = i % size;
+                let g = i / size % size;
+                let b = i / (size * size);
+
+                [
+                    (r as f32 / max * 255.0).round() as u8,
+                    (g as f32 / max * 255.0).round() as u8,
+                    (b as f32 / max * 255.0).round() as u8,
+                    255,
+                ]
+            })
+            .collect();
+
+        Self { size, data, domain_min: Vec3::ZERO, domain_max: Vec3::ONE }
+    }
+
+    /// Parses an Adobe/Resolve `.cube` file with a `LUT_3D_SIZE`.
+    /// `DOMAIN_MIN`/`DOMAIN_MAX` and Resolve's `LUT_3D_INPUT_RANGE` set the
+    /// input domain, other keywords that aren't supported are ignored.
+    pub fn from_cube(source: &str) -> Result<Self> {
+        let mut size = None;
+        let mut domain_min = Vec3::ZERO;
+        let mut domain_max = Vec3::ONE;
+        let mut data = Vec::new();
+
+        for line in source.lines() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut parts = line.split_whitespace();
+            let keyword = parts.next().unwrap();
+
+            let mut parse_vec3 = || -> Result<Vec3> {
+                let values = parts
+                    .by_ref()
+                    .map(|x| x.parse::<f32>())
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                if values.len() != 3 {
+                    bail!("Expected 3 values in .cube line '{}'", line);
+                }
+
+                Ok(Vec3::new(values[0], values[1], values[2]))
+            };
+
+            match keyword {
+                "TITLE" => {}
+                "LUT_1D_SIZE" => bail!("1D LUTs are not supported"),
+                "LUT_3D_SIZE" => {
+                    let value = parts.next().unwrap_or("").parse::<u32>()?;
+
+                    if !(2..=MAX_COLOR_LUT_SIZE).contains(&value) {
+                        bail!(
+                            "LUT_3D_SIZE must be between 2 and {}, got {}",
+                            MAX_COLOR_LUT_SIZE,
+                            value
+                        );
+                    }
+
+                    size = Some(value);
+                }
+                "DOMAIN_MIN" => domain_min = parse_vec3()?,
+                "DOMAIN_MAX" => domain_max = parse_vec3()?,
+                "LUT_3D_INPUT_RANGE" => {
+                    let values = parts
+                        .by_ref()
+                        .map(|x| x.parse::<f32>())
+                        .collect::<Result<Vec<_>, _>>()?;
+
+                    let [min, max] = values[..] else {
+                        bail!("Expected 2 values in .cube line '{}'", line);
+                    };
+
+                    domain_min = Vec3::splat(min);
+                    domain_max = Vec3::splat(max);
+                }
+                // Other keywords, e.g. `LUT_IN_VIDEO_RANGE`, are ignored.
+                _ if keyword.starts_with(char::is_alphabetic) => {}
+                _ => {
+                    let color = Vec3::new(
+                        keyword.parse()?,
+                        parts.next().unwrap_or("").parse()?,
+                        parts.next().unwrap_or("").parse()?,
+                    );
+
+                    let to_u8 =
+                        |x: f32| (x.clamp(0.0, 1.0) * 255.0).round() as u8;
+
+                    data.push([
+                        to_u8(color.x),
+                        to_u8(color.y),
+                        to_u8(color.z),
+                        255,
+                    ]);
+                }
+            }
+        }
+
+        let Some(size) = size else {
+            bail!("Missing LUT_3D_SIZE in .cube file");
+        };
+
+        let Some(entries) =
+            size.checked_mul(size).and_then(|x| x.checked_mul(size))
+        else {
+            bail!("LUT_3D_SIZE {} is too large", size);
+        };
+
+        if data.len() != entries as usize {
+            bail!(
+                "Expected {} entries in a .cube file of size {}, found {}",
+                entries,
+                size,
+                data.len()
+            );
+        }
+
+        if domain_min.cmpge(domain_max).any() {
+            bail!(
+                "The .cube domain minimum {} must be below the maximum {}",
+                domain_min,
+                domain_max
+            );
+        }
+
+        Ok(Self { size, data, domain_min, domain_max })
+    }
+
+    /// Reads a LUT laid out as square tiles of `size x size` pixels, one tile
+    /// per blue value, with red along the x axis and green along the y axis.
+    /// Covers both the common 512x512 image (8x8 tiles of 64) and horizontal
+    /// strips like 256x16.
+    pub fn from_image(image: &image::RgbaImage) -> Result<Self> {
+        let (width, height) = image.dimensions();
+        let pixels = width as u64 * height as u64;
+
+        let size = (pixels as f64).cbrt().round() as u32;
+
+        if size < 2 ||
+            (size as u64).pow(3) != pixels ||
+            width % size != 0 ||
+            height % size != 0
+        {
+            bail!("{}x{} is not a valid LUT image size", width, height);
+        }
+
+        let tiles_per_row = width / size;
+
+        let data = (0..size * size * size)
+            .map(|i| {
+                let r = i % size;
+                let g = i / size % size;
+                let b = i / (size * size);
+
+                let x = b % tiles_per_row * size + r;
+                let y = b / tiles_per_row * size + g;
+
+                let pixel = image.get_pixel(x, y).0;
+                [pixel[0], pixel[1], pixel[2], 255]
+            })
+            .collect();
+
+        Ok(Self { size, data, domain_min: Vec3::ZERO, domain_max: Vec3::ONE })
+    }
+
+    /// Loads a LUT from the bytes of an image, see `ColorLut::from_image`.
+    pub fn from_image_bytes(bytes: &[u8]) -> Result<Self> {
+        Self::from_image(&image::load_from_memory(bytes)?.to_rgba8())
+    }
+}
+
+/// Handle to a LUT uploaded to the GPU, returned by `load_color_lut`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ColorLutHandle(pub u32);
+
+/// Uploads the LUT at the start of the next frame.
+pub fn load_color_lut(lut: ColorLut) -> ColorLutHandle {
+    let handle =
+        ColorLutHandle(GENERATED_COLOR_LUT_IDS.fetch_add(1, Ordering::SeqCst));
+
+    COLOR_LUT_CREATIONS.borrow_mut().push((handle, lut));
+
+    handle
+}
+
+/// Internal use only, used by the renderer to upload new LUTs.
+pub fn consume_color_lut_creations() -> Vec<(ColorLutHandle, ColorLut)> {
+    let mut creations = COLOR_LUT_CREATIONS.borrow_mut();
+    let mut new_data = Vec::new();
+    std::mem::swap(&mut new_data, &mut *creations);
+
+    new_data
+}
+
+#[derive(Copy, Clone, Debug)]
+struct ColorGrading {
+    current: Option<ColorLutHandle>,
+    previous: Option<ColorLutHandle>,
+    weight: f32,
+    crossfade_start: f64,
+    crossfade_duration: f32,
+}
+
+impl Default for ColorGrading {
+    fn default() -> Self {
+        Self {
+            current: None,
+            previous: None,
+            weight: 1.0,
+            crossfade_start: 0.0,
+            crossfade_duration: 0.0,
+        }
+    }
+}
+
+/// Color grading applied at the end of post processing, as returned by
+/// `color_grading`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ColorGradingState {
+    /// LUT being faded in, `None` is the identity.
+    pub current: Option<ColorLutHandle>,
+    /// LUT being faded out, `None` is the identity.
+    pub previous: Option<ColorLutHandle>,
+    /// How far the crossfade from `previous` to `current` is, from 0 to 1.
+    pub crossfade: f32,
+    /// Blend between the original and the graded colors.
+    pub weight: f32,
+}
+
+impl ColorGradingState {
+    pub fn is_active(&self) -> bool {
+        self.weight > 0.0 &&
+            (self.current.is_some() ||
+                (self.previous.is_some() && self.crossfade < 1.0))
+    }
+}
+
+/// Switches to the given LUT immediately, `None` disables color grading.
+pub fn set_color_lut(lut: Option<ColorLutHandle>) {
+    crossfade_color_lut(lut, 0.0);
+}
+
+/// Fades from the current LUT to the given one over `duration` seconds, e.g.
+/// for day/night transitions. Fading to `None` fades grading out.
+pub fn crossfade_color_lut(lut: Option<ColorLutHandle>, duration: f32) {
+    let mut grading = COLOR_GRADING.borrow_mut();
+
+    grading.previous = grading.current;
+    grading.current = lut;
+    grading.crossfade_start = get_time();
+    grading.crossfade_duration = duration;
+}
+
+/// Blend weight between the original colors (0) and the graded ones (1).
+pub fn set_color_lut_weight(weight: f32) {
+    COLOR_GRADING.borrow_mut().weight = weight.clamp(0.0, 1.0);
+}
+
+pub fn color_grading() -> ColorGradingState {
+    let grading = *COLOR_GRADING.borrow();
+
+    let crossfade = if grading.crossfade_duration > 0.0 {
+        ((get_time() - grading.crossfade_start) as f32 /
+            grading.crossfade_duration)
+            .clamp(0.0, 1.0)
+    } else {
+        1.0
+    };
+
+    ColorGradingState {
+        current: grading.current,
+        previous: grading.previous,
+        crossfade,
+        weight: grading.weight,
+    }
+}
+
+#[test]
+fn test_color_lut_parsing() {
+    let cube = "
+        # Swaps the red and blue channels
+        TITLE \"swap\"
+        LUT_3D_SIZE 2
+        LUT_3D_INPUT_RANGE 0.0 1.0
+        LUT_IN_VIDEO_RANGE
+
+        0.0 0.0 0.0
+        0.0 0.0 1.0
+        0.0 1.0 0.0
+        0.0 1.0 1.0
+        1.0 0.0 0.0
+        1.0 0.0 1.0
+        1.0 1.0 0.0
+        1.0 1.0 1.0
+    ";
+
+    let lut = ColorLut::from_cube(cube).unwrap();
+    assert_eq!(lut.size, 2);
+    assert_eq!(lut.data[1], [0, 0, 255, 255]);
+    assert_eq!(lut.data[4], [255, 0, 0, 255]);
+
+    assert!(ColorLut::from_cube("LUT_3D_SIZE 2\n0 0 0").is_err());
+    assert!(ColorLut::from_cube("LUT_3D_SIZE 0").is_err());
+    assert!(ColorLut::from_cube("LUT_3D_SIZE 1\n0 0 0").is_err());
+    assert!(ColorLut::from_cube("LUT_3D_SIZE 4294967295").is_err());
+    assert!(ColorLut::from_cube("LUT_3D_INPUT_RANGE 0.0").is_err());
+
+    // The domain describes the input colors, the table is kept as it is.
+    let ranged = cube.replace("INPUT_RANGE 0.0 1.0", "INPUT_RANGE 0.0 2.0");
+    let lut = ColorLut::from_cube(&ranged).unwrap();
+    assert_eq!(lut.data[4], [255, 0, 0, 255]);
+    assert_eq!(lut.domain_min, Vec3::ZERO);
+    assert_eq!(lut.domain_max, Vec3::splat(2.0));
+
+    let domain = cube.replace(
+        "LUT_3D_INPUT_RANGE 0.0 1.0",
+        "DOMAIN_MIN 0.0 0.0 -1.0\nDOMAIN_MAX 1.0 2.0 1.0",
+    );
+    let lut = ColorLut::from_cube(&domain).unwrap();
+    assert_eq!(lut.data[1], [0, 0, 255, 255]);
+    assert_eq!(lut.domain_min, vec3(0.0, 0.0, -1.0));
+    assert_eq!(lut.domain_max, vec3(1.0, 2.0, 1.0));
+
+    let empty = cube.replace("INPUT_RANGE 0.0 1.0", "INPUT_RANGE 1.0 1.0");
+    assert!(ColorLut::from_cube(&empty).is_err());
+
+    // A 4x4x4 identity LUT as a horizontal 16x4 strip.
+    let identity = ColorLut::identity(4);
+    let strip = image::RgbaImage::from_fn(16, 4, |x, y| {
+        let (r, g, b) = (x % 4, y, x / 4);
+        image::Rgba(identity.data[(r + g * 4 + b * 16) as usize])
+    });
+
+    assert_eq!(ColorLut::from_image(&strip).unwrap(), identity);
+    assert!(ColorLut::from_image(&image::RgbaImage::new(10, 10)).is_err());
+}
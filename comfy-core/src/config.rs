@@ -106,6 +106,21 @@ pub struct GameConfig {
     pub vsync_enabled: bool,
     pub desired_maximum_frame_latency: u32,
 
+    /// Batches sprites of different textures at the same z-index together
+    /// by binding up to 8 textures at once as a binding array, with each
+    /// vertex/instance storing the index of its texture. This greatly
+    /// reduces the number of draw calls when many textures are interleaved.
+    ///
+    /// Requires `TEXTURE_BINDING_ARRAY` and non-uniform indexing support,
+    /// when the adapter doesn't have them (e.g. on WebGL) the regular per
+    /// texture batching is used. Only takes effect on game launch.
+    pub bindless_batching: bool,
+
+    /// Draws the world into a fixed low resolution that is scaled up to the
+    /// window by the largest integer factor that fits, with black bars
+    /// around it. Can be changed while the game is running.
+    pub pixel_perfect: Option<PixelPerfectConfig>,
+
     pub bloom_enabled: bool,
     pub tonemapping_enabled: bool,
     pub lighting: GlobalLightingParams,
@@ -157,6 +172,9 @@ impl Default for GameConfig {
             vsync_enabled: true,
             desired_maximum_frame_latency: 2,
 
+            bindless_batching: false,
+            pixel_perfect: None,
+
             bloom_enabled: false,
             tonemapping_enabled: false,
             lighting: GlobalLightingParams::default(),
@@ -179,6 +197,21 @@ impl Default for GameConfig {
     }
 }
 
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PixelPerfectConfig {
+    /// Resolution the world is drawn in, e.g. `uvec2(320, 180)`.
+    pub resolution: UVec2,
+    /// Rounds the camera position to whole pixels of the low resolution,
+    /// which keeps pixel art from shimmering while the camera moves.
+    pub snap_camera: bool,
+}
+
+impl PixelPerfectConfig {
+    pub fn new(resolution: UVec2) -> Self {
+        Self { resolution, snap_camera: true }
+    }
+}
+
 #[derive(Copy, Clone, Debug)]
 pub struct DevConfig {
     pub show_lighting_config: bool,
@@ -4,8 +4,12 @@
 mod asset_loader;
 mod assets;
 mod audio;
+mod blend;
 mod blood_canvas;
 mod camera;
+mod cameras;
+mod clip;
+mod color_grading;
 mod config;
 #[cfg(not(target_arch = "wasm32"))]
 mod desktop;
@@ -14,24 +18,38 @@ mod events;
 mod fast_sprite;
 mod global_state;
 mod input;
+mod light_cookies;
+mod light_grid;
 mod lighting;
 mod math;
+mod normal_maps;
 mod perf_counters;
+mod pixel_perfect;
 mod quad;
 pub mod random;
+mod render_layers;
 mod render_queues;
+mod render_targets;
 mod shaders;
+mod shadows;
 pub mod spatial_hash;
+mod sprite_instance;
 mod task_timer;
 mod text;
+mod texture_settings;
+mod tilemap;
 mod timer;
 mod tween;
 
 pub use crate::asset_loader::*;
 pub use crate::assets::*;
 pub use crate::audio::*;
+pub use crate::blend::*;
 pub use crate::blood_canvas::*;
 pub use crate::camera::*;
+pub use crate::cameras::*;
+pub use crate::clip::*;
+pub use crate::color_grading::*;
 pub use crate::config::*;
 #[cfg(not(target_arch = "wasm32"))]
 pub use crate::desktop::*;
@@ -40,15 +58,25 @@ pub use crate::events::*;
 pub use crate::fast_sprite::*;
 pub use crate::global_state::*;
 pub use crate::input::*;
+pub use crate::light_cookies::*;
+pub use crate::light_grid::*;
 pub use crate::lighting::*;
 pub use crate::math::*;
+pub use crate::normal_maps::*;
 pub use crate::perf_counters::*;
+pub use crate::pixel_perfect::*;
 pub use crate::quad::*;
 pub use crate::random::*;
+pub use crate::render_layers::*;
 pub use crate::render_queues::*;
+pub use crate::render_targets::*;
 pub use crate::shaders::*;
+pub use crate::shadows::*;
+pub use crate::sprite_instance::*;
 pub use crate::task_timer::*;
 pub use crate::text::*;
+pub use crate::texture_settings::*;
+pub use crate::tilemap::*;
 pub use crate::timer::*;
 pub use crate::tween::*;
 
@@ -564,15 +592,6 @@ pub struct SpriteDraw {
 
 pub type TextureLoadQueue = Vec<LoadedImage>;
 
-#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Ord, PartialOrd, Hash)]
-pub enum BlendMode {
-    #[default]
-    None,
-    // TODO: Rename to Add
-    Additive,
-    Alpha,
-}
-
 pub struct DrawParams<'a> {
     pub aspect_ratio: f32,
     pub projection: Mat4,
@@ -580,6 +599,7 @@ pub struct DrawParams<'a> {
 
     pub clear_color: Color,
     pub lights: Vec<Light>,
+    pub occluders: Vec<Occluder>,
 
     pub config: &'a mut GameConfig,
 
@@ -1666,7 +1686,7 @@ pub fn rescale<T: NumCast>(value: T, from: Range<T>, to: Range<T>) -> f32 {
 #[derive(Copy, Clone, Debug)]
 pub struct Velocity(pub Vec2);
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct AABB {
     pub min: Vec2,
     pub max: Vec2,
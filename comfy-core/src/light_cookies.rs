@@ -0,0 +1,99 @@
+use crate::*;
+
+/// Number of different textures which can be used as light cookies.
+pub const MAX_LIGHT_COOKIES: usize = 16;
+
+/// Cookie textures are resized to this resolution when uploaded.
+pub const LIGHT_COOKIE_SIZE: u32 = 256;
+
+/// Textures used as light cookies by their index, `None` for free slots.
+static LIGHT_COOKIES: Lazy<AtomicRefCell<Vec<Option<TextureHandle>>>> =
+    Lazy::new(|| AtomicRefCell::new(Vec::new()));
+
+/// Index of the texture among the light cookies, adding it if it wasn't used
+/// as a cookie before. Returns -1 once `MAX_LIGHT_COOKIES` textures are used.
+///
+/// Textures which no light drawn during a frame uses are removed at the start
+/// of the next one, freeing their index for other textures. The renderer
+/// looks the index up when it uploads the lights, so it's always the current
+/// one.
+pub fn light_cookie_index(texture: TextureHandle) -> i32 {
+    let mut cookies = LIGHT_COOKIES.borrow_mut();
+
+    if let Some(index) = cookies.iter().position(|x| *x == Some(texture)) {
+        return index as i32;
+    }
+
+    if let Some(index) = cookies.iter().position(|x| x.is_none()) {
+        cookies[index] = Some(texture);
+        return index as i32;
+    }
+
+    if cookies.len() >= MAX_LIGHT_COOKIES {
+        warn!(
+            "Only {} textures can be used as light cookies, ignoring {:?}",
+            MAX_LIGHT_COOKIES, texture
+        );
+
+        return -1;
+    }
+
+    cookies.push(Some(texture));
+    cookies.len() as i32 - 1
+}
+
+/// Textures used as light cookies by their index, `None` for free slots.
+pub fn light_cookies() -> Vec<Option<TextureHandle>> {
+    LIGHT_COOKIES.borrow().clone()
+}
+
+/// Frees the cookies of textures which aren't among the `used` ones.
+pub(crate) fn free_unused_light_cookies(
+    used: impl Iterator<Item = TextureHandle>,
+) {
+    let used = used.collect::<HashSet<_>>();
+
+    for cookie in LIGHT_COOKIES.borrow_mut().iter_mut() {
+        if cookie.is_some_and(|x| !used.contains(&x)) {
+            *cookie = None;
+        }
+    }
+}
+
+#[test]
+fn test_light_cookie_index() {
+    let a = TextureHandle::Raw(1);
+    let b = TextureHandle::Raw(2);
+
+    let a_index = light_cookie_index(a);
+
+    assert_eq!(light_cookie_index(a), a_index);
+    assert_eq!(light_cookie_index(b), a_index + 1);
+    assert_eq!(light_cookies()[a_index as usize], Some(a));
+
+    // Only `b` was used by a light, `a` gets freed.
+    let b_index = a_index + 1;
+    let c = TextureHandle::Raw(3);
+
+    free_unused_light_cookies([b].into_iter());
+
+    assert_eq!(light_cookies()[a_index as usize], None);
+    assert_eq!(light_cookies()[b_index as usize], Some(b));
+
+    assert_eq!(light_cookie_index(c), a_index);
+    assert_eq!(light_cookie_index(b), b_index);
+
+    // Lights keep the texture and get its index when they're uploaded, so
+    // freeing `c` in between doesn't leave them with a stale index.
+    let d = TextureHandle::Raw(4);
+    let light = Light::simple(Vec2::ZERO, 1.0, 1.0).with_cookie(c);
+
+    free_unused_light_cookies([b].into_iter());
+    assert_eq!(light_cookie_index(d), a_index);
+
+    let index = light.to_raw().cookie;
+    assert_ne!(index, a_index);
+    assert_eq!(light_cookies()[index as usize], Some(c));
+
+    assert_eq!(Light::simple(Vec2::ZERO, 1.0, 1.0).to_raw().cookie, -1);
+}
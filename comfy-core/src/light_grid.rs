@@ -0,0 +1,214 @@
+use crate::*;
+
+/// Number of cells along each axis of the `LightGrid` built every frame.
+pub const LIGHT_GRID_SIZE: u32 = 32;
+
+/// Lights binned into a uniform world space grid, which lets every pixel only
+/// go through the lights that can reach it instead of all of them.
+///
+/// The grid covers the part of the visible `bounds` reached by any light, so
+/// lights far off screen neither make its cells coarser nor end up in them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LightGrid {
+    /// World position of the grid's bottom left corner.
+    pub origin: Vec2,
+    pub cell_size: Vec2,
+    /// Number of cells along each axis.
+    pub size: UVec2,
+    /// Offset into `indices` and number of lights of every cell, row by row.
+    pub cells: Vec<[u32; 2]>,
+    /// Indices of the lights in every cell.
+    pub indices: Vec<u32>,
+}
+
+impl LightGrid {
+    /// Bins the lights reaching into `bounds`, usually the world space area
+    /// visible to all cameras.
+    pub fn new(lights: &[Light], bounds: AABB, size: u32) -> Self {
+        let empty = Self {
+            origin: Vec2::ZERO,
+            cell_size: Vec2::ONE,
+            size: UVec2::ZERO,
+            cells: vec![],
+            indices: vec![],
+        };
+
+        let light_bounds = |light: &Light| {
+            let position = Vec2::from(light.world_position);
+            let radius = splat(light.extent());
+
+            AABB::new(position - radius, position + radius)
+        };
+
+        let overlaps = |a: &AABB, b: &AABB| {
+            a.min.cmple(b.max).all() && b.min.cmple(a.max).all()
+        };
+
+        let (min, max) = lights
+            .iter()
+            .map(light_bounds)
+            .filter(|x| overlaps(x, &bounds))
+            .fold(
+                (splat(f32::INFINITY), splat(f32::NEG_INFINITY)),
+                |(min, max), x| (min.min(x.min), max.max(x.max)),
+            );
+
+        let min = min.max(bounds.min);
+        let max = max.min(bounds.max);
+
+        if size == 0 || min.cmpgt(max).any() {
+            return empty;
+        }
+
+        let mut grid = Self {
+            origin: min,
+            cell_size: ((max - min) / size as f32).max(splat(0.001)),
+            size: UVec2::splat(size),
+            cells: vec![[0, 0]; (size * size) as usize],
+            indices: vec![],
+        };
+
+        let grid_bounds = AABB::new(min, max);
+        let visible =
+            |light: &Light| overlaps(&light_bounds(light), &grid_bounds);
+
+        for light in lights.iter().filter(|x| visible(x)) {
+            for cell in grid.light_cells(light).collect_vec() {
+                grid.cells[cell][1] += 1;
+            }
+        }
+
+        let mut offset = 0;
+
+        for cell in grid.cells.iter_mut() {
+            let count = cell[1];
+            *cell = [offset, 0];
+            offset += count;
+        }
+
+        grid.indices = vec![0; offset as usize];
+
+        for (i, light) in lights.iter().enumerate().filter(|(_, x)| visible(x))
+        {
+            for cell in grid.light_cells(light).collect_vec() {
+                let [offset, count] = grid.cells[cell];
+
+                grid.indices[(offset + count) as usize] = i as u32;
+                grid.cells[cell][1] += 1;
+            }
+        }
+
+        grid
+    }
+
+    /// Indices of the lights which can reach `position`.
+    pub fn lights_at(&self, position: Vec2) -> &[u32] {
+        let cell = ((position - self.origin) / self.cell_size).floor();
+
+        if cell.cmplt(Vec2::ZERO).any() || cell.cmpge(self.size.as_vec2()).any()
+        {
+            return &[];
+        }
+
+        let [offset, count] =
+            self.cells[(cell.y as u32 * self.size.x + cell.x as u32) as usize];
+
+        &self.indices[offset as usize..(offset + count) as usize]
+    }
+
+    /// Cells overlapping the circle of the light's `extent`.
+    fn light_cells(&self, light: &Light) -> impl Iterator<Item = usize> + '_ {
+        let position = Vec2::from(light.world_position);
+        let radius = light.extent();
+
+        let to_cell = |point: Vec2| {
+            ((point - self.origin) / self.cell_size)
+                .floor()
+                .max(Vec2::ZERO)
+                .as_uvec2()
+                .min(self.size - 1)
+        };
+
+        let start = to_cell(position - radius);
+        let end = to_cell(position + radius);
+
+        (start.y..=end.y)
+            .flat_map(move |y| (start.x..=end.x).map(move |x| uvec2(x, y)))
+            .filter(move |cell| {
+                let min = self.origin + cell.as_vec2() * self.cell_size;
+                let closest = position.clamp(min, min + self.cell_size);
+
+                closest.distance_squared(position) <= radius * radius
+            })
+            .map(|cell| (cell.y * self.size.x + cell.x) as usize)
+    }
+
+    pub fn uniform(&self, light_count: usize) -> LightGridUniform {
+        LightGridUniform {
+            origin: self.origin.to_array(),
+            cell_size: self.cell_size.to_array(),
+            size: self.size.to_array(),
+            light_count: light_count as u32,
+            _padding: 0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LightGridUniform {
+    pub origin: [f32; 2],
+    pub cell_size: [f32; 2],
+    pub size: [u32; 2],
+    pub light_count: u32,
+    pub _padding: u32,
+}
+
+#[test]
+fn test_light_grid() {
+    let lights = [
+        Light::simple(vec2(0.0, 0.0), 1.0, 1.0),
+        Light::simple(vec2(10.0, 0.0), 1.0, 1.0),
+        Light::simple(vec2(5.0, 0.0), 6.0, 1.0),
+    ];
+
+    let everything = AABB::new(splat(f32::NEG_INFINITY), splat(f32::INFINITY));
+    let grid = LightGrid::new(&lights, everything, 8);
+
+    assert_eq!(grid.cells.len(), 64);
+    assert_eq!(grid.lights_at(vec2(0.0, 0.0)), &[0, 2]);
+    assert_eq!(grid.lights_at(vec2(10.0, 0.0)), &[1, 2]);
+    assert_eq!(grid.lights_at(vec2(5.0, 5.0)), &[2]);
+
+    // Corners of the grid are outside of every light's circle.
+    assert!(grid.lights_at(vec2(10.9, 5.9)).is_empty());
+
+    // Nothing is lit outside of the grid.
+    assert!(grid.lights_at(vec2(100.0, 0.0)).is_empty());
+    assert!(LightGrid::new(&[], everything, 8)
+        .lights_at(Vec2::ZERO)
+        .is_empty());
+
+    // Only the visible part is covered, lights outside of it are culled.
+    let visible = AABB::new(vec2(-2.0, -2.0), vec2(2.0, 2.0));
+    let grid = LightGrid::new(&lights, visible, 8);
+
+    // Nothing lights anything left of x = -1.
+    assert_eq!(grid.origin, vec2(-1.0, -2.0));
+    assert_eq!(grid.cell_size, vec2(0.375, 0.5));
+    assert_eq!(grid.lights_at(vec2(0.0, 0.0)), &[0, 2]);
+    assert!(!grid.indices.contains(&1));
+
+    let off_screen = AABB::new(vec2(50.0, 50.0), vec2(60.0, 60.0));
+    assert!(LightGrid::new(&lights, off_screen, 8).cells.is_empty());
+}
+
+#[test]
+fn test_light_grid_area_light() {
+    let window = Light::simple(Vec2::ZERO, 1.0, 1.0).with_area(vec2(10.0, 2.0));
+    let everything = AABB::new(splat(f32::NEG_INFINITY), splat(f32::INFINITY));
+    let grid = LightGrid::new(&[window], everything, 8);
+
+    // Area lights reach `radius` past the edge of their rectangle.
+    assert_eq!(grid.lights_at(vec2(5.5, 0.0)), &[0]);
+}
@@ -6,22 +6,37 @@ static LIGHTS: Lazy<AtomicRefCell<LightingState>> =
 #[derive(Default)]
 pub struct LightingState {
     pub lights: Vec<Light>,
+    pub occluders: Vec<Occluder>,
 }
 
 impl LightingState {
     pub fn begin_frame() {
-        LIGHTS.borrow_mut().lights.clear();
+        let mut state = LIGHTS.borrow_mut();
+
+        free_unused_light_cookies(state.lights.iter().filter_map(|x| x.cookie));
+
+        state.lights.clear();
+        state.occluders.clear();
     }
 
     pub fn take_lights() -> Vec<Light> {
         LIGHTS.borrow_mut().lights.clone()
     }
+
+    pub fn take_occluders() -> Vec<Occluder> {
+        std::mem::take(&mut LIGHTS.borrow_mut().occluders)
+    }
 }
 
 pub fn draw_light(light: Light) {
     LIGHTS.borrow_mut().lights.push(light);
 }
 
+/// Blocks the light of lights with `casts_shadows` for this frame.
+pub fn draw_occluder(occluder: Occluder) {
+    LIGHTS.borrow_mut().occluders.push(occluder);
+}
+
 pub fn light_count() -> usize {
     LIGHTS.borrow().lights.len()
 }
@@ -34,15 +49,92 @@ pub struct PointLight {
     pub color: Color,
 }
 
+#[derive(Copy, Clone, Debug)]
+pub struct Light {
+    pub color: Color,
+    pub world_position: [f32; 2],
+    pub screen_position: [f32; 2],
+    pub radius: f32,
+    pub strength: f32,
+    /// Distance of the light above the sprites in world units, only used
+    /// when shading sprites with a normal map. Lower lights make the
+    /// surface details more pronounced.
+    pub height: f32,
+    /// Lights with a non-zero value are blocked by occluders drawn with
+    /// `draw_occluder`, see `Light::with_shadows`.
+    pub casts_shadows: u32,
+    /// Row of the light's shadow map, set by the renderer.
+    pub shadow_index: i32,
+    /// `LightShape` as `u32`.
+    pub shape: u32,
+    /// Rotation in radians counter-clockwise from the +X axis. Spot lights
+    /// point in this direction, and area lights and cookies are rotated by
+    /// it.
+    pub rotation: f32,
+    /// Angle between the direction of a spot light and where its light
+    /// starts fading out, in radians.
+    pub inner_angle: f32,
+    /// Angle between the direction of a spot light and the edge of its cone,
+    /// in radians.
+    pub outer_angle: f32,
+    /// Texture tinting the light, see `Light::with_cookie`.
+    pub cookie: Option<TextureHandle>,
+    /// Size of the rectangle of area lights in world units.
+    pub size: [f32; 2],
+}
+
+impl Default for Light {
+    fn default() -> Self {
+        Self {
+            color: Color::default(),
+            world_position: [0.0; 2],
+            screen_position: [0.0; 2],
+            radius: 0.0,
+            strength: 0.0,
+            height: 1.0,
+            casts_shadows: 0,
+            shadow_index: -1,
+            shape: LightShape::Point as u32,
+            rotation: 0.0,
+            inner_angle: 0.0,
+            outer_angle: 0.0,
+            cookie: None,
+            size: [0.0; 2],
+        }
+    }
+}
+
+/// A `Light` as it's uploaded to the GPU, `Light` in the shaders.
 #[derive(Copy, Clone, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
 #[repr(C)]
-pub struct Light {
+pub struct LightRaw {
     pub color: Color,
     pub world_position: [f32; 2],
     pub screen_position: [f32; 2],
     pub radius: f32,
     pub strength: f32,
-    pub _padding: [f32; 2],
+    pub height: f32,
+    pub casts_shadows: u32,
+    pub shadow_index: i32,
+    pub shape: u32,
+    pub rotation: f32,
+    pub inner_angle: f32,
+    pub outer_angle: f32,
+    /// Layer of the cookie texture in `light_cookies`, -1 for none.
+    pub cookie: i32,
+    pub size: [f32; 2],
+}
+
+/// How a `Light` emits its light.
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LightShape {
+    /// Shines equally in every direction.
+    Point = 0,
+    /// Shines in a cone, see `Light::with_spot`.
+    Spot = 1,
+    /// Shines from a rectangle, see `Light::with_area`.
+    Area = 2,
 }
 
 impl Light {
@@ -53,17 +145,120 @@ impl Light {
             screen_position: Vec2::ZERO.as_array(),
             radius,
             strength,
-            _padding: [0.0; 2],
+            height: 1.0,
+            casts_shadows: 0,
+            shadow_index: -1,
+            shape: LightShape::Point as u32,
+            rotation: 0.0,
+            inner_angle: 0.0,
+            outer_angle: 0.0,
+            cookie: None,
+            size: [0.0; 2],
+        }
+    }
+
+    pub fn with_color(self, color: Color) -> Self {
+        Self { color, ..self }
+    }
+
+    pub fn with_height(self, height: f32) -> Self {
+        Self { height, ..self }
+    }
+
+    /// Makes the light cast soft shadows from occluders drawn with
+    /// `draw_occluder`. `GlobalLightingParams::shadow_strength` controls how
+    /// dark the shadows are.
+    pub fn with_shadows(self, casts_shadows: bool) -> Self {
+        Self { casts_shadows: casts_shadows as u32, ..self }
+    }
+
+    /// Turns the light into a spot light shining in `direction`. The light
+    /// fades out between `inner_angle` and `outer_angle` away from the
+    /// direction, e.g. `outer_angle` of `PI / 4.0` makes a 90 degree cone.
+    pub fn with_spot(
+        self,
+        direction: Vec2,
+        inner_angle: f32,
+        outer_angle: f32,
+    ) -> Self {
+        Self {
+            shape: LightShape::Spot as u32,
+            rotation: direction.y.atan2(direction.x),
+            inner_angle: inner_angle.min(outer_angle),
+            outer_angle,
+            ..self
+        }
+    }
+
+    /// Turns the light into an area light shining from a rectangle of `size`
+    /// centered at the light's position, e.g. a window. The light fades out
+    /// over `radius` away from the rectangle.
+    pub fn with_area(self, size: Vec2) -> Self {
+        Self { shape: LightShape::Area as u32, size: size.to_array(), ..self }
+    }
+
+    pub fn with_rotation(self, rotation: f32) -> Self {
+        Self { rotation, ..self }
+    }
+
+    /// Tints the light with a texture covering the area the light reaches,
+    /// rotated by the light's rotation, e.g. for flashlights or stained
+    /// glass windows. Up to `MAX_LIGHT_COOKIES` different textures can be
+    /// used as cookies in the same frame.
+    pub fn with_cookie(self, texture: TextureHandle) -> Self {
+        Self { cookie: Some(texture), ..self }
+    }
+
+    /// Used by the renderer when uploading the lights, which looks up the
+    /// layer of the cookie texture at that point.
+    pub fn to_raw(&self) -> LightRaw {
+        LightRaw {
+            color: self.color,
+            world_position: self.world_position,
+            screen_position: self.screen_position,
+            radius: self.radius,
+            strength: self.strength,
+            height: self.height,
+            casts_shadows: self.casts_shadows,
+            shadow_index: self.shadow_index,
+            shape: self.shape,
+            rotation: self.rotation,
+            inner_angle: self.inner_angle,
+            outer_angle: self.outer_angle,
+            cookie: self.cookie.map_or(-1, light_cookie_index),
+            size: self.size,
+        }
+    }
+
+    pub fn shape(&self) -> LightShape {
+        match self.shape {
+            1 => LightShape::Spot,
+            2 => LightShape::Area,
+            _ => LightShape::Point,
+        }
+    }
+
+    /// Distance from the light's position to the furthest point it lights.
+    pub fn extent(&self) -> f32 {
+        match self.shape() {
+            LightShape::Area => {
+                self.radius + Vec2::from(self.size).length() / 2.0
+            }
+            _ => self.radius,
         }
     }
 }
 
-pub const MAX_LIGHTS: usize = 128;
+/// Number of lights drawn each frame on devices without storage buffers
+/// (WebGL2), which keep the lights closest to the main camera. Other devices
+/// have no limit on the number of lights.
+pub const MAX_UNIFORM_LIGHTS: usize = 128;
 
+/// Lights of devices without storage buffers.
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 #[repr(C)]
 pub struct LightUniform {
-    pub lights: [Light; 128],
+    pub lights: [LightRaw; MAX_UNIFORM_LIGHTS],
     pub num_lights: i32,
     _padding: [f32; 3],
 }
@@ -71,7 +266,7 @@ pub struct LightUniform {
 impl Default for LightUniform {
     fn default() -> Self {
         Self {
-            lights: [Light::default(); 128],
+            lights: [LightRaw::default(); MAX_UNIFORM_LIGHTS],
             num_lights: 0,
             _padding: [0.0; 3],
         }
@@ -126,7 +321,11 @@ pub struct GlobalLightingParams {
     pub bloom_threshold: f32,
     pub bloom_lerp: f32,
     pub bloom_gamma: f32,
-    pub _padding: [f32; 2],
+
+    /// Set by the renderer from `color_grading()`, changing these has no
+    /// effect.
+    pub lut_weight: f32,
+    pub lut_crossfade: f32,
 }
 
 impl Default for GlobalLightingParams {
@@ -164,7 +363,9 @@ impl Default for GlobalLightingParams {
             bloom_threshold: 0.8,
             bloom_lerp: 0.3,
             bloom_gamma: 1.0,
-            _padding: [0.0; 2],
+
+            lut_weight: 1.0,
+            lut_crossfade: 1.0,
         }
     }
 }
@@ -245,7 +446,6 @@ pub fn lighting_ui(
         field_editor(ui, "Global Brightness", &mut params.global_brightness);
     changed |=
         field_editor(ui, "Gamma Correction", &mut params.gamma_correction);
-    changed |= field_editor(ui, "Use LUT", &mut params.use_lut);
 
     changed
 }
@@ -289,3 +489,17 @@ where T: Clone + PartialEq + 'static + egui::emath::Numeric {
 
     changed
 }
+
+#[test]
+fn test_light_default() {
+    let light = Light::default();
+
+    assert_eq!(light.cookie, None);
+    assert_eq!(light.shadow_index, -1);
+    assert_eq!(light.height, 1.0);
+
+    let raw = light.to_raw();
+
+    assert_eq!(raw.cookie, -1);
+    assert_eq!(raw.shadow_index, -1);
+}
@@ -0,0 +1,92 @@
+use crate::*;
+
+static NORMAL_MAPS: Lazy<AtomicRefCell<HashMap<TextureHandle, TextureHandle>>> =
+    Lazy::new(|| AtomicRefCell::new(HashMap::default()));
+
+static NORMAL_MAP_CHANGES: Lazy<AtomicRefCell<Vec<TextureHandle>>> =
+    Lazy::new(|| AtomicRefCell::new(Vec::new()));
+
+/// Uses `normal_map` when lighting sprites drawn with `texture`, `None`
+/// removes it.
+///
+/// Normal maps are expected in the usual OpenGL (Y+) convention, with the
+/// normal pointing out of the screen being `(128, 128, 255)`. Textures used as
+/// normal maps must not be sRGB, textures loaded before becoming a normal
+/// map are re-created as linear by the renderer. Textures created manually
+/// should be created with `is_normal_map: true`.
+///
+/// Sprites with a normal map are never batched with bindless batching.
+pub fn set_normal_map(
+    texture: TextureHandle,
+    normal_map: Option<TextureHandle>,
+) {
+    let mut normal_maps = NORMAL_MAPS.borrow_mut();
+
+    match normal_map {
+        Some(normal_map) => {
+            normal_maps.insert(texture, normal_map);
+            NORMAL_MAP_CHANGES.borrow_mut().push(normal_map);
+        }
+        None => {
+            normal_maps.remove(&texture);
+        }
+    };
+}
+
+/// Internal use only, textures which were set as normal maps since the last
+/// call.
+pub fn consume_normal_map_changes() -> Vec<TextureHandle> {
+    let mut changes = NORMAL_MAP_CHANGES.borrow_mut();
+    let mut new_data = Vec::new();
+    std::mem::swap(&mut new_data, &mut *changes);
+    new_data
+}
+
+/// Normal map of the texture set with `set_normal_map`.
+pub fn normal_map(texture: TextureHandle) -> Option<TextureHandle> {
+    NORMAL_MAPS.borrow().get(&texture).copied()
+}
+
+/// Whether the texture is used as a normal map of another texture.
+pub fn is_normal_map(texture: TextureHandle) -> bool {
+    NORMAL_MAPS.borrow().values().any(|x| *x == texture)
+}
+
+/// Path of the normal map belonging to the diffuse texture at `path`,
+/// `foo/bar.png` becomes `foo/bar_n.png`.
+pub fn normal_map_path(path: &str) -> String {
+    let file_start = path.rfind('/').map(|x| x + 1).unwrap_or(0);
+
+    match path[file_start..].rfind('.') {
+        Some(dot) => {
+            let dot = file_start + dot;
+            format!("{}_n{}", &path[..dot], &path[dot..])
+        }
+        None => format!("{}_n", path),
+    }
+}
+
+/// Queues a texture for loading just like `load_multiple_textures`, together
+/// with its normal map at `normal_map_path(path)`, e.g. `foo.png` and
+/// `foo_n.png`. The normal map is available as `{name}_n`.
+pub fn load_texture_with_normal_map(name: &str, path: &str) {
+    let normal_name = format!("{}_n", name);
+
+    set_normal_map(
+        texture_id_unchecked(name),
+        Some(texture_id_unchecked(&normal_name)),
+    );
+
+    load_multiple_textures(vec![
+        (name.to_string(), path.to_string()),
+        (normal_name, normal_map_path(path)),
+    ]);
+}
+
+#[test]
+fn test_normal_map_path() {
+    assert_eq!(normal_map_path("foo.png"), "foo_n.png");
+    assert_eq!(normal_map_path("tiles/wall.png"), "tiles/wall_n.png");
+    assert_eq!(normal_map_path("dir.v2/wall"), "dir.v2/wall_n");
+    assert_eq!(normal_map_path("wall.tar.png"), "wall.tar_n.png");
+}
@@ -0,0 +1,107 @@
+use crate::*;
+
+/// Integer factor the low resolution is scaled up by to fit into the window,
+/// at least 1.
+pub fn pixel_perfect_scale(resolution: UVec2, window_size: Vec2) -> f32 {
+    let scale = window_size / resolution.as_vec2().max(Vec2::ONE);
+    scale.min_element().floor().max(1.0)
+}
+
+/// Part of the window in pixels the low resolution image is drawn into,
+/// centered with black bars around it. `None` when pixel perfect rendering
+/// is disabled.
+pub fn pixel_perfect_rect(window_size: Vec2) -> Option<AABB> {
+    game_config()
+        .pixel_perfect
+        .map(|config| letterbox_rect(config.resolution, window_size))
+}
+
+/// Size of the image the world is drawn into, which is the low resolution
+/// in pixel perfect mode and the window otherwise.
+pub fn render_resolution(window_size: Vec2) -> Vec2 {
+    game_config()
+        .pixel_perfect
+        .map(|config| config.resolution.as_vec2())
+        .unwrap_or(window_size)
+}
+
+/// Converts a position in window pixels (e.g. the mouse) into pixels of the
+/// image the world is drawn into.
+pub fn window_to_render(position: Vec2, window_size: Vec2) -> Vec2 {
+    match game_config().pixel_perfect {
+        Some(config) => {
+            let rect = letterbox_rect(config.resolution, window_size);
+            let scale = pixel_perfect_scale(config.resolution, window_size);
+            (position - rect.min) / scale
+        }
+        None => position,
+    }
+}
+
+/// Inverse of `window_to_render`.
+pub fn render_to_window(position: Vec2, window_size: Vec2) -> Vec2 {
+    match game_config().pixel_perfect {
+        Some(config) => {
+            let rect = letterbox_rect(config.resolution, window_size);
+            let scale = pixel_perfect_scale(config.resolution, window_size);
+            rect.min + position * scale
+        }
+        None => position,
+    }
+}
+
+/// Rounds a world position to the pixel grid, where `pixel_size` is the size
+/// of one pixel in world units.
+pub fn snap_to_pixel_grid(position: Vec2, pixel_size: Vec2) -> Vec2 {
+    if pixel_size.x <= 0.0 || pixel_size.y <= 0.0 {
+        return position;
+    }
+
+    (position / pixel_size).round() * pixel_size
+}
+
+/// Snaps the center of a camera to the pixel grid when
+/// `PixelPerfectConfig::snap_camera` is enabled. `world_viewport` is the
+/// area the camera sees in world units and `viewport_pixels` the size of its
+/// viewport in pixels of the image it draws into.
+pub fn snap_camera_center(
+    center: Vec2,
+    world_viewport: Vec2,
+    viewport_pixels: Vec2,
+) -> Vec2 {
+    if game_config().pixel_perfect.is_some_and(|x| x.snap_camera) {
+        snap_to_pixel_grid(
+            center,
+            world_viewport / viewport_pixels.max(Vec2::ONE),
+        )
+    } else {
+        center
+    }
+}
+
+fn letterbox_rect(resolution: UVec2, window_size: Vec2) -> AABB {
+    let size =
+        resolution.as_vec2() * pixel_perfect_scale(resolution, window_size);
+    let min = ((window_size - size) / 2.0).floor();
+
+    AABB::new(min, min + size)
+}
+
+#[test]
+fn test_pixel_perfect_letterbox() {
+    let resolution = uvec2(320, 180);
+
+    assert_eq!(pixel_perfect_scale(resolution, vec2(1920.0, 1080.0)), 6.0);
+    assert_eq!(pixel_perfect_scale(resolution, vec2(1280.0, 800.0)), 4.0);
+    assert_eq!(pixel_perfect_scale(resolution, vec2(100.0, 100.0)), 1.0);
+
+    assert_eq!(
+        letterbox_rect(resolution, vec2(1280.0, 800.0)),
+        AABB::new(vec2(0.0, 40.0), vec2(1280.0, 760.0))
+    );
+
+    assert_eq!(
+        snap_to_pixel_grid(vec2(1.26, -0.74), splat(0.5)),
+        vec2(1.5, -0.5)
+    );
+}
@@ -2,7 +2,7 @@ use std::sync::atomic::{AtomicBool, Ordering};
 
 use crate::*;
 
-const Z_DIV: f32 = 1000.0;
+pub(crate) const Z_DIV: f32 = 1000.0;
 static SPRITE_CULLING_ENABLED: AtomicBool = AtomicBool::new(true);
 
 /// When set to `true` (default) sprites that are outside of the camera's viewport will
@@ -153,6 +153,27 @@ pub fn draw_sprite_ex(
         }
     };
 
+    if get_sprite_instancing() && get_current_shader().0 == 0 {
+        let instance = SpriteInstance::from_raw_params(
+            position.extend(z_index as f32 / Z_DIV),
+            raw,
+            size.x as f32,
+            size.y as f32,
+            tint,
+            params.scroll_offset,
+        );
+
+        queue_sprite_instance(
+            instance,
+            z_index,
+            texture,
+            params.blend_mode,
+            position.y + params.y_sort_offset,
+        );
+
+        return;
+    }
+
     let vertices = rotated_rectangle(
         position.extend(z_index as f32 / Z_DIV),
         raw,
@@ -339,6 +360,35 @@ pub fn draw_sprite_pro(
         Vec2::new(tex_0_x, tex_1_y),
     ];
 
+    if get_sprite_instancing() &&
+        get_current_shader().0 == 0 &&
+        params.rotation_x == 0.0
+    {
+        let center = rotate_point_around_pivot(
+            position - origin + params.size / 2.0,
+            position + pivot,
+            params.rotation,
+        );
+
+        let instance = SpriteInstance::new(
+            center.extend(z_index as f32 / Z_DIV),
+            params.size,
+            params.rotation,
+            [tex_0_x, tex_0_y, tex_1_x, tex_1_y],
+            tint,
+        );
+
+        queue_sprite_instance(
+            instance,
+            z_index,
+            texture,
+            params.blend_mode,
+            position.y + params.y_sort_offset,
+        );
+
+        return;
+    }
+
     let vertices = [0, 1, 2, 3].map(|i| {
         SpriteVertex::new(
             rotate_around_point(
@@ -0,0 +1,76 @@
+use crate::*;
+
+use std::sync::atomic::{AtomicU32, Ordering};
+
+static CURRENT_RENDER_LAYERS: AtomicU32 =
+    AtomicU32::new(RenderLayers::DEFAULT.0);
+
+/// Bitmask of render layers. Every draw is on one or more layers, and
+/// cameras and render targets only draw what is on the layers they see.
+///
+/// ```
+/// # use comfy_core::*;
+/// const MAP_ICONS: RenderLayers = RenderLayers::layer(1);
+///
+/// // The minimap only sees map icons, the main camera everything else.
+/// let minimap = RenderLayers::NONE.with(1);
+/// let main = RenderLayers::ALL.without(1);
+///
+/// assert!(minimap.intersects(MAP_ICONS));
+/// assert!(!main.intersects(MAP_ICONS));
+/// assert!(main.intersects(RenderLayers::DEFAULT));
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct RenderLayers(pub u32);
+
+impl RenderLayers {
+    /// Layer 0, where everything is drawn unless `use_render_layers` says
+    /// otherwise.
+    pub const DEFAULT: Self = Self::layer(0);
+    pub const ALL: Self = Self(u32::MAX);
+    pub const NONE: Self = Self(0);
+
+    /// A mask containing only the given layer. Panics if the layer isn't
+    /// below 32.
+    pub const fn layer(layer: u8) -> Self {
+        assert!(layer < 32, "Render layers must be below 32");
+        Self(1 << layer)
+    }
+
+    pub const fn with(self, layer: u8) -> Self {
+        Self(self.0 | Self::layer(layer).0)
+    }
+
+    pub const fn without(self, layer: u8) -> Self {
+        Self(self.0 & !Self::layer(layer).0)
+    }
+
+    pub const fn contains(self, layer: u8) -> bool {
+        self.0 & Self::layer(layer).0 != 0
+    }
+
+    /// True if the masks have at least one layer in common.
+    pub const fn intersects(self, other: RenderLayers) -> bool {
+        self.0 & other.0 != 0
+    }
+}
+
+impl Default for RenderLayers {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Puts everything drawn after this (sprites, meshes, text, tilemaps) on the
+/// given render layers, until `use_default_render_layers` is called.
+pub fn use_render_layers(layers: RenderLayers) {
+    CURRENT_RENDER_LAYERS.store(layers.0, Ordering::SeqCst);
+}
+
+pub fn use_default_render_layers() {
+    use_render_layers(RenderLayers::DEFAULT);
+}
+
+pub fn get_current_render_layers() -> RenderLayers {
+    RenderLayers(CURRENT_RENDER_LAYERS.load(Ordering::SeqCst))
+}
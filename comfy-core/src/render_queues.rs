@@ -123,6 +123,8 @@ pub struct MeshGroupKey {
     pub texture_id: TextureHandle,
     pub shader: ShaderInstanceId,
     pub render_target: RenderTargetId,
+    pub clip: ClipId,
+    pub layers: RenderLayers,
 }
 
 pub fn consume_render_queues() -> BTreeMap<MeshGroupKey, RenderQueue> {
@@ -133,23 +135,45 @@ pub fn consume_render_queues() -> BTreeMap<MeshGroupKey, RenderQueue> {
 }
 
 pub fn queue_mesh_draw(mesh: Mesh, blend_mode: BlendMode) {
-    let shader = get_current_shader();
-    let render_target = get_current_render_target();
-
-    RENDER_QUEUES
-        .borrow_mut()
-        .data
-        .entry(MeshGroupKey {
-            z_index: mesh.z_index,
-            blend_mode,
-            texture_id: mesh
-                .texture
-                .unwrap_or_else(|| TextureHandle::from_path("1px")),
-            shader,
-            render_target,
-        })
-        .or_default()
-        .push(mesh);
+    let key = MeshGroupKey {
+        z_index: mesh.z_index,
+        blend_mode,
+        texture_id: mesh
+            .texture
+            .unwrap_or_else(|| TextureHandle::from_path("1px")),
+        shader: get_current_shader(),
+        render_target: get_current_render_target(),
+        clip: get_current_clip(),
+        layers: get_current_render_layers(),
+    };
+
+    // Sprite instances queued before with the same key become meshes, so
+    // that they're still drawn before this mesh.
+    let instances = take_sprite_instance_queue(&key);
+
+    let mut queues = RENDER_QUEUES.borrow_mut();
+    let queue = queues.data.entry(key).or_default();
+
+    if let Some(instances) = instances {
+        queue.extend(instances.into_meshes(key.z_index, key.texture_id));
+    }
+
+    queue.push(mesh);
+}
+
+/// Appends the meshes to the queue of the key, but only if meshes were
+/// already queued with it. Returns whether they were queued.
+pub(crate) fn extend_existing_mesh_queue(
+    key: &MeshGroupKey,
+    meshes: impl FnOnce() -> Vec<Mesh>,
+) -> bool {
+    match RENDER_QUEUES.borrow_mut().data.get_mut(key) {
+        Some(queue) => {
+            queue.extend(meshes());
+            true
+        }
+        None => false,
+    }
 }
 
 pub fn draw_text_internal(
@@ -167,5 +191,6 @@ pub fn draw_text_internal(
         align,
         pro_params,
         z_index: params.z_index,
+        layers: get_current_render_layers(),
     });
 }
@@ -0,0 +1,166 @@
+use crate::*;
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+static GENERATED_RENDER_TARGET_IDS: AtomicU32 = AtomicU32::new(1);
+
+static RENDER_TARGETS: Lazy<
+    AtomicRefCell<BTreeMap<RenderTargetId, RenderTargetParams>>,
+> = Lazy::new(|| AtomicRefCell::new(BTreeMap::new()));
+
+static RENDER_TARGET_CREATIONS: Lazy<AtomicRefCell<Vec<RenderTargetId>>> =
+    Lazy::new(|| AtomicRefCell::new(Vec::new()));
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum RenderTargetFormat {
+    /// The HDR format the screen is drawn in before tonemapping.
+    #[default]
+    Rgba16Float,
+    /// 8 bits per channel in sRGB, for targets that don't need HDR.
+    Rgba8UnormSrgb,
+}
+
+#[derive(Clone, Debug)]
+pub struct RenderTargetParams {
+    /// Shown by graphics debuggers like RenderDoc.
+    pub label: String,
+    pub size: UVec2,
+    pub format: RenderTargetFormat,
+    /// Filtering used when the target is sampled as a texture.
+    pub filter: TextureFilter,
+    /// Render layers of draws the target accepts.
+    pub layers: RenderLayers,
+    /// Camera the target draws the whole scene with, e.g. for mirrors or a
+    /// minimap. Without a camera only draws routed into the target with
+    /// `use_render_target` are drawn, using the main camera.
+    ///
+    /// Draws sampling the target itself, e.g. the minimap sprite, are not
+    /// drawn by its camera, since a target can't be sampled while it's being
+    /// drawn into.
+    pub camera: Option<Camera>,
+    /// Color the target is cleared with every frame. `None` keeps what was
+    /// drawn into it in previous frames.
+    pub clear_color: Option<Color>,
+    /// Render targets are drawn in ascending order before anything is drawn
+    /// to the screen, so a target can sample targets with a lower order.
+    pub order: i32,
+}
+
+impl RenderTargetParams {
+    pub fn new(label: impl Into<String>, size: UVec2) -> Self {
+        Self {
+            label: label.into(),
+            size,
+            format: RenderTargetFormat::default(),
+            filter: TextureFilter::Nearest,
+            layers: RenderLayers::ALL,
+            camera: None,
+            clear_color: None,
+            order: 0,
+        }
+    }
+
+    pub fn with_format(self, format: RenderTargetFormat) -> Self {
+        Self { format, ..self }
+    }
+
+    pub fn with_filter(self, filter: TextureFilter) -> Self {
+        Self { filter, ..self }
+    }
+
+    pub fn with_layers(self, layers: RenderLayers) -> Self {
+        Self { layers, ..self }
+    }
+
+    pub fn with_camera(self, camera: Camera) -> Self {
+        Self { camera: Some(camera), ..self }
+    }
+
+    pub fn with_clear_color(self, clear_color: Color) -> Self {
+        Self { clear_color: Some(clear_color), ..self }
+    }
+
+    pub fn with_order(self, order: i32) -> Self {
+        Self { order, ..self }
+    }
+}
+
+/// Creates a render target of the given size. The returned `TextureHandle`
+/// samples the target like any other texture, e.g. in `draw_sprite`.
+///
+/// The target is created by the renderer before the next frame is drawn, so
+/// it can be drawn into and sampled right away.
+pub fn create_render_target(
+    size: UVec2,
+    format: RenderTargetFormat,
+) -> (RenderTargetId, TextureHandle) {
+    create_render_target_ex(
+        RenderTargetParams::new("User Render Target", size).with_format(format),
+    )
+}
+
+/// Like `create_render_target`, but with all of the target's parameters.
+pub fn create_render_target_ex(
+    params: RenderTargetParams,
+) -> (RenderTargetId, TextureHandle) {
+    let id = RenderTargetId(
+        GENERATED_RENDER_TARGET_IDS.fetch_add(1, Ordering::SeqCst),
+    );
+
+    RENDER_TARGETS.borrow_mut().insert(id, params);
+    RENDER_TARGET_CREATIONS.borrow_mut().push(id);
+
+    (id, TextureHandle::RenderTarget(id))
+}
+
+pub fn render_target_params(id: RenderTargetId) -> Option<RenderTargetParams> {
+    RENDER_TARGETS.borrow().get(&id).cloned()
+}
+
+/// Changes the camera the target draws the scene with, e.g. to follow the
+/// player with a minimap.
+pub fn set_render_target_camera(id: RenderTargetId, camera: Option<Camera>) {
+    update_render_target(id, |params| params.camera = camera);
+}
+
+pub fn set_render_target_clear_color(
+    id: RenderTargetId,
+    clear_color: Option<Color>,
+) {
+    update_render_target(id, |params| params.clear_color = clear_color);
+}
+
+/// All render targets in the order they're drawn in.
+pub fn render_targets() -> Vec<(RenderTargetId, RenderTargetParams)> {
+    RENDER_TARGETS
+        .borrow()
+        .iter()
+        .map(|(id, params)| (*id, params.clone()))
+        .sorted_by_key(|(id, params)| (params.order, *id))
+        .collect()
+}
+
+/// Render targets created since the last call. Intended for internal use by
+/// the renderer.
+pub fn consume_render_target_creations(
+) -> Vec<(RenderTargetId, RenderTargetParams)> {
+    let mut creations = RENDER_TARGET_CREATIONS.borrow_mut();
+    let mut new_data = Vec::new();
+    std::mem::swap(&mut new_data, &mut *creations);
+
+    new_data
+        .into_iter()
+        .filter_map(|id| render_target_params(id).map(|params| (id, params)))
+        .collect()
+}
+
+fn update_render_target(
+    id: RenderTargetId,
+    f: impl FnOnce(&mut RenderTargetParams),
+) {
+    match RENDER_TARGETS.borrow_mut().get_mut(&id) {
+        Some(params) => f(params),
+        None => error!("Render target {:?} doesn't exist", id),
+    }
+}
@@ -54,27 +54,270 @@ pub struct ShaderInstance {
     pub uniforms: HashMap<String, Uniform>,
 }
 
+/// Definition of a shader uniform, with an optional default value used when
+/// the shader instance doesn't set it.
+///
+/// Values are laid out in the uniform buffer the way WGSL expects, including
+/// the padding. Arrays of scalars and vectors smaller than `vec4` are
+/// declared as arrays of `vec4`, since elements of arrays in uniform buffers
+/// have to be 16 byte aligned. The value is in the first components, e.g.
+/// `weights[i].x` for an array of `f32`.
+///
+/// `Texture` uniforms declare a `texture_2d<f32>` named after the uniform and
+/// a `sampler` with a `_sampler` suffix, e.g. `noise` and `noise_sampler`.
 #[derive(Clone, Debug)]
 pub enum UniformDef {
     F32(Option<f32>),
-    Custom { default_data: Option<Vec<u8>>, wgsl_decl: String },
+    I32(Option<i32>),
+    U32(Option<u32>),
+    Vec2(Option<Vec2>),
+    Vec3(Option<Vec3>),
+    Vec4(Option<Vec4>),
+    /// A `vec4<f32>` with the color's RGBA components.
+    Color(Option<Color>),
+    Mat4(Option<Mat4>),
+    /// Fixed size array of the given element type, all elements default to
+    /// the element's default. Textures can't be put in arrays, see
+    /// `UniformDef::array`.
+    Array(Box<UniformDef>, u32),
+    /// An extra texture for the shader to sample, e.g. noise or a palette.
+    /// Render targets can be bound with `TextureHandle::RenderTarget`.
+    /// Without a value the error texture is bound.
+    Texture(Option<TextureHandle>),
+    Custom {
+        default_data: Option<Vec<u8>>,
+        wgsl_decl: String,
+    },
 }
 
 impl UniformDef {
-    pub fn to_wgsl(&self) -> &str {
+    /// A `UniformDef::Array` of `len` elements. Fails for arrays of textures,
+    /// which can't be declared as uniforms, and for empty arrays.
+    pub fn array(element: UniformDef, len: u32) -> Result<Self> {
+        let array = UniformDef::Array(Box::new(element), len);
+        array.validate()?;
+        Ok(array)
+    }
+
+    /// Checks that the definition can be declared in WGSL, `create_shader`
+    /// fails for definitions which can't.
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            UniformDef::Array(element, len) => {
+                if element.is_texture() {
+                    bail!("Arrays of textures can't be used as uniforms");
+                }
+
+                if *len == 0 {
+                    bail!("Uniform arrays must have at least one element");
+                }
+
+                element.validate()
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn to_wgsl(&self) -> String {
+        match self {
+            UniformDef::F32(_) => "f32".into(),
+            UniformDef::I32(_) => "i32".into(),
+            UniformDef::U32(_) => "u32".into(),
+            UniformDef::Vec2(_) => "vec2<f32>".into(),
+            UniformDef::Vec3(_) => "vec3<f32>".into(),
+            UniformDef::Vec4(_) | UniformDef::Color(_) => "vec4<f32>".into(),
+            UniformDef::Mat4(_) => "mat4x4<f32>".into(),
+            UniformDef::Texture(_) => "texture_2d<f32>".into(),
+            UniformDef::Array(element, len) => {
+                let element = match **element {
+                    UniformDef::F32(_) |
+                    UniformDef::Vec2(_) |
+                    UniformDef::Vec3(_) => "vec4<f32>".into(),
+                    UniformDef::I32(_) => "vec4<i32>".into(),
+                    UniformDef::U32(_) => "vec4<u32>".into(),
+                    _ => element.to_wgsl(),
+                };
+
+                format!("array<{}, {}>", element, len)
+            }
+            UniformDef::Custom { wgsl_decl, .. } => wgsl_decl.clone(),
+        }
+    }
+
+    /// Textures aren't stored in uniform buffers.
+    pub fn is_texture(&self) -> bool {
+        matches!(self, UniformDef::Texture(_))
+    }
+
+    /// Number of bindings the uniform takes up, two for a texture and its
+    /// sampler.
+    pub fn binding_count(&self) -> u32 {
+        if self.is_texture() {
+            2
+        } else {
+            1
+        }
+    }
+
+    /// Size of the uniform's buffer in bytes, padded to a multiple of 16.
+    pub fn size(&self) -> usize {
+        let size = match self {
+            UniformDef::F32(_) | UniformDef::I32(_) | UniformDef::U32(_) => 4,
+            UniformDef::Vec2(_) => 8,
+            UniformDef::Vec3(_) => 12,
+            UniformDef::Vec4(_) | UniformDef::Color(_) => 16,
+            UniformDef::Mat4(_) => 64,
+            // Elements are padded to 16 bytes, see `encode_into`.
+            UniformDef::Array(element, len) => element.size() * *len as usize,
+            UniformDef::Texture(_) => 0,
+            UniformDef::Custom { default_data, .. } => {
+                default_data.as_ref().map(|x| x.len()).unwrap_or(0)
+            }
+        };
+
+        size.max(16).next_multiple_of(16)
+    }
+
+    pub fn default_value(&self) -> Option<Uniform> {
         match self {
-            UniformDef::F32(_) => "f32",
-            UniformDef::Custom { wgsl_decl, .. } => wgsl_decl,
+            UniformDef::F32(value) => value.map(Uniform::from),
+            UniformDef::I32(value) => value.map(Uniform::from),
+            UniformDef::U32(value) => value.map(Uniform::from),
+            UniformDef::Vec2(value) => value.map(Uniform::from),
+            UniformDef::Vec3(value) => value.map(Uniform::from),
+            UniformDef::Vec4(value) => value.map(Uniform::from),
+            UniformDef::Color(value) => value.map(Uniform::from),
+            UniformDef::Mat4(value) => value.map(Uniform::from),
+            UniformDef::Array(element, len) => {
+                element
+                    .default_value()
+                    .map(|value| Uniform::Array(vec![value; *len as usize]))
+            }
+            UniformDef::Texture(value) => value.map(Uniform::Texture),
+            UniformDef::Custom { default_data, .. } => {
+                default_data.clone().map(Uniform::Custom)
+            }
+        }
+    }
+
+    /// Bytes of the value as they're uploaded to the uniform buffer, or
+    /// `None` if the value doesn't match the definition. Arrays can be given
+    /// fewer elements than their length, the rest is zeroed.
+    pub fn encode(&self, value: &Uniform) -> Option<Vec<u8>> {
+        let mut bytes = vec![0; self.size()];
+        self.encode_into(value, &mut bytes)?;
+        Some(bytes)
+    }
+
+    fn encode_into(&self, value: &Uniform, out: &mut [u8]) -> Option<()> {
+        fn put<T: bytemuck::Pod>(out: &mut [u8], values: &[T]) {
+            let bytes: &[u8] = bytemuck::cast_slice(values);
+            out[..bytes.len()].copy_from_slice(bytes);
         }
+
+        fn floats<const N: usize>(values: &[OrderedFloat<f32>; N]) -> [f32; N] {
+            values.map(|x| x.0)
+        }
+
+        match (self, value) {
+            (UniformDef::F32(_), Uniform::F32(x)) => put(out, &[x.0]),
+            (UniformDef::I32(_), Uniform::I32(x)) => put(out, &[*x]),
+            (UniformDef::U32(_), Uniform::U32(x)) => put(out, &[*x]),
+            (UniformDef::Vec2(_), Uniform::Vec2(x)) => put(out, &floats(x)),
+            (UniformDef::Vec3(_), Uniform::Vec3(x)) => put(out, &floats(x)),
+            (UniformDef::Vec4(_) | UniformDef::Color(_), Uniform::Vec4(x)) => {
+                put(out, &floats(x))
+            }
+            (UniformDef::Mat4(_), Uniform::Mat4(x)) => put(out, &floats(x)),
+            (UniformDef::Array(element, len), Uniform::Array(values)) => {
+                if values.len() > *len as usize {
+                    return None;
+                }
+
+                let stride = element.size();
+
+                for (i, value) in values.iter().enumerate() {
+                    element.encode_into(value, &mut out[i * stride..])?;
+                }
+            }
+            (UniformDef::Custom { .. }, Uniform::Custom(data)) => {
+                if data.len() > out.len() {
+                    return None;
+                }
+
+                put(out, data);
+            }
+            _ => return None,
+        }
+
+        Some(())
     }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Uniform {
     F32(OrderedFloat<f32>),
+    I32(i32),
+    U32(u32),
+    Vec2([OrderedFloat<f32>; 2]),
+    Vec3([OrderedFloat<f32>; 3]),
+    /// Also used for `UniformDef::Color`.
+    Vec4([OrderedFloat<f32>; 4]),
+    /// Column major, like `Mat4::to_cols_array`.
+    Mat4([OrderedFloat<f32>; 16]),
+    Array(Vec<Uniform>),
+    Texture(TextureHandle),
     Custom(Vec<u8>),
 }
 
+impl From<f32> for Uniform {
+    fn from(value: f32) -> Self {
+        Uniform::F32(OrderedFloat(value))
+    }
+}
+
+impl From<i32> for Uniform {
+    fn from(value: i32) -> Self {
+        Uniform::I32(value)
+    }
+}
+
+impl From<u32> for Uniform {
+    fn from(value: u32) -> Self {
+        Uniform::U32(value)
+    }
+}
+
+impl From<Vec2> for Uniform {
+    fn from(value: Vec2) -> Self {
+        Uniform::Vec2(value.to_array().map(OrderedFloat))
+    }
+}
+
+impl From<Vec3> for Uniform {
+    fn from(value: Vec3) -> Self {
+        Uniform::Vec3(value.to_array().map(OrderedFloat))
+    }
+}
+
+impl From<Vec4> for Uniform {
+    fn from(value: Vec4) -> Self {
+        Uniform::Vec4(value.to_array().map(OrderedFloat))
+    }
+}
+
+impl From<Color> for Uniform {
+    fn from(value: Color) -> Self {
+        Uniform::Vec4([value.r, value.g, value.b, value.a].map(OrderedFloat))
+    }
+}
+
+impl From<Mat4> for Uniform {
+    fn from(value: Mat4) -> Self {
+        Uniform::Mat4(value.to_cols_array().map(OrderedFloat))
+    }
+}
+
 // static CURRENT_RENDER_TARGET: Lazy<AtomicRefCell<Option<RenderTargetId>>> =
 //     Lazy::new(|| AtomicRefCell::new(None));
 
@@ -98,6 +341,51 @@ pub fn set_uniform_f32(name: impl Into<String>, value: f32) {
     set_uniform(name, Uniform::F32(OrderedFloat(value)));
 }
 
+pub fn set_uniform_i32(name: impl Into<String>, value: i32) {
+    set_uniform(name, Uniform::from(value));
+}
+
+pub fn set_uniform_u32(name: impl Into<String>, value: u32) {
+    set_uniform(name, Uniform::from(value));
+}
+
+pub fn set_uniform_vec2(name: impl Into<String>, value: Vec2) {
+    set_uniform(name, Uniform::from(value));
+}
+
+pub fn set_uniform_vec3(name: impl Into<String>, value: Vec3) {
+    set_uniform(name, Uniform::from(value));
+}
+
+pub fn set_uniform_vec4(name: impl Into<String>, value: Vec4) {
+    set_uniform(name, Uniform::from(value));
+}
+
+/// Sets a `UniformDef::Color` (or `Vec4`) uniform by name.
+pub fn set_uniform_color(name: impl Into<String>, value: Color) {
+    set_uniform(name, Uniform::from(value));
+}
+
+pub fn set_uniform_mat4(name: impl Into<String>, value: Mat4) {
+    set_uniform(name, Uniform::from(value));
+}
+
+/// Binds a texture to a `UniformDef::Texture` uniform by name.
+pub fn set_uniform_texture(name: impl Into<String>, texture: TextureHandle) {
+    set_uniform(name, Uniform::Texture(texture));
+}
+
+/// Sets a `UniformDef::Array` uniform by name, e.g. a list of positions.
+pub fn set_uniform_array<T: Into<Uniform>>(
+    name: impl Into<String>,
+    values: impl IntoIterator<Item = T>,
+) {
+    set_uniform(
+        name,
+        Uniform::Array(values.into_iter().map(Into::into).collect()),
+    );
+}
+
 /// Creates a new shader and returns its ID. The `source` parameter should only contain the
 /// fragment function, as the rest of the shader is automatically generated.
 ///
@@ -120,7 +408,8 @@ pub fn create_shader(
         panic!(
             "Missing @vertex function in shader passed to `create_shader`.
 
-             Did you forget to call `sprite_shader_from_fragment`?"
+             Did you forget to call `sprite_shader_from_fragment` or
+             `sprite_shader_from_vertex`?"
         );
     }
 
@@ -128,6 +417,12 @@ pub fn create_shader(
         bail!("Shader with name '{}' already exists", name);
     }
 
+    for (uniform, def) in uniform_defs.iter() {
+        def.validate().map_err(|err| {
+            anyhow::anyhow!("Invalid uniform {} of {}: {}", uniform, name, err)
+        })?;
+    }
+
     let bindings = uniform_defs_to_bindings(&uniform_defs);
 
     shaders.insert_shader(id, Shader {
@@ -141,14 +436,21 @@ pub fn create_shader(
     Ok(id)
 }
 
+/// Assigns bindings to the uniforms in order of their names. Textures take up
+/// two bindings, the texture and its sampler right after it.
 pub fn uniform_defs_to_bindings(
     uniform_defs: &UniformDefs,
 ) -> HashMap<String, u32> {
+    let mut binding = 0;
+
     uniform_defs
         .iter()
         .sorted_by_key(|x| x.0)
-        .enumerate()
-        .map(|(i, (name, _))| (name.clone(), i as u32))
+        .map(|(name, def)| {
+            let result = (name.clone(), binding);
+            binding += def.binding_count();
+            result
+        })
         .collect::<HashMap<String, u32>>()
 }
 
@@ -169,13 +471,27 @@ pub fn build_shader_source(
     for (name, binding) in bindings.iter() {
         let typ = uniform_defs.get(name).unwrap();
 
-        uniforms_src.push_str(&format!(
-            "@group(2) @binding({})
-            var<uniform> {}: {};",
-            binding,
-            name,
-            typ.to_wgsl()
-        ));
+        if typ.is_texture() {
+            uniforms_src.push_str(&format!(
+                "@group(2) @binding({})
+                var {}: {};
+                @group(2) @binding({})
+                var {}_sampler: sampler;",
+                binding,
+                name,
+                typ.to_wgsl(),
+                binding + 1,
+                name,
+            ));
+        } else {
+            uniforms_src.push_str(&format!(
+                "@group(2) @binding({})
+                var<uniform> {}: {};",
+                binding,
+                name,
+                typ.to_wgsl()
+            ));
+        }
     }
 
     format!("{}\n{}", uniforms_src, fragment_source)
@@ -183,3 +499,98 @@ pub fn build_shader_source(
 
 #[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct RenderTargetId(pub u32);
+
+#[test]
+fn test_uniform_layout() {
+    assert_eq!(UniformDef::Vec3(None).size(), 16);
+    assert_eq!(UniformDef::Mat4(None).size(), 64);
+
+    let weights = UniformDef::Array(Box::new(UniformDef::F32(None)), 3);
+    assert_eq!(weights.to_wgsl(), "array<vec4<f32>, 3>");
+    assert_eq!(weights.size(), 48);
+
+    let bytes = weights
+        .encode(&Uniform::Array(vec![1.0f32.into(), 2.0f32.into()]))
+        .unwrap();
+
+    let floats = bytes
+        .chunks(4)
+        .map(|x| f32::from_le_bytes(x.try_into().unwrap()))
+        .collect_vec();
+
+    assert_eq!(floats, [
+        1.0, 0.0, 0.0, 0.0, 2.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0
+    ]);
+
+    assert_eq!(UniformDef::Vec2(None).encode(&1.0f32.into()), None);
+
+    assert_eq!(
+        UniformDef::Color(Some(Color::new(1.0, 0.5, 0.0, 1.0))).default_value(),
+        Some(Uniform::Vec4([1.0, 0.5, 0.0, 1.0].map(OrderedFloat)))
+    );
+}
+
+#[test]
+fn test_uniform_vec3_array_layout() {
+    let points = UniformDef::Array(Box::new(UniformDef::Vec3(None)), 4);
+    assert_eq!(points.to_wgsl(), "array<vec4<f32>, 4>");
+    assert_eq!(points.size(), 64);
+
+    let values = vec![Uniform::from(Vec3::ONE); 4];
+    let bytes = points.encode(&Uniform::Array(values)).unwrap();
+    assert_eq!(bytes.len(), points.size());
+
+    let floats = bytes
+        .chunks(4)
+        .map(|x| f32::from_le_bytes(x.try_into().unwrap()))
+        .collect_vec();
+
+    assert_eq!(floats[12..], [1.0, 1.0, 1.0, 0.0]);
+}
+
+#[test]
+fn test_texture_bindings() {
+    let defs: UniformDefs = [
+        ("a".to_string(), UniformDef::F32(None)),
+        ("noise".to_string(), UniformDef::Texture(None)),
+        ("z".to_string(), UniformDef::Vec2(None)),
+    ]
+    .into_iter()
+    .collect();
+
+    let bindings = uniform_defs_to_bindings(&defs);
+
+    assert_eq!(bindings["a"], 0);
+    assert_eq!(bindings["noise"], 1);
+    assert_eq!(bindings["z"], 3);
+
+    let source = build_shader_source("", &bindings, &defs);
+    assert!(source.contains("var noise_sampler: sampler;"));
+}
+
+#[test]
+fn test_texture_arrays_are_rejected() {
+    let texture = || UniformDef::Texture(None);
+
+    assert!(UniformDef::array(texture(), 4).is_err());
+    assert!(UniformDef::array(UniformDef::F32(None), 0).is_err());
+    assert!(UniformDef::array(UniformDef::F32(None), 4).is_ok());
+
+    // Also when nested, or built without `UniformDef::array`.
+    let nested = UniformDef::Array(
+        Box::new(UniformDef::Array(Box::new(texture()), 2)),
+        2,
+    );
+    assert!(nested.validate().is_err());
+
+    let result = create_shader(
+        &mut ShaderMap::new(),
+        "texture-array",
+        "@vertex fn vs_main() {}",
+        [("noise".to_string(), UniformDef::Array(Box::new(texture()), 4))]
+            .into_iter()
+            .collect(),
+    );
+
+    assert!(result.is_err());
+}
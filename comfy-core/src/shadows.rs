@@ -0,0 +1,168 @@
+use crate::*;
+
+/// Number of directions in the shadow map of every light.
+pub const SHADOW_MAP_RESOLUTION: usize = 512;
+
+/// Only this many lights with `casts_shadows` get a shadow map each frame,
+/// the others are lit as if there were no occluders.
+pub const MAX_SHADOW_LIGHTS: usize = 32;
+
+/// A shape which blocks the light of lights with `casts_shadows`. Occluders
+/// are drawn every frame with `draw_occluder`, just like lights.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Occluder {
+    /// Corners of a closed polygon in world space. Convex polygons work
+    /// best, as only the area behind the polygon is in shadow.
+    pub points: Vec<Vec2>,
+}
+
+impl Occluder {
+    pub fn polygon(points: Vec<Vec2>) -> Self {
+        Self { points }
+    }
+
+    /// An axis aligned rectangle.
+    pub fn rect(center: Vec2, size: Vec2) -> Self {
+        Self::rotated_rect(center, size, 0.0)
+    }
+
+    pub fn rotated_rect(center: Vec2, size: Vec2, rotation: f32) -> Self {
+        let half = size / 2.0;
+        let rotation = vec2(rotation.cos(), rotation.sin());
+
+        let points = [
+            vec2(-half.x, -half.y),
+            vec2(half.x, -half.y),
+            vec2(half.x, half.y),
+            vec2(-half.x, half.y),
+        ]
+        .into_iter()
+        .map(|corner| center + corner.rotate(rotation))
+        .collect();
+
+        Self { points }
+    }
+
+    /// A circle approximated with a 16 sided polygon.
+    pub fn circle(center: Vec2, radius: f32) -> Self {
+        let points = (0..16)
+            .map(|i| {
+                let angle = i as f32 / 16.0 * std::f32::consts::TAU;
+                center + vec2(angle.cos(), angle.sin()) * radius
+            })
+            .collect();
+
+        Self { points }
+    }
+
+    /// Edges of the polygon, including the one closing it.
+    pub fn segments(&self) -> impl Iterator<Item = (Vec2, Vec2)> + '_ {
+        let len = self.points.len();
+
+        (0..len).map(move |i| (self.points[i], self.points[(i + 1) % len]))
+    }
+}
+
+/// Distances from the light to where the shadow of the closest occluder
+/// starts in `resolution` directions, starting at the +X axis and going
+/// counter-clockwise, each taken through the middle of its angle range.
+///
+/// Shadows start at the far side of the occluder, which keeps the occluder
+/// itself lit. Directions in which nothing blocks the light have the light's
+/// `radius`.
+pub fn compute_shadow_map(
+    position: Vec2,
+    radius: f32,
+    occluders: &[Occluder],
+    resolution: usize,
+) -> Vec<f32> {
+    let occluders = occluders
+        .iter()
+        .map(|x| x.segments().collect_vec())
+        .filter(|segments| {
+            segments
+                .iter()
+                .any(|(a, b)| distance_to_segment(position, *a, *b) <= radius)
+        })
+        .collect_vec();
+
+    (0..resolution)
+        .map(|i| {
+            let angle =
+                (i as f32 + 0.5) / resolution as f32 * std::f32::consts::TAU;
+            let direction = vec2(angle.cos(), angle.sin());
+
+            // Where the ray enters and leaves the closest occluder.
+            let mut closest = (radius, radius);
+
+            for segments in occluders.iter() {
+                let (enter, leave) = segments
+                    .iter()
+                    .filter_map(|(a, b)| {
+                        ray_segment_distance(position, direction, *a, *b)
+                    })
+                    .fold((f32::INFINITY, 0.0f32), |(enter, leave), t| {
+                        (enter.min(t), leave.max(t))
+                    });
+
+                if enter < closest.0 {
+                    closest = (enter, leave);
+                }
+            }
+
+            closest.1
+        })
+        .collect()
+}
+
+fn ray_segment_distance(
+    origin: Vec2,
+    direction: Vec2,
+    a: Vec2,
+    b: Vec2,
+) -> Option<f32> {
+    let edge = b - a;
+    let denominator = direction.perp_dot(edge);
+
+    if denominator.abs() < f32::EPSILON {
+        return None;
+    }
+
+    let to_a = a - origin;
+    let t = to_a.perp_dot(edge) / denominator;
+    let u = to_a.perp_dot(direction) / denominator;
+
+    (t >= 0.0 && (0.0..=1.0).contains(&u)).then_some(t)
+}
+
+fn distance_to_segment(point: Vec2, a: Vec2, b: Vec2) -> f32 {
+    let edge = b - a;
+    let t = ((point - a).dot(edge) / edge.length_squared().max(f32::EPSILON))
+        .clamp(0.0, 1.0);
+
+    point.distance(a + edge * t)
+}
+
+#[test]
+fn test_shadow_map() {
+    let wall = Occluder::rect(vec2(3.0, 0.0), vec2(2.0, 10.0));
+    assert_eq!(wall.segments().count(), 4);
+
+    let map = compute_shadow_map(Vec2::ZERO, 5.0, &[wall], 8);
+
+    // The first direction is 22.5 degrees above the +X axis and leaves the
+    // wall through its right edge at x = 4.
+    let expected = 4.0 / (std::f32::consts::PI / 8.0).cos();
+    assert!((map[0] - expected).abs() < 0.001);
+    assert!((map[7] - expected).abs() < 0.001);
+
+    // Nothing blocks the light to the left.
+    assert_eq!(map[3], 5.0);
+    assert_eq!(map[4], 5.0);
+
+    // Occluders outside of the light's radius are ignored.
+    let far = Occluder::circle(vec2(20.0, 0.0), 1.0);
+    assert!(compute_shadow_map(Vec2::ZERO, 5.0, &[far], 8)
+        .iter()
+        .all(|x| *x == 5.0));
+}
@@ -0,0 +1,356 @@
+use crate::*;
+
+use std::{
+    collections::BTreeMap,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+static SPRITE_INSTANCING_ENABLED: AtomicBool = AtomicBool::new(true);
+
+static SPRITE_INSTANCE_QUEUES: Lazy<
+    AtomicRefCell<BTreeMap<MeshGroupKey, SpriteInstanceQueue>>,
+> = Lazy::new(|| AtomicRefCell::new(BTreeMap::new()));
+
+/// When set to `true` (default) sprites drawn with `draw_sprite_ex`,
+/// `draw_sprite_pro` and particles are uploaded to the GPU as a single
+/// `SpriteInstance` each, and the vertex shader expands them into quads.
+///
+/// Setting this to `false` makes them build their vertices on the CPU like
+/// every other mesh, which is mostly useful for debugging.
+///
+/// Sprites drawn with a custom shader or with `rotation_x` always use the
+/// regular mesh path.
+///
+/// Sprites queued together with meshes of the same z-index, blend mode and
+/// texture are drawn as meshes, which keeps them in the order they were
+/// queued in.
+pub fn set_sprite_instancing(enabled: bool) {
+    SPRITE_INSTANCING_ENABLED.store(enabled, Ordering::SeqCst);
+}
+
+pub fn get_sprite_instancing() -> bool {
+    SPRITE_INSTANCING_ENABLED.load(Ordering::SeqCst)
+}
+
+/// Per-instance data of the instanced sprite path. The vertex shader
+/// expands every instance into a quad of `size` centered at `position`,
+/// rotated by `rotation` radians around its center.
+///
+/// Negative `size` components mirror the quad, which is how `flip_x` and
+/// `flip_y` of `draw_sprite_ex` are handled.
+#[repr(C)]
+#[derive(
+    Copy, Clone, Debug, Default, PartialEq, bytemuck::Pod, bytemuck::Zeroable,
+)]
+pub struct SpriteInstance {
+    pub position: [f32; 3],
+    pub rotation: f32,
+    pub size: [f32; 2],
+    /// Texture coordinates of the bottom left and top right corners of the
+    /// quad, i.e. `[u0, v0, u1, v1]`.
+    pub uv_rect: [f32; 4],
+    pub color: [f32; 4],
+    /// Index into the bound texture array when bindless batching is used,
+    /// set by the renderer.
+    pub texture_index: u32,
+}
+
+impl SpriteInstance {
+    pub fn new(
+        position: Vec3,
+        size: Vec2,
+        rotation: f32,
+        uv_rect: [f32; 4],
+        color: Color,
+    ) -> Self {
+        Self {
+            position: position.into(),
+            rotation,
+            size: size.into(),
+            uv_rect,
+            color: [color.r, color.g, color.b, color.a],
+            texture_index: 0,
+        }
+    }
+
+    /// Builds an instance which covers exactly the same quad as
+    /// `rotated_rectangle` called with the same arguments.
+    pub fn from_raw_params(
+        position: Vec3,
+        params: RawDrawParams,
+        tex_width: f32,
+        tex_height: f32,
+        color: Color,
+        scroll_offset: Vec2,
+    ) -> Self {
+        let (x, y) = (position.x, position.y);
+
+        let dims = params
+            .source_rect
+            .map(|rect| {
+                IRect {
+                    size: rect.size,
+                    offset: ivec2(
+                        rect.offset.x,
+                        tex_height as i32 - rect.offset.y - rect.size.y,
+                    ),
+                }
+            })
+            .unwrap_or(IRect::new(
+                ivec2(0, 0),
+                ivec2(tex_width as i32, tex_height as i32),
+            ));
+
+        let mut size = params.dest_size.unwrap_or(Vec2::ONE);
+
+        if params.flip_x {
+            size.x = -size.x;
+        }
+        if params.flip_y {
+            size.y = -size.y;
+        }
+
+        let center = match params.pivot {
+            Some(pivot) => {
+                Vec2::from_angle(params.rotation)
+                    .rotate(vec2(x, y) + size / 2.0 - pivot) +
+                    pivot -
+                    size / 2.0
+            }
+            None => vec2(x, y),
+        };
+
+        let uv_min = dims.offset.as_vec2() / vec2(tex_width, tex_height);
+        let uv_max =
+            (dims.offset + dims.size).as_vec2() / vec2(tex_width, tex_height);
+
+        Self::new(
+            center.extend(position.z),
+            size,
+            params.rotation,
+            [
+                uv_min.x + scroll_offset.x,
+                uv_min.y + scroll_offset.y,
+                uv_max.x + scroll_offset.x,
+                uv_max.y + scroll_offset.y,
+            ],
+            color,
+        )
+    }
+
+    /// Expands the instance on the CPU, producing the same vertices as the
+    /// instanced vertex shader. Used when instances need to be sorted
+    /// together with regular meshes.
+    pub fn to_vertices(&self) -> [SpriteVertex; 4] {
+        let center = Vec3::from(self.position);
+        let size = Vec2::from(self.size);
+        let rotation = Vec2::from_angle(self.rotation);
+        let [u0, v0, u1, v1] = self.uv_rect;
+
+        let color = Color::new(
+            self.color[0],
+            self.color[1],
+            self.color[2],
+            self.color[3],
+        );
+
+        [vec2(0.0, 0.0), vec2(1.0, 0.0), vec2(1.0, 1.0), vec2(0.0, 1.0)].map(
+            |corner| {
+                let offset = rotation.rotate((corner - 0.5) * size);
+
+                SpriteVertex::new(
+                    center + offset.extend(0.0),
+                    vec2(lerp(u0, u1, corner.x), lerp(v0, v1, corner.y)),
+                    color,
+                )
+            },
+        )
+    }
+}
+
+/// Sprite instances of a single `MeshGroupKey`. `sort_origins` holds the y
+/// coordinate each instance would be y-sorted by as a regular mesh.
+#[derive(Clone, Debug, Default)]
+pub struct SpriteInstanceQueue {
+    pub instances: Vec<SpriteInstance>,
+    pub sort_origins: Vec<f32>,
+}
+
+impl SpriteInstanceQueue {
+    /// Converts the instances into regular quad meshes, used by the renderer
+    /// for y-sorted z-indexes.
+    pub fn into_meshes(
+        self,
+        z_index: i32,
+        texture: TextureHandle,
+    ) -> impl Iterator<Item = Mesh> {
+        const QUAD_INDICES_U32: &[u32] = &[0, 2, 1, 0, 3, 2];
+
+        self.instances.into_iter().zip(self.sort_origins).map(
+            move |(instance, sort_origin)| {
+                Mesh {
+                    origin: vec3(
+                        instance.position[0],
+                        sort_origin,
+                        z_index as f32,
+                    ),
+                    vertices: SmallVec::from_slice(&instance.to_vertices()),
+                    indices: QUAD_INDICES_U32.into(),
+                    z_index,
+                    texture: Some(texture),
+                    y_sort_offset: 0.0,
+                }
+            },
+        )
+    }
+}
+
+pub fn consume_sprite_instance_queues(
+) -> BTreeMap<MeshGroupKey, SpriteInstanceQueue> {
+    let mut queues = SPRITE_INSTANCE_QUEUES.borrow_mut();
+    let mut new_data = BTreeMap::new();
+    std::mem::swap(&mut new_data, &mut *queues);
+    new_data
+}
+
+/// Removes the instances queued with the key, used when a mesh is queued
+/// with the same key.
+pub(crate) fn take_sprite_instance_queue(
+    key: &MeshGroupKey,
+) -> Option<SpriteInstanceQueue> {
+    SPRITE_INSTANCE_QUEUES.borrow_mut().remove(key)
+}
+
+/// Queues a sprite instance, `sort_origin` is the y coordinate (including
+/// any y-sort offset) used if the z-index is y-sorted.
+///
+/// If meshes were already queued with the same key the sprite is queued as
+/// a mesh after them instead, so that the two keep their order.
+pub fn queue_sprite_instance(
+    instance: SpriteInstance,
+    z_index: i32,
+    texture: TextureHandle,
+    blend_mode: BlendMode,
+    sort_origin: f32,
+) {
+    let key = MeshGroupKey {
+        z_index,
+        blend_mode,
+        texture_id: texture,
+        shader: ShaderInstanceId::default(),
+        render_target: get_current_render_target(),
+        clip: get_current_clip(),
+        layers: get_current_render_layers(),
+    };
+
+    let queued_as_mesh = extend_existing_mesh_queue(&key, || {
+        SpriteInstanceQueue {
+            instances: vec![instance],
+            sort_origins: vec![sort_origin],
+        }
+        .into_meshes(z_index, texture)
+        .collect()
+    });
+
+    if queued_as_mesh {
+        return;
+    }
+
+    let mut queues = SPRITE_INSTANCE_QUEUES.borrow_mut();
+    let queue = queues.entry(key).or_default();
+
+    queue.instances.push(instance);
+    queue.sort_origins.push(sort_origin);
+}
+
+#[test]
+fn test_sprite_instance_matches_rotated_rectangle() {
+    let params = RawDrawParams {
+        dest_size: Some(vec2(2.0, 3.0)),
+        source_rect: Some(IRect::new(ivec2(4, 8), ivec2(16, 8))),
+        rotation: 0.7,
+        flip_x: true,
+        flip_y: false,
+        pivot: Some(vec2(1.5, -2.0)),
+    };
+
+    let position = vec3(3.0, 4.0, 0.5);
+    let scroll = vec2(0.25, 0.0);
+
+    let expected =
+        rotated_rectangle(position, params, 64.0, 32.0, WHITE, scroll);
+    let actual = SpriteInstance::from_raw_params(
+        position, params, 64.0, 32.0, WHITE, scroll,
+    )
+    .to_vertices();
+
+    for (a, b) in actual.iter().zip(expected.iter()) {
+        for i in 0..3 {
+            assert!((a.position[i] - b.position[i]).abs() < 1e-5);
+        }
+        for i in 0..2 {
+            assert!((a.tex_coords[i] - b.tex_coords[i]).abs() < 1e-5);
+        }
+    }
+}
+
+#[test]
+fn test_sprite_instances_keep_order_with_meshes() {
+    let texture = TextureHandle::from_path("instance-order");
+    let other = TextureHandle::from_path("instance-order-other");
+    let z_index = 7331;
+
+    let instance = |x: f32| {
+        SpriteInstance::new(vec3(x, 0.0, 0.0), Vec2::ONE, 0.0, [0.0; 4], WHITE)
+    };
+
+    let mesh = |x: f32| {
+        Mesh {
+            origin: vec3(x, 0.0, 0.0),
+            z_index,
+            texture: Some(texture),
+            ..Default::default()
+        }
+    };
+
+    queue_sprite_instance(
+        instance(1.0),
+        z_index,
+        texture,
+        BlendMode::Alpha,
+        0.0,
+    );
+    queue_mesh_draw(mesh(2.0), BlendMode::Alpha);
+    queue_sprite_instance(
+        instance(3.0),
+        z_index,
+        texture,
+        BlendMode::Alpha,
+        0.0,
+    );
+    queue_sprite_instance(instance(4.0), z_index, other, BlendMode::Alpha, 0.0);
+
+    let meshes = consume_render_queues();
+    let instances = consume_sprite_instance_queues();
+
+    let key = |texture_id| {
+        MeshGroupKey {
+            z_index,
+            blend_mode: BlendMode::Alpha,
+            texture_id,
+            shader: ShaderInstanceId::default(),
+            render_target: RenderTargetId::default(),
+            clip: ClipId::default(),
+            layers: RenderLayers::DEFAULT,
+        }
+    };
+
+    // Everything with the same key is drawn as meshes, in the queued order.
+    let order = meshes[&key(texture)].iter().map(|x| x.origin.x).collect_vec();
+    assert_eq!(order, vec![1.0, 2.0, 3.0]);
+    assert!(!instances.contains_key(&key(texture)));
+
+    // Sprites without meshes of the same key stay instanced.
+    assert_eq!(instances[&key(other)].instances.len(), 1);
+    assert!(!meshes.contains_key(&key(other)));
+}
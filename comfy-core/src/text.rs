@@ -9,6 +9,7 @@ pub struct DrawText {
     pub color: Color,
     pub align: TextAlign,
     pub z_index: i32,
+    pub layers: RenderLayers,
     // Temporarily to allow both egui and comfy rasterization
     pub pro_params: Option<ProTextParams>,
 }
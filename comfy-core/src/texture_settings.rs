@@ -0,0 +1,124 @@
+use crate::*;
+
+static TEXTURE_SETTINGS: Lazy<
+    AtomicRefCell<HashMap<TextureHandle, TextureSettings>>,
+> = Lazy::new(|| AtomicRefCell::new(HashMap::default()));
+
+static TEXTURE_SETTINGS_CHANGES: Lazy<
+    AtomicRefCell<Vec<(TextureHandle, TextureSettings)>>,
+> = Lazy::new(|| AtomicRefCell::new(Vec::new()));
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum TextureFilter {
+    /// Picks the closest texel, keeps pixel art crisp.
+    Nearest,
+    /// Interpolates between neighbouring texels, for smooth/HD art.
+    Linear,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum TextureWrap {
+    /// UVs outside of `0..1` repeat the edge texels.
+    Clamp,
+    /// UVs outside of `0..1` tile the texture, useful for scrolling
+    /// backgrounds.
+    Repeat,
+    /// Like `Repeat`, but every other tile is mirrored.
+    MirrorRepeat,
+}
+
+/// Sampler settings of a single texture.
+///
+/// Settings can be given before the texture is loaded (e.g. with
+/// `load_texture_with_settings`), in which case they're used when creating
+/// it, or changed at any time later with `set_texture_settings`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TextureSettings {
+    pub filter: TextureFilter,
+    pub wrap: TextureWrap,
+    /// Generates a full mip chain when the texture is created, which avoids
+    /// aliasing when the texture is drawn smaller than its size. Turning
+    /// this on for an already loaded texture re-creates it.
+    pub mipmaps: bool,
+}
+
+impl TextureSettings {
+    pub const PIXEL_ART: Self = Self {
+        filter: TextureFilter::Nearest,
+        wrap: TextureWrap::Clamp,
+        mipmaps: false,
+    };
+
+    pub const SMOOTH: Self = Self {
+        filter: TextureFilter::Linear,
+        wrap: TextureWrap::Clamp,
+        mipmaps: true,
+    };
+
+    pub fn with_filter(self, filter: TextureFilter) -> Self {
+        Self { filter, ..self }
+    }
+
+    pub fn with_wrap(self, wrap: TextureWrap) -> Self {
+        Self { wrap, ..self }
+    }
+
+    pub fn with_mipmaps(self, mipmaps: bool) -> Self {
+        Self { mipmaps, ..self }
+    }
+}
+
+impl Default for TextureSettings {
+    fn default() -> Self {
+        Self::PIXEL_ART
+    }
+}
+
+/// Changes sampler settings of a texture. The renderer applies the change at
+/// the start of the next frame, or once the texture finishes loading.
+pub fn set_texture_settings(texture: TextureHandle, settings: TextureSettings) {
+    let previous = TEXTURE_SETTINGS.borrow_mut().insert(texture, settings);
+
+    if previous != Some(settings) {
+        TEXTURE_SETTINGS_CHANGES.borrow_mut().push((texture, settings));
+    }
+}
+
+/// Settings explicitly given for the texture, `None` means the loading path
+/// uses its own defaults.
+pub fn texture_settings(texture: TextureHandle) -> Option<TextureSettings> {
+    TEXTURE_SETTINGS.borrow().get(&texture).copied()
+}
+
+pub fn consume_texture_settings_changes(
+) -> Vec<(TextureHandle, TextureSettings)> {
+    let mut changes = TEXTURE_SETTINGS_CHANGES.borrow_mut();
+    let mut new_data = Vec::new();
+    std::mem::swap(&mut new_data, &mut *changes);
+    new_data
+}
+
+/// Queues a texture for loading just like `load_multiple_textures`, using
+/// the given sampler settings.
+pub fn load_texture_with_settings(
+    name: &str,
+    path: &str,
+    settings: TextureSettings,
+) {
+    set_texture_settings(texture_id_unchecked(name), settings);
+    load_multiple_textures(vec![(name.to_string(), path.to_string())]);
+}
+
+/// Number of mip levels of a full mip chain for the given size.
+pub fn mip_level_count(width: u32, height: u32) -> u32 {
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+#[test]
+fn test_mip_level_count() {
+    assert_eq!(mip_level_count(1, 1), 1);
+    assert_eq!(mip_level_count(2, 1), 2);
+    assert_eq!(mip_level_count(16, 16), 5);
+    assert_eq!(mip_level_count(17, 3), 5);
+    assert_eq!(mip_level_count(256, 512), 10);
+}
@@ -0,0 +1,586 @@
+use crate::*;
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static TILEMAP_IDS: AtomicU64 = AtomicU64::new(1);
+
+static TILEMAP_QUEUE: Lazy<AtomicRefCell<Vec<TilemapChunkDraw>>> =
+    Lazy::new(|| AtomicRefCell::new(Vec::new()));
+
+static RELEASED_TILEMAPS: Lazy<AtomicRefCell<Vec<TilemapId>>> =
+    Lazy::new(|| AtomicRefCell::new(Vec::new()));
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TilemapId(pub u64);
+
+/// Identifies a single chunk of a tilemap, used by the renderer to cache its
+/// GPU buffers between frames.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TilemapChunkId {
+    pub tilemap: TilemapId,
+    pub chunk: UVec2,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Tile {
+    /// Index of the tile in the tileset, counted row by row from the top
+    /// left corner of the texture.
+    pub index: u32,
+    pub flip_x: bool,
+    pub flip_y: bool,
+}
+
+impl Tile {
+    pub fn new(index: u32) -> Self {
+        Self { index, flip_x: false, flip_y: false }
+    }
+}
+
+/// Vertex data of a single chunk, built on the CPU only when the chunk
+/// changes. Every tile is a quad of 4 vertices and 6 indices.
+#[derive(Clone, Debug, Default)]
+pub struct TilemapChunkMesh {
+    pub vertices: Vec<SpriteVertex>,
+    pub indices: Vec<u32>,
+}
+
+impl TilemapChunkMesh {
+    /// Splits the chunk into one mesh per tile, with the origin at the
+    /// tile's center. Used by the renderer when the chunk's z-index is
+    /// y-sorted, since the tiles then have to be sorted together with other
+    /// meshes.
+    pub fn tile_meshes(
+        &self,
+        z_index: i32,
+        texture: TextureHandle,
+    ) -> impl Iterator<Item = Mesh> + '_ {
+        const QUAD_INDICES_U32: &[u32] = &[0, 2, 1, 0, 3, 2];
+
+        self.vertices.chunks_exact(4).map(move |quad| {
+            let center = quad
+                .iter()
+                .map(|v| Vec3::from(v.position))
+                .fold(Vec3::ZERO, |acc, p| acc + p) /
+                4.0;
+
+            Mesh {
+                origin: center.truncate().extend(z_index as f32),
+                vertices: SmallVec::from_slice(quad),
+                indices: QUAD_INDICES_U32.into(),
+                z_index,
+                texture: Some(texture),
+                y_sort_offset: 0.0,
+            }
+        })
+    }
+}
+
+/// A visible chunk queued for rendering by `Tilemap::draw`.
+#[derive(Clone, Debug)]
+pub struct TilemapChunkDraw {
+    pub id: TilemapChunkId,
+    /// Incremented every time the chunk changes, the renderer only
+    /// re-uploads the chunk's buffers when this differs from what it has.
+    pub version: u64,
+    pub z_index: i32,
+    pub blend_mode: BlendMode,
+    pub texture: TextureHandle,
+    pub shader: ShaderInstanceId,
+    pub render_target: RenderTargetId,
+    pub clip: ClipId,
+    pub layers: RenderLayers,
+    pub mesh: Arc<TilemapChunkMesh>,
+}
+
+pub fn consume_tilemap_queue() -> Vec<TilemapChunkDraw> {
+    let mut queue = TILEMAP_QUEUE.borrow_mut();
+    let mut new_data = Vec::new();
+    std::mem::swap(&mut *queue, &mut new_data);
+    new_data
+}
+
+/// Returns ids of tilemaps dropped since the last call, so that the renderer
+/// can free their chunk buffers.
+pub fn consume_released_tilemaps() -> Vec<TilemapId> {
+    let mut released = RELEASED_TILEMAPS.borrow_mut();
+    let mut new_data = Vec::new();
+    std::mem::swap(&mut *released, &mut new_data);
+    new_data
+}
+
+#[derive(Default)]
+struct TilemapChunk {
+    version: u64,
+    mesh: AtomicRefCell<Option<Arc<TilemapChunkMesh>>>,
+}
+
+/// A grid of tiles from a single tileset texture, rendered in chunks whose
+/// vertex buffers are built once and only re-uploaded when a tile inside
+/// them changes. Chunks outside of the camera bounds are skipped entirely,
+/// which makes drawing large maps cheap compared to calling `draw_sprite_ex`
+/// for every tile.
+///
+/// Tile coordinates start at the bottom left corner of the map, which is at
+/// `position` in world space.
+///
+/// ```ignore
+/// let mut map = Tilemap::new(texture_id("tileset"), uvec2(16, 16), 500, 500);
+/// map.fill(Some(Tile::new(3)));
+/// map.set_tile(10, 10, Some(Tile::new(7)));
+///
+/// // every frame
+/// map.draw();
+/// ```
+///
+/// If the tilemap's z-index has y-sort enabled, its visible chunks are drawn
+/// tile by tile together with other meshes so that they can be sorted.
+pub struct Tilemap {
+    id: TilemapId,
+    texture: TextureHandle,
+    tileset_tile_size: UVec2,
+    tileset_padding: u32,
+    tileset_spacing: u32,
+    width: u32,
+    height: u32,
+    chunk_size: u32,
+    tiles: Vec<Option<Tile>>,
+    chunks: Vec<TilemapChunk>,
+
+    position: Vec2,
+    tile_size: Vec2,
+    color: Color,
+    z_index: i32,
+    blend_mode: BlendMode,
+}
+
+impl Tilemap {
+    pub const DEFAULT_CHUNK_SIZE: u32 = 32;
+
+    /// Creates an empty tilemap of `width * height` tiles, each one world
+    /// unit in size. `tileset_tile_size` is the size of a single tile in the
+    /// tileset texture in pixels.
+    pub fn new(
+        texture: TextureHandle,
+        tileset_tile_size: UVec2,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        Self::with_chunk_size(
+            texture,
+            tileset_tile_size,
+            width,
+            height,
+            Self::DEFAULT_CHUNK_SIZE,
+        )
+    }
+
+    pub fn with_chunk_size(
+        texture: TextureHandle,
+        tileset_tile_size: UVec2,
+        width: u32,
+        height: u32,
+        chunk_size: u32,
+    ) -> Self {
+        assert!(chunk_size > 0, "tilemap chunk size must be positive");
+
+        let chunks_x = width.div_ceil(chunk_size);
+        let chunks_y = height.div_ceil(chunk_size);
+
+        Self {
+            id: TilemapId(TILEMAP_IDS.fetch_add(1, Ordering::SeqCst)),
+            texture,
+            tileset_tile_size,
+            tileset_padding: 0,
+            tileset_spacing: 0,
+            width,
+            height,
+            chunk_size,
+            tiles: vec![None; (width * height) as usize],
+            chunks: (0..chunks_x * chunks_y)
+                .map(|_| TilemapChunk::default())
+                .collect(),
+
+            position: Vec2::ZERO,
+            tile_size: Vec2::ONE,
+            color: WHITE,
+            z_index: 0,
+            blend_mode: BlendMode::None,
+        }
+    }
+
+    pub fn id(&self) -> TilemapId {
+        self.id
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn chunk_size(&self) -> u32 {
+        self.chunk_size
+    }
+
+    pub fn texture(&self) -> TextureHandle {
+        self.texture
+    }
+
+    pub fn position(&self) -> Vec2 {
+        self.position
+    }
+
+    /// Size of a single tile in world units.
+    pub fn tile_size(&self) -> Vec2 {
+        self.tile_size
+    }
+
+    pub fn color(&self) -> Color {
+        self.color
+    }
+
+    pub fn z_index(&self) -> i32 {
+        self.z_index
+    }
+
+    pub fn blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+
+    pub fn set_texture(&mut self, texture: TextureHandle, tile_size: UVec2) {
+        self.texture = texture;
+        self.tileset_tile_size = tile_size;
+        self.mark_all_dirty();
+    }
+
+    /// Pixels around the tiles of the tileset texture (`padding`) and between
+    /// them (`spacing`), both zero by default.
+    pub fn set_tileset_spacing(&mut self, padding: u32, spacing: u32) {
+        if (self.tileset_padding, self.tileset_spacing) != (padding, spacing) {
+            self.tileset_padding = padding;
+            self.tileset_spacing = spacing;
+            self.mark_all_dirty();
+        }
+    }
+
+    pub fn set_position(&mut self, position: Vec2) {
+        if self.position != position {
+            self.position = position;
+            self.mark_all_dirty();
+        }
+    }
+
+    pub fn set_tile_size(&mut self, tile_size: Vec2) {
+        if self.tile_size != tile_size {
+            self.tile_size = tile_size;
+            self.mark_all_dirty();
+        }
+    }
+
+    pub fn set_color(&mut self, color: Color) {
+        if self.color != color {
+            self.color = color;
+            self.mark_all_dirty();
+        }
+    }
+
+    pub fn set_z_index(&mut self, z_index: i32) {
+        if self.z_index != z_index {
+            self.z_index = z_index;
+            self.mark_all_dirty();
+        }
+    }
+
+    pub fn set_blend_mode(&mut self, blend_mode: BlendMode) {
+        self.blend_mode = blend_mode;
+    }
+
+    pub fn in_bounds(&self, x: u32, y: u32) -> bool {
+        x < self.width && y < self.height
+    }
+
+    pub fn tile(&self, x: u32, y: u32) -> Option<Tile> {
+        if self.in_bounds(x, y) {
+            self.tiles[(x + y * self.width) as usize]
+        } else {
+            None
+        }
+    }
+
+    /// Sets a tile, marking its chunk dirty only if the tile actually
+    /// changed. Out of bounds coordinates are ignored.
+    pub fn set_tile(&mut self, x: u32, y: u32, tile: Option<Tile>) {
+        if !self.in_bounds(x, y) {
+            return;
+        }
+
+        let index = (x + y * self.width) as usize;
+
+        if self.tiles[index] != tile {
+            self.tiles[index] = tile;
+
+            let chunk = self
+                .chunk_index(uvec2(x / self.chunk_size, y / self.chunk_size));
+            self.mark_chunk_dirty(chunk);
+        }
+    }
+
+    pub fn fill(&mut self, tile: Option<Tile>) {
+        self.tiles.fill(tile);
+        self.mark_all_dirty();
+    }
+
+    pub fn clear(&mut self) {
+        self.fill(None);
+    }
+
+    /// Converts a world position into tile coordinates, returning `None`
+    /// outside of the map.
+    pub fn world_to_tile(&self, world_pos: Vec2) -> Option<UVec2> {
+        let local = (world_pos - self.position) / self.tile_size;
+
+        if local.x < 0.0 || local.y < 0.0 {
+            return None;
+        }
+
+        let tile = local.floor().as_uvec2();
+        self.in_bounds(tile.x, tile.y).then_some(tile)
+    }
+
+    /// Center of the given tile in world space.
+    pub fn tile_to_world(&self, x: u32, y: u32) -> Vec2 {
+        self.position + (uvec2(x, y).as_vec2() + 0.5) * self.tile_size
+    }
+
+    /// World space bounds of the whole map.
+    pub fn bounds(&self) -> AABB {
+        AABB::new(
+            self.position,
+            self.position +
+                uvec2(self.width, self.height).as_vec2() * self.tile_size,
+        )
+    }
+
+    /// Queues all chunks visible by the main camera for rendering. Chunks
+    /// whose tiles changed since the last draw are rebuilt first.
+    pub fn draw(&self) {
+        let camera = CAMERA_BOUNDS.load();
+        self.draw_in(AABB::from_center_size(camera.center, camera.size));
+    }
+
+    /// Same as `draw`, but only draws chunks intersecting the given world
+    /// space bounds.
+    pub fn draw_in(&self, view: AABB) {
+        let _span = span!("Tilemap::draw");
+
+        let texture_size = match Assets::image_size(self.texture) {
+            ImageSizeResult::Loaded(size) => size,
+            ImageSizeResult::LoadingInProgress => return,
+            ImageSizeResult::ImageNotFound => {
+                error!("NO SIZE FOR TILEMAP TEXTURE {:?}", self.texture);
+                return;
+            }
+        };
+
+        let chunk_world_size = self.tile_size * self.chunk_size as f32;
+        let chunks = uvec2(
+            self.width.div_ceil(self.chunk_size),
+            self.height.div_ceil(self.chunk_size),
+        );
+
+        if chunks.x == 0 || chunks.y == 0 {
+            return;
+        }
+
+        let min = ((view.min - self.position) / chunk_world_size).floor();
+        let max = ((view.max - self.position) / chunk_world_size).floor();
+
+        if max.x < 0.0 ||
+            max.y < 0.0 ||
+            min.x >= chunks.x as f32 ||
+            min.y >= chunks.y as f32
+        {
+            return;
+        }
+
+        let min = min.max(Vec2::ZERO).as_uvec2();
+        let max = max.as_uvec2().min(chunks - 1);
+
+        let shader = get_current_shader();
+        let render_target = get_current_render_target();
+        let clip = get_current_clip();
+        let layers = get_current_render_layers();
+
+        let mut queue = TILEMAP_QUEUE.borrow_mut();
+
+        for cy in min.y..=max.y {
+            for cx in min.x..=max.x {
+                let chunk_coord = uvec2(cx, cy);
+                let chunk = &self.chunks[self.chunk_index(chunk_coord)];
+
+                let mesh = chunk
+                    .mesh
+                    .borrow_mut()
+                    .get_or_insert_with(|| {
+                        Arc::new(
+                            self.build_chunk_mesh(chunk_coord, texture_size),
+                        )
+                    })
+                    .clone();
+
+                if mesh.indices.is_empty() {
+                    continue;
+                }
+
+                perf_counter_inc("tilemap chunks", 1);
+
+                queue.push(TilemapChunkDraw {
+                    id: TilemapChunkId { tilemap: self.id, chunk: chunk_coord },
+                    version: chunk.version,
+                    z_index: self.z_index,
+                    blend_mode: self.blend_mode,
+                    texture: self.texture,
+                    shader,
+                    render_target,
+                    clip,
+                    layers,
+                    mesh,
+                });
+            }
+        }
+    }
+
+    fn chunk_index(&self, chunk: UVec2) -> usize {
+        (chunk.x + chunk.y * self.width.div_ceil(self.chunk_size)) as usize
+    }
+
+    fn mark_chunk_dirty(&mut self, index: usize) {
+        let chunk = &mut self.chunks[index];
+        chunk.version += 1;
+        *chunk.mesh.get_mut() = None;
+    }
+
+    fn mark_all_dirty(&mut self) {
+        for i in 0..self.chunks.len() {
+            self.mark_chunk_dirty(i);
+        }
+    }
+
+    fn build_chunk_mesh(
+        &self,
+        chunk: UVec2,
+        texture_size: UVec2,
+    ) -> TilemapChunkMesh {
+        let _span = span!("Tilemap::build_chunk_mesh");
+
+        let mut mesh = TilemapChunkMesh::default();
+
+        let padding = self.tileset_padding;
+        let spacing = self.tileset_spacing;
+        let stride = self.tileset_tile_size + spacing;
+
+        let columns = ((texture_size.x.saturating_sub(2 * padding) + spacing) /
+            stride.x.max(1))
+        .max(1);
+        let z = self.z_index as f32 / Z_DIV;
+
+        let start = chunk * self.chunk_size;
+        let end = (start + self.chunk_size).min(uvec2(self.width, self.height));
+
+        for y in start.y..end.y {
+            for x in start.x..end.x {
+                let Some(tile) = self.tiles[(x + y * self.width) as usize]
+                else {
+                    continue;
+                };
+
+                let src = padding +
+                    uvec2(tile.index % columns, tile.index / columns) *
+                        stride;
+
+                // Same as `draw_sprite_pro`, texture Y is measured from the
+                // bottom of the image.
+                let uv_min = vec2(
+                    src.x as f32,
+                    (texture_size.y as i32 -
+                        src.y as i32 -
+                        self.tileset_tile_size.y as i32)
+                        as f32,
+                ) / texture_size.as_vec2();
+                let uv_size =
+                    self.tileset_tile_size.as_vec2() / texture_size.as_vec2();
+
+                let (mut u0, mut u1) = (uv_min.x, uv_min.x + uv_size.x);
+                let (mut v0, mut v1) = (uv_min.y, uv_min.y + uv_size.y);
+
+                if tile.flip_x {
+                    std::mem::swap(&mut u0, &mut u1);
+                }
+                if tile.flip_y {
+                    std::mem::swap(&mut v0, &mut v1);
+                }
+
+                let p0 = self.position + uvec2(x, y).as_vec2() * self.tile_size;
+                let p1 = p0 + self.tile_size;
+
+                let offset = mesh.vertices.len() as u32;
+
+                mesh.vertices.extend([
+                    SpriteVertex::new(
+                        vec3(p0.x, p0.y, z),
+                        vec2(u0, v0),
+                        self.color,
+                    ),
+                    SpriteVertex::new(
+                        vec3(p1.x, p0.y, z),
+                        vec2(u1, v0),
+                        self.color,
+                    ),
+                    SpriteVertex::new(
+                        vec3(p1.x, p1.y, z),
+                        vec2(u1, v1),
+                        self.color,
+                    ),
+                    SpriteVertex::new(
+                        vec3(p0.x, p1.y, z),
+                        vec2(u0, v1),
+                        self.color,
+                    ),
+                ]);
+
+                mesh.indices
+                    .extend([0, 2, 1, 0, 3, 2].iter().map(|i| i + offset));
+            }
+        }
+
+        mesh
+    }
+}
+
+impl Drop for Tilemap {
+    fn drop(&mut self) {
+        RELEASED_TILEMAPS.borrow_mut().push(self.id);
+    }
+}
+
+#[test]
+fn test_tilemap_dirty_chunks() {
+    let texture = texture_id_unchecked("tileset");
+    let mut map = Tilemap::with_chunk_size(texture, uvec2(1, 1), 10, 10, 4);
+
+    assert_eq!(map.chunks.len(), 9);
+
+    map.set_tile(5, 9, Some(Tile::new(0)));
+    assert_eq!(map.chunks[map.chunk_index(uvec2(1, 2))].version, 1);
+    assert_eq!(map.chunks[0].version, 0);
+
+    // Setting the same tile again doesn't dirty the chunk.
+    map.set_tile(5, 9, Some(Tile::new(0)));
+    assert_eq!(map.chunks[map.chunk_index(uvec2(1, 2))].version, 1);
+
+    assert_eq!(map.world_to_tile(vec2(5.5, 9.2)), Some(uvec2(5, 9)));
+    assert_eq!(map.world_to_tile(vec2(-0.5, 2.0)), None);
+    assert_eq!(map.world_to_tile(vec2(10.5, 2.0)), None);
+}
@@ -0,0 +1,253 @@
+use crate::*;
+
+use comfy_core::spatial_hash::{AabbShape, Shape, SpatialHash, UserData};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum LdtkColliderKind {
+    Solid,
+    /// Platforms that can be jumped through from below. These are only
+    /// merged horizontally, so that each collider is a single row of cells.
+    OneWay,
+}
+
+/// Which IntGrid values produce colliders. Values not listed in either
+/// are ignored.
+#[derive(Clone, Debug, Default)]
+pub struct IntGridCollisionConfig {
+    pub solid: Vec<i64>,
+    pub one_way: Vec<i64>,
+}
+
+impl IntGridCollisionConfig {
+    pub fn solid(values: &[i64]) -> Self {
+        Self { solid: values.to_vec(), one_way: vec![] }
+    }
+
+    pub fn with_one_way(mut self, values: &[i64]) -> Self {
+        self.one_way = values.to_vec();
+        self
+    }
+
+    pub fn kind(&self, value: i64) -> Option<LdtkColliderKind> {
+        if self.solid.contains(&value) {
+            Some(LdtkColliderKind::Solid)
+        } else if self.one_way.contains(&value) {
+            Some(LdtkColliderKind::OneWay)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct LdtkCollider {
+    pub kind: LdtkColliderKind,
+    /// Collider bounds in world coordinates.
+    pub aabb: AABB,
+    /// The merged cells in grid coordinates of the layer, with the origin at
+    /// the top left like in LDtk.
+    pub cells: IRect,
+}
+
+impl LdtkCollider {
+    pub fn shape(&self) -> Shape {
+        Shape::Aabb(AabbShape { min: self.aabb.min, max: self.aabb.max })
+    }
+}
+
+/// Merges filled cells of a grid into as few rectangles as possible using
+/// greedy meshing. Cells are scanned row by row from the top left, each
+/// rectangle grows to the right first and then down as long as the whole
+/// row below is filled.
+///
+/// When `merge_vertically` is `false` every rectangle is a single row high.
+pub fn greedy_merge_cells(
+    width: i32,
+    height: i32,
+    merge_vertically: bool,
+    is_filled: impl Fn(i32, i32) -> bool,
+) -> Vec<IRect> {
+    let mut visited = vec![false; (width * height).max(0) as usize];
+    let mut result = Vec::new();
+
+    let index = |x: i32, y: i32| (x + y * width) as usize;
+
+    for y in 0..height {
+        for x in 0..width {
+            if visited[index(x, y)] || !is_filled(x, y) {
+                continue;
+            }
+
+            let mut w = 1;
+            while x + w < width &&
+                !visited[index(x + w, y)] &&
+                is_filled(x + w, y)
+            {
+                w += 1;
+            }
+
+            let mut h = 1;
+            if merge_vertically {
+                while y + h < height &&
+                    (x..x + w).all(|cx| {
+                        !visited[index(cx, y + h)] && is_filled(cx, y + h)
+                    })
+                {
+                    h += 1;
+                }
+            }
+
+            for cy in y..y + h {
+                for cx in x..x + w {
+                    visited[index(cx, cy)] = true;
+                }
+            }
+
+            result.push(IRect::new(ivec2(x, y), ivec2(w, h)));
+        }
+    }
+
+    result
+}
+
+/// Builds colliders from an IntGrid layer, merging neighbouring cells of
+/// the same kind into rectangles.
+///
+/// Colliders are measured in units of `pixels_per_unit` LDtk pixels with the
+/// level's bottom left corner at `offset`, like the tilemaps and entities of
+/// `LdtkWorldStreamer`, which keeps them in place when the layer's grid size
+/// differs from other layers. Passing the layer's `grid_size` gives the same
+/// coordinates as `LdtkLayerExtensions::grid_to_world`, i.e. one unit per
+/// cell.
+pub fn intgrid_colliders(
+    layer: &LayerInstance,
+    config: &IntGridCollisionConfig,
+    offset: Vec2,
+    pixels_per_unit: f32,
+) -> Vec<LdtkCollider> {
+    let width = layer.c_wid as i32;
+    let height = layer.c_hei as i32;
+    let cell_size = layer.grid_size as f32 / pixels_per_unit;
+
+    let layer_offset = offset +
+        vec2(layer.px_total_offset_x as f32, -layer.px_total_offset_y as f32) /
+            pixels_per_unit;
+
+    let kind_at = |x: i32, y: i32| {
+        layer
+            .int_grid_csv
+            .get((x + y * width) as usize)
+            .and_then(|value| config.kind(*value))
+    };
+
+    let mut result = Vec::new();
+
+    for kind in [LdtkColliderKind::Solid, LdtkColliderKind::OneWay] {
+        let merge_vertically = kind == LdtkColliderKind::Solid;
+
+        let rects =
+            greedy_merge_cells(width, height, merge_vertically, |x, y| {
+                kind_at(x, y) == Some(kind)
+            });
+
+        result.extend(rects.into_iter().map(|cells| {
+            let min = vec2(
+                cells.offset.x as f32,
+                (height - cells.offset.y - cells.size.y) as f32,
+            ) * cell_size;
+
+            LdtkCollider {
+                kind,
+                aabb: AABB::new(
+                    layer_offset + min,
+                    layer_offset + min + cells.size.as_vec2() * cell_size,
+                ),
+                cells,
+            }
+        }));
+    }
+
+    result
+}
+
+/// Adds colliders into a `SpatialHash`, with `user_data` deciding what gets
+/// stored for each of them (e.g. a different `entity_type` for one-way
+/// platforms).
+pub fn add_colliders_to_spatial_hash(
+    spatial: &mut SpatialHash,
+    colliders: &[LdtkCollider],
+    user_data: impl Fn(&LdtkCollider) -> UserData,
+) {
+    for collider in colliders.iter() {
+        spatial.add_shape(collider.shape(), user_data(collider));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rects(
+        cells: &[&str],
+        merge_vertically: bool,
+    ) -> Vec<(i32, i32, i32, i32)> {
+        let width = cells[0].len() as i32;
+        let height = cells.len() as i32;
+
+        greedy_merge_cells(width, height, merge_vertically, |x, y| {
+            cells[y as usize].as_bytes()[x as usize] == b'#'
+        })
+        .into_iter()
+        .map(|r| (r.offset.x, r.offset.y, r.size.x, r.size.y))
+        .collect()
+    }
+
+    #[test]
+    fn greedy_merge() {
+        let cells = ["##..", "##..", "####"];
+
+        assert_eq!(rects(&cells, true), vec![(0, 0, 2, 3), (2, 2, 2, 1)]);
+        assert_eq!(rects(&cells, false), vec![
+            (0, 0, 2, 1),
+            (0, 1, 2, 1),
+            (0, 2, 4, 1)
+        ]);
+        assert_eq!(rects(&["...."], true), vec![]);
+    }
+
+    #[test]
+    fn colliders_in_world_units() {
+        let (_, level) = test_project();
+        let mut layer = level.layer_instances.unwrap()[0].clone();
+
+        // An 8 pixel IntGrid layer, 4x2 cells with solid bottom corners.
+        layer.grid_size = 8;
+        layer.c_wid = 4;
+        layer.c_hei = 2;
+        layer.px_total_offset_x = 8;
+        layer.px_total_offset_y = 0;
+        layer.int_grid_csv = vec![0, 0, 0, 0, 1, 0, 0, 1];
+
+        let config = IntGridCollisionConfig::solid(&[1]);
+
+        // In a 16 pixel project every cell is half a unit.
+        let colliders =
+            intgrid_colliders(&layer, &config, vec2(10.0, -4.0), 16.0);
+
+        let aabbs = colliders.iter().map(|x| x.aabb).collect_vec();
+
+        assert_eq!(aabbs, vec![
+            AABB::new(vec2(10.5, -4.0), vec2(11.0, -3.5)),
+            AABB::new(vec2(12.0, -4.0), vec2(12.5, -3.5)),
+        ]);
+
+        // With the layer's own grid size every cell is a unit.
+        let colliders = intgrid_colliders(&layer, &config, Vec2::ZERO, 8.0);
+
+        assert_eq!(
+            colliders[1].aabb,
+            AABB::new(vec2(4.0, 0.0), vec2(5.0, 1.0))
+        );
+        assert_eq!(colliders[1].cells.offset, ivec2(3, 1));
+    }
+}
@@ -0,0 +1,360 @@
+use crate::*;
+
+use serde_json::Value;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LdtkFieldError {
+    /// No field with the given identifier exists.
+    Missing(String),
+    /// The field exists but has no value set in the editor.
+    Null(String),
+    /// The field exists but its `__type` is different from what was asked
+    /// for, e.g. reading an `Int` field as a `String`.
+    TypeMismatch { field: String, expected: String, found: String },
+    /// The field has the right type but its value couldn't be parsed.
+    InvalidValue { field: String, value: String },
+}
+
+impl std::fmt::Display for LdtkFieldError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Missing(field) => write!(f, "field {field:?} doesn't exist"),
+            Self::Null(field) => write!(f, "field {field:?} has no value"),
+            Self::TypeMismatch { field, expected, found } => {
+                write!(
+                    f,
+                    "field {field:?} has type {found}, expected {expected}"
+                )
+            }
+            Self::InvalidValue { field, value } => {
+                write!(f, "field {field:?} has an invalid value {value}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for LdtkFieldError {}
+
+pub type LdtkFieldResult<T> = std::result::Result<T, LdtkFieldError>;
+
+/// Typed accessors for LDtk custom fields, implemented for both entity
+/// instances and levels.
+///
+/// Every accessor checks the field's `__type` first, so reading an `Int`
+/// field with `field_string` returns `LdtkFieldError::TypeMismatch` instead
+/// of silently returning nothing. Fields that are nullable in LDtk and were
+/// left empty return `LdtkFieldError::Null`, use `.ok()` if you want to treat
+/// them as optional.
+pub trait LdtkFields {
+    fn field_instances(&self) -> &[FieldInstance];
+
+    fn field_instance(&self, name: &str) -> LdtkFieldResult<&FieldInstance> {
+        self.field_instances()
+            .iter()
+            .find(|x| x.identifier == name)
+            .ok_or_else(|| LdtkFieldError::Missing(name.to_string()))
+    }
+
+    fn has_field(&self, name: &str) -> bool {
+        self.field_instance(name).is_ok()
+    }
+
+    fn field_int(&self, name: &str) -> LdtkFieldResult<i64> {
+        typed_field(self, name, "Int", parse_int)
+    }
+
+    fn field_float(&self, name: &str) -> LdtkFieldResult<f32> {
+        typed_field(self, name, "Float", parse_float)
+    }
+
+    fn field_bool(&self, name: &str) -> LdtkFieldResult<bool> {
+        typed_field(self, name, "Bool", Value::as_bool)
+    }
+
+    /// Reads a `String` field, including multiline strings.
+    fn field_string(&self, name: &str) -> LdtkFieldResult<&str> {
+        typed_field(self, name, "String", Value::as_str)
+    }
+
+    fn field_file_path(&self, name: &str) -> LdtkFieldResult<&str> {
+        typed_field(self, name, "FilePath", Value::as_str)
+    }
+
+    fn field_color(&self, name: &str) -> LdtkFieldResult<Color> {
+        typed_field(self, name, "Color", parse_color)
+    }
+
+    /// Returns the selected value of an `Enum` field.
+    fn field_enum(&self, name: &str) -> LdtkFieldResult<&str> {
+        typed_field(self, name, "Enum", Value::as_str)
+    }
+
+    fn field_point(&self, name: &str) -> LdtkFieldResult<GridPoint> {
+        typed_field(self, name, "Point", parse_point)
+    }
+
+    fn field_tile(&self, name: &str) -> LdtkFieldResult<TilesetRectangle> {
+        typed_field(self, name, "Tile", parse_tile)
+    }
+
+    fn field_entity_ref(
+        &self,
+        name: &str,
+    ) -> LdtkFieldResult<ReferenceToAnEntityInstance> {
+        typed_field(self, name, "EntityRef", parse_entity_ref)
+    }
+
+    fn field_int_array(&self, name: &str) -> LdtkFieldResult<Vec<i64>> {
+        typed_array_field(self, name, "Int", parse_int)
+    }
+
+    fn field_float_array(&self, name: &str) -> LdtkFieldResult<Vec<f32>> {
+        typed_array_field(self, name, "Float", parse_float)
+    }
+
+    fn field_bool_array(&self, name: &str) -> LdtkFieldResult<Vec<bool>> {
+        typed_array_field(self, name, "Bool", Value::as_bool)
+    }
+
+    fn field_string_array(&self, name: &str) -> LdtkFieldResult<Vec<&str>> {
+        typed_array_field(self, name, "String", Value::as_str)
+    }
+
+    fn field_file_path_array(&self, name: &str) -> LdtkFieldResult<Vec<&str>> {
+        typed_array_field(self, name, "FilePath", Value::as_str)
+    }
+
+    fn field_color_array(&self, name: &str) -> LdtkFieldResult<Vec<Color>> {
+        typed_array_field(self, name, "Color", parse_color)
+    }
+
+    fn field_enum_array(&self, name: &str) -> LdtkFieldResult<Vec<&str>> {
+        typed_array_field(self, name, "Enum", Value::as_str)
+    }
+
+    fn field_point_array(&self, name: &str) -> LdtkFieldResult<Vec<GridPoint>> {
+        typed_array_field(self, name, "Point", parse_point)
+    }
+
+    fn field_tile_array(
+        &self,
+        name: &str,
+    ) -> LdtkFieldResult<Vec<TilesetRectangle>> {
+        typed_array_field(self, name, "Tile", parse_tile)
+    }
+
+    fn field_entity_ref_array(
+        &self,
+        name: &str,
+    ) -> LdtkFieldResult<Vec<ReferenceToAnEntityInstance>> {
+        typed_array_field(self, name, "EntityRef", parse_entity_ref)
+    }
+}
+
+impl LdtkFields for EntityInstance {
+    fn field_instances(&self) -> &[FieldInstance] {
+        &self.field_instances
+    }
+}
+
+impl LdtkFields for Level {
+    fn field_instances(&self) -> &[FieldInstance] {
+        &self.field_instances
+    }
+}
+
+/// Checks whether an LDtk `__type` string (without the `Array<>` wrapper)
+/// matches the expected type. Enums are reported as `LocalEnum.Name` or
+/// `ExternEnum.Name` (`Enum(Name)` in older versions), and multiline strings
+/// as `Multilines`.
+fn type_matches(found: &str, expected: &str) -> bool {
+    match expected {
+        "Enum" => {
+            found.starts_with("LocalEnum.") ||
+                found.starts_with("ExternEnum.") ||
+                found.starts_with("Enum(")
+        }
+        "String" => found == "String" || found == "Multilines",
+        _ => found == expected,
+    }
+}
+
+fn checked_value<'a, F: LdtkFields + ?Sized>(
+    fields: &'a F,
+    name: &str,
+    expected: &'static str,
+    is_array: bool,
+) -> LdtkFieldResult<&'a Value> {
+    let field = fields.field_instance(name)?;
+    let found = field.field_instance_type.as_str();
+
+    let inner = match found.strip_prefix("Array<") {
+        Some(inner) if is_array => inner.strip_suffix('>'),
+        None if !is_array => Some(found),
+        _ => None,
+    };
+
+    if !inner.is_some_and(|inner| type_matches(inner, expected)) {
+        return Err(LdtkFieldError::TypeMismatch {
+            field: name.to_string(),
+            expected: if is_array {
+                format!("Array<{expected}>")
+            } else {
+                expected.to_string()
+            },
+            found: found.to_string(),
+        });
+    }
+
+    match field.value.as_ref() {
+        Some(Value::Null) | None => Err(LdtkFieldError::Null(name.to_string())),
+        Some(value) => Ok(value),
+    }
+}
+
+fn typed_field<'a, F: LdtkFields + ?Sized, T>(
+    fields: &'a F,
+    name: &str,
+    expected: &'static str,
+    parse: impl Fn(&'a Value) -> Option<T>,
+) -> LdtkFieldResult<T> {
+    let value = checked_value(fields, name, expected, false)?;
+
+    parse(value).ok_or_else(|| {
+        LdtkFieldError::InvalidValue {
+            field: name.to_string(),
+            value: value.to_string(),
+        }
+    })
+}
+
+fn typed_array_field<'a, F: LdtkFields + ?Sized, T>(
+    fields: &'a F,
+    name: &str,
+    expected: &'static str,
+    parse: impl Fn(&'a Value) -> Option<T>,
+) -> LdtkFieldResult<Vec<T>> {
+    let value = checked_value(fields, name, expected, true)?;
+
+    let array = value.as_array().ok_or_else(|| {
+        LdtkFieldError::InvalidValue {
+            field: name.to_string(),
+            value: value.to_string(),
+        }
+    })?;
+
+    array
+        .iter()
+        .enumerate()
+        .map(|(i, item)| {
+            if item.is_null() {
+                return Err(LdtkFieldError::Null(format!("{name}[{i}]")));
+            }
+
+            parse(item).ok_or_else(|| {
+                LdtkFieldError::InvalidValue {
+                    field: format!("{name}[{i}]"),
+                    value: item.to_string(),
+                }
+            })
+        })
+        .collect()
+}
+
+fn parse_int(value: &Value) -> Option<i64> {
+    value.as_i64()
+}
+
+fn parse_float(value: &Value) -> Option<f32> {
+    value.as_f64().map(|x| x as f32)
+}
+
+fn parse_color(value: &Value) -> Option<Color> {
+    parse_hex_color(value.as_str()?)
+}
+
+fn parse_point(value: &Value) -> Option<GridPoint> {
+    serde_json::from_value(value.clone()).ok()
+}
+
+fn parse_tile(value: &Value) -> Option<TilesetRectangle> {
+    serde_json::from_value(value.clone()).ok()
+}
+
+fn parse_entity_ref(value: &Value) -> Option<ReferenceToAnEntityInstance> {
+    serde_json::from_value(value.clone()).ok()
+}
+
+/// Parses colors in the `#rrggbb` format LDtk uses for color fields.
+pub fn parse_hex_color(hex: &str) -> Option<Color> {
+    let hex = hex.strip_prefix('#').unwrap_or(hex);
+
+    if hex.len() != 6 {
+        return None;
+    }
+
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+
+    Some(Color::rgb8(channel(0)?, channel(2)?, channel(4)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(name: &str, ty: &str, value: Value) -> FieldInstance {
+        FieldInstance {
+            identifier: name.to_string(),
+            tile: None,
+            field_instance_type: ty.to_string(),
+            value: Some(value),
+            def_uid: 0,
+            real_editor_values: vec![],
+        }
+    }
+
+    struct Fields(Vec<FieldInstance>);
+
+    impl LdtkFields for Fields {
+        fn field_instances(&self) -> &[FieldInstance] {
+            &self.0
+        }
+    }
+
+    #[test]
+    fn typed_field_access() {
+        let fields = Fields(vec![
+            field("hp", "Int", serde_json::json!(10)),
+            field("tint", "Color", serde_json::json!("#ff8000")),
+            field("kind", "LocalEnum.Kind", serde_json::json!("Slime")),
+            field("drops", "Array<Int>", serde_json::json!([1, 2, 3])),
+            field("target", "Point", Value::Null),
+        ]);
+
+        assert_eq!(fields.field_int("hp"), Ok(10));
+        assert_eq!(fields.field_enum("kind"), Ok("Slime"));
+        assert_eq!(fields.field_int_array("drops"), Ok(vec![1, 2, 3]));
+        assert_eq!(fields.field_color("tint"), Ok(Color::rgb8(255, 128, 0)));
+
+        assert_eq!(
+            fields.field_string("hp"),
+            Err(LdtkFieldError::TypeMismatch {
+                field: "hp".to_string(),
+                expected: "String".to_string(),
+                found: "Int".to_string(),
+            })
+        );
+        assert!(matches!(
+            fields.field_int("drops"),
+            Err(LdtkFieldError::TypeMismatch { .. })
+        ));
+        assert_eq!(
+            fields.field_point("target").map(|_| ()),
+            Err(LdtkFieldError::Null("target".to_string()))
+        );
+        assert_eq!(
+            fields.field_float("speed"),
+            Err(LdtkFieldError::Missing("speed".to_string()))
+        );
+    }
+}
@@ -1,11 +1,20 @@
+mod collision;
+mod fields;
 mod quicktype;
+mod spawning;
+mod streaming;
 
 use comfy_core::*;
 use grids::Grid;
 use notify::{Config, RecommendedWatcher, Watcher};
+use std::path::PathBuf;
 
+pub use collision::*;
+pub use fields::*;
 pub use quicktype::*;
 pub use serde_json;
+pub use spawning::*;
+pub use streaming::*;
 
 pub fn parse_ldtk_map(
     map: &str,
@@ -13,6 +22,19 @@ pub fn parse_ldtk_map(
     serde_json::from_str(map)
 }
 
+pub fn parse_ldtk_level(level: &str) -> Result<Level, serde_json::Error> {
+    serde_json::from_str(level)
+}
+
+/// What got reloaded by `LdtkWorldMap::maybe_reload`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LdtkReloadEvent {
+    /// The main project file was reloaded.
+    Project,
+    /// A level saved in a separate `.ldtkl` file was reloaded.
+    Level(String),
+}
+
 pub struct LdtkWorldMap {
     #[cfg(not(feature = "ci-release"))]
     pub watcher: RecommendedWatcher,
@@ -20,10 +42,30 @@ pub struct LdtkWorldMap {
     #[cfg(not(feature = "ci-release"))]
     pub recv: std::sync::mpsc::Receiver<Result<notify::Event, notify::Error>>,
     pub path: String,
+    /// `iid`s of levels saved in separate files that are currently loaded.
+    loaded_external_levels: HashSet<String>,
+    /// Level files that couldn't be loaded, they're only tried again once
+    /// they change.
+    failed_level_files: HashSet<PathBuf>,
 }
 
 impl LdtkWorldMap {
+    /// Creates the map and loads all levels saved in separate `.ldtkl` files
+    /// (the "Save levels to separate files" project option) right away.
     pub fn new(json: LdtkJson, path: &str) -> Self {
+        let mut map = Self::new_lazy(json, path);
+
+        if let Err(err) = map.load_all_levels() {
+            error!("Failed to load LDtk levels: {err:?}");
+        }
+
+        map
+    }
+
+    /// Same as `new`, but levels saved in separate files are only loaded
+    /// when requested through `load_level`. Until then their
+    /// `layer_instances` are `None`.
+    pub fn new_lazy(json: LdtkJson, path: &str) -> Self {
         #[cfg(not(feature = "ci-release"))]
         let (send, recv) = std::sync::mpsc::channel();
 
@@ -42,33 +84,249 @@ impl LdtkWorldMap {
             #[cfg(not(feature = "ci-release"))]
             recv,
             path: path.to_string(),
+            loaded_external_levels: HashSet::new(),
+            failed_level_files: HashSet::new(),
+        }
+    }
+
+    pub fn level(&self, iid: &str) -> Option<&Level> {
+        find_ldtk_level(&self.json, iid)
+    }
+
+    /// Path of the `.ldtkl` file of a level, or `None` if the level is
+    /// embedded in the project file.
+    pub fn level_file_path(&self, level: &Level) -> Option<PathBuf> {
+        let rel_path = level.external_rel_path.as_ref()?;
+        let dir = Path::new(&self.path).parent().unwrap_or(Path::new(""));
+
+        Some(dir.join(rel_path))
+    }
+
+    /// Returns `true` if the level's layers are available, which is always
+    /// the case for levels embedded in the project file.
+    pub fn is_level_loaded(&self, iid: &str) -> bool {
+        self.level(iid).is_some_and(|level| level.layer_instances.is_some())
+    }
+
+    /// Returns `true` if the level's file failed to load and didn't change
+    /// since. `LdtkWorldStreamer::update_map` doesn't try to load such levels
+    /// again until their file changes.
+    pub fn level_failed_to_load(&self, iid: &str) -> bool {
+        self.level(iid)
+            .and_then(|level| self.level_file_path(level))
+            .is_some_and(|path| self.failed_level_files.contains(&path))
+    }
+
+    /// Loads a level saved in a separate file, and starts watching the file
+    /// for changes. Does nothing if the level is already loaded.
+    pub fn load_level(&mut self, iid: &str) -> Result<()> {
+        let Some(level) = self.level(iid) else {
+            bail!("LDtk level {iid} doesn't exist");
+        };
+
+        if level.layer_instances.is_some() {
+            return Ok(());
+        }
+
+        let Some(path) = self.level_file_path(level) else {
+            bail!("LDtk level {iid} has no layers and no external file");
+        };
+
+        // Failed files are watched too, so that they're retried once fixed.
+        #[cfg(not(feature = "ci-release"))]
+        if let Err(err) =
+            self.watcher.watch(&path, notify::RecursiveMode::NonRecursive)
+        {
+            error!("Failed to watch {path:?}: {err:?}");
+        }
+
+        let level = match std::fs::read_to_string(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|x| Ok(parse_ldtk_level(&x)?))
+        {
+            Ok(level) => level,
+            Err(err) => {
+                self.failed_level_files.insert(path);
+                return Err(err);
+            }
+        };
+
+        self.failed_level_files.remove(&path);
+        self.insert_level(level);
+        self.loaded_external_levels.insert(iid.to_string());
+
+        Ok(())
+    }
+
+    /// Loads all levels saved in separate files. Levels that fail to load
+    /// don't stop the others from loading, their errors are returned
+    /// together.
+    pub fn load_all_levels(&mut self) -> Result<()> {
+        let iids = all_levels(&self.json)
+            .filter(|level| level.layer_instances.is_none())
+            .map(|level| level.iid.clone())
+            .collect_vec();
+
+        let errors = iids
+            .into_iter()
+            .filter_map(|iid| {
+                self.load_level(&iid).err().map(|err| format!("{iid}: {err:?}"))
+            })
+            .collect_vec();
+
+        if !errors.is_empty() {
+            bail!(
+                "{} LDtk levels failed to load:\n{}",
+                errors.len(),
+                errors.join("\n")
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Drops the layers of a level saved in a separate file to free memory.
+    /// Embedded levels are left untouched.
+    pub fn unload_level(&mut self, iid: &str) {
+        if !self.loaded_external_levels.remove(iid) {
+            return;
+        }
+
+        #[cfg(not(feature = "ci-release"))]
+        if let Some(path) =
+            self.level(iid).and_then(|x| self.level_file_path(x))
+        {
+            self.watcher.unwatch(&path).ok();
+        }
+
+        if let Some(level) =
+            all_levels_mut(&mut self.json).find(|x| x.iid == iid)
+        {
+            level.layer_instances = None;
+        }
+    }
+
+    /// Replaces the level with the same `iid`. This is useful on platforms
+    /// without a filesystem, where the `.ldtkl` files can be embedded with
+    /// `include_str!` and parsed with `parse_ldtk_level`.
+    pub fn insert_level(&mut self, level: Level) {
+        if let Some(existing) =
+            all_levels_mut(&mut self.json).find(|x| x.iid == level.iid)
+        {
+            *existing = level;
         }
     }
 
     #[cfg(feature = "ci-release")]
-    pub fn maybe_reload(&mut self) {}
+    pub fn maybe_reload(&mut self) -> Vec<LdtkReloadEvent> {
+        vec![]
+    }
 
+    /// Reloads the project or individual level files that changed on disk.
+    /// Levels saved in separate files that were loaded before a project
+    /// reload are loaded again, and level files that failed to load are
+    /// tried again by the next `load_level`. Changes of other files, e.g.
+    /// levels unloaded after the change, are ignored.
     #[cfg(not(feature = "ci-release"))]
-    pub fn maybe_reload(&mut self) {
-        let mut reload_level = false;
-
-        while let Ok(_event) = self.recv.try_recv() {
-            reload_level = true;
+    pub fn maybe_reload(&mut self) -> Vec<LdtkReloadEvent> {
+        let mut reload_project = false;
+        let mut reload_levels = HashSet::new();
+
+        while let Ok(event) = self.recv.try_recv() {
+            for changed in event.iter().flat_map(|event| event.paths.iter()) {
+                let level = self.loaded_external_levels.iter().find(|iid| {
+                    self.level(iid)
+                        .and_then(|level| self.level_file_path(level))
+                        .is_some_and(|path| is_same_file(changed, &path))
+                });
+
+                let failed = self
+                    .failed_level_files
+                    .iter()
+                    .find(|path| is_same_file(changed, path))
+                    .cloned();
+
+                if let Some(iid) = level {
+                    reload_levels.insert(iid.clone());
+                } else if let Some(path) = failed {
+                    info!("LDtk level {path:?} changed, loading it again");
+                    self.failed_level_files.remove(&path);
+                } else if is_same_file(changed, Path::new(&self.path)) {
+                    reload_project = true;
+                }
+            }
         }
 
-        if reload_level {
-            match parse_ldtk_map(&std::fs::read_to_string(&self.path).unwrap())
+        let mut events = vec![];
+
+        if reload_project {
+            // The file can be missing for a moment while LDtk saves it, the
+            // next event reloads it again.
+            match std::fs::read_to_string(&self.path)
+                .map_err(anyhow::Error::from)
+                .and_then(|x| Ok(parse_ldtk_map(&x)?))
             {
                 // match deathmind_ldtk::serde_json::from_str(&std::fs::read_to_string(LDTK_PATH).unwrap()) {
                 Ok(json) => {
-                    println!("Reloaded map");
+                    info!("Reloaded LDtk map {}", self.path);
                     self.json = json;
+
+                    for iid in std::mem::take(&mut self.loaded_external_levels)
+                    {
+                        if let Err(err) = self.load_level(&iid) {
+                            error!("Error loading LDtk level {iid}: {err:?}");
+                        }
+                    }
+
+                    events.push(LdtkReloadEvent::Project);
                 }
                 Err(err) => {
-                    println!("Error parsing map {err:?}");
+                    error!("Error parsing LDtk map {}: {err:?}", self.path);
+                }
+            }
+        } else {
+            for iid in reload_levels {
+                let Some(path) =
+                    self.level(&iid).and_then(|x| self.level_file_path(x))
+                else {
+                    continue;
+                };
+
+                match std::fs::read_to_string(&path)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|x| Ok(parse_ldtk_level(&x)?))
+                {
+                    Ok(level) => {
+                        info!("Reloaded LDtk level {path:?}");
+                        self.insert_level(level);
+                        events.push(LdtkReloadEvent::Level(iid));
+                    }
+                    Err(err) => {
+                        error!("Error parsing LDtk level {path:?}: {err:?}");
+                    }
                 }
             }
         }
+
+        events
+    }
+}
+
+fn all_levels(json: &LdtkJson) -> impl Iterator<Item = &Level> {
+    json.levels.iter().chain(json.worlds.iter().flat_map(|x| x.levels.iter()))
+}
+
+fn all_levels_mut(json: &mut LdtkJson) -> impl Iterator<Item = &mut Level> {
+    json.levels
+        .iter_mut()
+        .chain(json.worlds.iter_mut().flat_map(|x| x.levels.iter_mut()))
+}
+
+#[cfg(not(feature = "ci-release"))]
+fn is_same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a.ends_with(b),
     }
 }
 
@@ -130,6 +388,7 @@ pub trait LdtkEntityExtensions {
     fn world_pos(&self, layer_c_hei: i64, layer_grid_size: i64) -> Vec2;
     fn world_size(&self, layer_grid_size: i64) -> Vec2;
 
+    // Untyped shorthands for `LdtkFields`, returning `None` on any error.
     fn bool_field(&self, name: &str) -> Option<bool>;
     fn str_field(&self, name: &str) -> Option<&str>;
     fn str_array_field(&self, name: &str) -> Option<Vec<String>>;
@@ -154,38 +413,26 @@ impl LdtkEntityExtensions for EntityInstance {
     }
 
     fn bool_field(&self, name: &str) -> Option<bool> {
-        self.field_instances
-            .iter()
-            .find(|x| x.identifier == name)
-            .and_then(|x| x.value.as_ref())
-            .and_then(|x| x.as_bool())
+        self.field_bool(name).ok()
     }
 
+    /// Any field stored as a string, e.g. `String`, `Enum` or `FilePath`.
     fn str_field(&self, name: &str) -> Option<&str> {
-        self.field_instances
-            .iter()
-            .find(|x| x.identifier == name)
-            .and_then(|x| x.value.as_ref())
-            .and_then(|x| x.as_str())
+        self.field_instance(name).ok()?.value.as_ref()?.as_str()
     }
 
+    /// Any array field stored as strings, skipping empty elements.
     fn str_array_field(&self, name: &str) -> Option<Vec<String>> {
-        let field =
-            self.field_instances.iter().find(|x| x.identifier == name)?;
-        let array = field.value.as_ref()?.as_array()?;
+        let array =
+            self.field_instance(name).ok()?.value.as_ref()?.as_array()?;
         let strings = array.iter().filter_map(|x| x.as_str());
         Some(strings.map(|x| x.to_string()).collect_vec())
     }
 
+    /// IIDs of the referenced entities, `None` if any reference is empty.
     fn entity_array_field(&self, name: &str) -> Option<Vec<String>> {
-        let field =
-            self.field_instances.iter().find(|x| x.identifier == name)?;
-        let array = field.value.as_ref()?.as_array()?;
-        let strings = array
-            .iter()
-            .filter_map(|x| x.get("entityIid"))
-            .filter_map(|x| x.as_str());
-        Some(strings.map(|x| x.to_string()).collect_vec())
+        let refs = self.field_entity_ref_array(name).ok()?;
+        Some(refs.into_iter().map(|x| x.entity_iid).collect_vec())
     }
 }
 
@@ -197,3 +444,65 @@ pub fn grid_from_csv(layer: &LayerInstance) -> Grid<i32> {
         layer.int_grid_csv[(x + y * width) as usize] as i32
     })
 }
+
+/// The example project without its first level, and that level, which tests
+/// use as a template for their own levels.
+#[cfg(test)]
+fn test_project() -> (LdtkJson, Level) {
+    let mut json =
+        parse_ldtk_map(include_str!("../../assets/comfy_ldtk.ldtk")).unwrap();
+
+    let level = json.levels.remove(0);
+
+    (json, level)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failed_level_files() {
+        let dir = std::env::temp_dir()
+            .join(format!("comfy-ldtk-levels-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let (mut json, template) = test_project();
+
+        let level_file = |iid: &str| {
+            let mut level = template.clone();
+            level.iid = iid.to_string();
+            serde_json::to_string(&level).unwrap()
+        };
+
+        for iid in ["broken", "good", "missing"] {
+            let mut level = template.clone();
+            level.iid = iid.to_string();
+            level.layer_instances = None;
+            level.external_rel_path = Some(format!("{iid}.ldtkl"));
+            json.levels.push(level);
+        }
+
+        std::fs::write(dir.join("good.ldtkl"), level_file("good")).unwrap();
+        std::fs::write(dir.join("broken.ldtkl"), "{").unwrap();
+
+        let path = dir.join("world.ldtk");
+        std::fs::write(&path, "{}").unwrap();
+
+        let mut map = LdtkWorldMap::new_lazy(json, path.to_str().unwrap());
+        let err = map.load_all_levels().unwrap_err().to_string();
+
+        // Errors don't stop the other levels from loading.
+        assert!(err.starts_with("2 LDtk levels failed to load"));
+        assert!(map.is_level_loaded("good"));
+        assert!(!map.level_failed_to_load("good"));
+        assert!(map.level_failed_to_load("broken"));
+        assert!(map.level_failed_to_load("missing"));
+
+        std::fs::write(dir.join("broken.ldtkl"), level_file("broken")).unwrap();
+        map.load_level("broken").unwrap();
+        assert!(!map.level_failed_to_load("broken"));
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
@@ -0,0 +1,316 @@
+use crate::*;
+
+use hecs::EntityBuilder;
+
+/// Component attached to every entity spawned through `LdtkEntityRegistry`,
+/// linking it back to the LDtk entity instance and level it came from.
+#[derive(Clone, Debug)]
+pub struct LdtkEntity {
+    pub iid: String,
+    pub identifier: String,
+    pub level_iid: String,
+}
+
+/// Everything a spawner gets to know about the LDtk entity instance it is
+/// spawning, together with typed accessors for its custom fields.
+pub struct LdtkSpawnContext<'a> {
+    pub level: &'a Level,
+    pub layer: &'a LayerInstance,
+    pub entity: &'a EntityInstance,
+    /// World position of the level's bottom left corner, added to all
+    /// positions returned by the context.
+    pub offset: Vec2,
+    /// How many LDtk pixels make up one world unit. Without it positions
+    /// are in cells of the entity's layer.
+    pub pixels_per_unit: Option<f32>,
+}
+
+impl<'a> LdtkSpawnContext<'a> {
+    /// Center of the entity in world coordinates.
+    pub fn world_pos(&self) -> Vec2 {
+        self.entity.world_pos(self.layer.c_hei, self.layer.grid_size) *
+            self.cell_size() +
+            self.layer_offset()
+    }
+
+    /// Size of the entity in world units.
+    pub fn world_size(&self) -> Vec2 {
+        self.entity.world_size(self.layer.grid_size) * self.cell_size()
+    }
+
+    /// Returns a `Point` field converted to world coordinates, pointing at
+    /// the center of the referenced grid cell.
+    pub fn world_point(&self, name: &str) -> LdtkFieldResult<Vec2> {
+        let point = self.field_point(name)?;
+
+        Ok((self.layer.grid_to_world(point.cx as i32, point.cy as i32) +
+            splat(0.5)) *
+            self.cell_size() +
+            self.layer_offset())
+    }
+
+    /// Returns the `iid` of the entity referenced by an `EntityRef` field.
+    /// Use `LdtkEntityRegistry::entity_for_iid` to look up the spawned
+    /// entity once the whole level is spawned.
+    pub fn entity_ref_iid(&self, name: &str) -> LdtkFieldResult<String> {
+        Ok(self.field_entity_ref(name)?.entity_iid)
+    }
+
+    /// Size of one cell of the entity's layer in world units.
+    fn cell_size(&self) -> f32 {
+        self.pixels_per_unit
+            .map_or(1.0, |ppu| self.layer.grid_size as f32 / ppu)
+    }
+
+    fn layer_offset(&self) -> Vec2 {
+        let ppu = self.pixels_per_unit.unwrap_or(self.layer.grid_size as f32);
+
+        self.offset +
+            vec2(
+                self.layer.px_total_offset_x as f32,
+                -self.layer.px_total_offset_y as f32,
+            ) / ppu
+    }
+}
+
+impl<'a> LdtkFields for LdtkSpawnContext<'a> {
+    fn field_instances(&self) -> &[FieldInstance] {
+        &self.entity.field_instances
+    }
+}
+
+pub type LdtkSpawnFn = Box<dyn Fn(&LdtkSpawnContext) -> Option<EntityBuilder>>;
+
+/// Maps LDtk entity identifiers to spawners that turn entity instances into
+/// hecs entities.
+///
+/// Spawned entities are tracked per level, so that they can be despawned
+/// together when the level is unloaded. Every spawned entity also gets an
+/// `LdtkEntity` component.
+///
+/// ```ignore
+/// let mut registry = LdtkEntityRegistry::new();
+///
+/// registry.register("Enemy", |ctx| {
+///     let mut builder = EntityBuilder::new();
+///     builder.add(Transform::position(ctx.world_pos()));
+///     builder.add(Health(ctx.field_int("Health").unwrap_or(10)));
+///     Some(builder)
+/// });
+///
+/// registry.spawn_level_entities(&mut world_mut(), level);
+/// ```
+#[derive(Default)]
+pub struct LdtkEntityRegistry {
+    spawners: HashMap<String, LdtkSpawnFn>,
+    spawned_levels: HashMap<String, Vec<Entity>>,
+    entities_by_iid: HashMap<String, Entity>,
+}
+
+impl LdtkEntityRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a spawner for the given LDtk entity identifier, replacing
+    /// any previously registered one. Returning `None` from the spawner
+    /// skips the entity instance.
+    pub fn register(
+        &mut self,
+        identifier: &str,
+        spawner: impl Fn(&LdtkSpawnContext) -> Option<EntityBuilder> + 'static,
+    ) -> &mut Self {
+        self.spawners.insert(identifier.to_string(), Box::new(spawner));
+        self
+    }
+
+    pub fn is_registered(&self, identifier: &str) -> bool {
+        self.spawners.contains_key(identifier)
+    }
+
+    /// Spawns all entity instances of the level that have a registered
+    /// spawner. Entity instances without a spawner are ignored.
+    ///
+    /// If the level was already spawned its previous entities are despawned
+    /// first, which makes this safe to call again after a hot reload.
+    pub fn spawn_level_entities(
+        &mut self,
+        world: &mut hecs::World,
+        level: &Level,
+    ) -> &[Entity] {
+        self.spawn(world, level, Vec2::ZERO, None)
+    }
+
+    /// Same as `spawn_level_entities`, but offsets all positions by the
+    /// given world position of the level and measures them in units of
+    /// `pixels_per_unit` LDtk pixels instead of layer cells. This is what
+    /// `LdtkWorldStreamer` uses to place levels next to each other, which
+    /// keeps entities in place when their layer's grid size differs from
+    /// other layers.
+    pub fn spawn_level_entities_at(
+        &mut self,
+        world: &mut hecs::World,
+        level: &Level,
+        offset: Vec2,
+        pixels_per_unit: f32,
+    ) -> &[Entity] {
+        self.spawn(world, level, offset, Some(pixels_per_unit))
+    }
+
+    fn spawn(
+        &mut self,
+        world: &mut hecs::World,
+        level: &Level,
+        offset: Vec2,
+        pixels_per_unit: Option<f32>,
+    ) -> &[Entity] {
+        self.despawn_level_entities(world, &level.iid);
+
+        let mut spawned = Vec::new();
+
+        for layer in level.layer_instances.iter().flatten() {
+            for entity in layer.entity_instances.iter() {
+                let Some(spawner) = self.spawners.get(&entity.identifier)
+                else {
+                    continue;
+                };
+
+                let ctx = LdtkSpawnContext {
+                    level,
+                    layer,
+                    entity,
+                    offset,
+                    pixels_per_unit,
+                };
+
+                if let Some(mut builder) = spawner(&ctx) {
+                    builder.add(LdtkEntity {
+                        iid: entity.iid.clone(),
+                        identifier: entity.identifier.clone(),
+                        level_iid: level.iid.clone(),
+                    });
+
+                    let id = world.spawn(builder.build());
+                    self.entities_by_iid.insert(entity.iid.clone(), id);
+                    spawned.push(id);
+                }
+            }
+        }
+
+        self.spawned_levels.entry(level.iid.clone()).or_insert(spawned)
+    }
+
+    /// Despawns all entities previously spawned for the given level. Entities
+    /// that were already despawned by the game are skipped.
+    pub fn despawn_level_entities(
+        &mut self,
+        world: &mut hecs::World,
+        level_iid: &str,
+    ) {
+        let Some(entities) = self.spawned_levels.remove(level_iid) else {
+            return;
+        };
+
+        for entity in entities {
+            world.despawn(entity).ok();
+        }
+
+        self.entities_by_iid.retain(|_, entity| world.contains(*entity));
+    }
+
+    /// Despawns entities of all levels spawned by this registry.
+    pub fn despawn_all(&mut self, world: &mut hecs::World) {
+        for (_, entities) in self.spawned_levels.drain() {
+            for entity in entities {
+                world.despawn(entity).ok();
+            }
+        }
+
+        self.entities_by_iid.clear();
+    }
+
+    pub fn is_level_spawned(&self, level_iid: &str) -> bool {
+        self.spawned_levels.contains_key(level_iid)
+    }
+
+    pub fn spawned_levels(&self) -> impl Iterator<Item = &str> {
+        self.spawned_levels.keys().map(|x| x.as_str())
+    }
+
+    pub fn level_entities(&self, level_iid: &str) -> &[Entity] {
+        self.spawned_levels
+            .get(level_iid)
+            .map(|x| x.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Looks up the hecs entity spawned for an LDtk entity instance `iid`,
+    /// e.g. one returned by `LdtkSpawnContext::entity_ref_iid`.
+    pub fn entity_for_iid(&self, iid: &str) -> Option<Entity> {
+        self.entities_by_iid.get(iid).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Name(String);
+
+    fn level() -> Level {
+        test_project().1
+    }
+
+    fn registry() -> LdtkEntityRegistry {
+        let mut registry = LdtkEntityRegistry::new();
+
+        registry.register("Character", |ctx| {
+            let mut builder = EntityBuilder::new();
+            builder.add(Name(ctx.field_string("Name").ok()?.to_string()));
+            Some(builder)
+        });
+
+        registry
+    }
+
+    #[test]
+    fn spawn_and_despawn_level_entities() {
+        let mut world = hecs::World::new();
+        let mut registry = registry();
+        let level = level();
+
+        let spawned = registry.spawn_level_entities(&mut world, &level);
+        assert_eq!(spawned.len(), 1);
+
+        let entity = spawned[0];
+        assert_eq!(world.get::<&Name>(entity).unwrap().0, "Comfy");
+        assert_eq!(
+            world.get::<&LdtkEntity>(entity).unwrap().level_iid,
+            level.iid
+        );
+
+        let iid = &world.get::<&LdtkEntity>(entity).unwrap().iid.clone();
+        assert_eq!(registry.entity_for_iid(iid), Some(entity));
+        assert!(registry.is_level_spawned(&level.iid));
+
+        // Spawning the level again replaces its entities.
+        registry.spawn_level_entities(&mut world, &level);
+        assert_eq!(world.len(), 1);
+        assert!(!world.contains(entity));
+
+        registry.despawn_level_entities(&mut world, &level.iid);
+        assert_eq!(world.len(), 0);
+        assert!(!registry.is_level_spawned(&level.iid));
+        assert_eq!(registry.entity_for_iid(iid), None);
+    }
+
+    #[test]
+    fn unregistered_entities_are_ignored() {
+        let mut world = hecs::World::new();
+        let mut registry = LdtkEntityRegistry::new();
+
+        assert!(!registry.is_registered("Character"));
+        assert!(registry.spawn_level_entities(&mut world, &level()).is_empty());
+        assert_eq!(world.len(), 0);
+    }
+}
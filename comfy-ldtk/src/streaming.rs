@@ -0,0 +1,635 @@
+use crate::*;
+
+use std::collections::BTreeMap;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LdtkLevelEvent {
+    /// The level's entities were spawned and its tiles are being drawn.
+    Loaded(String),
+    /// The level's entities were despawned and its tiles are no longer drawn.
+    Unloaded(String),
+    /// The camera center moved into the level.
+    Entered(String),
+    /// The camera center moved out of the level.
+    Left(String),
+}
+
+/// Position of a level in the world, in world units.
+#[derive(Clone, Debug)]
+pub struct LdtkLevelBounds {
+    pub iid: String,
+    pub identifier: String,
+    /// Position of the level's top left corner in LDtk world pixels.
+    pub world_px: IVec2,
+    pub rect: AABB,
+    pub world_depth: i64,
+    pub neighbours: Vec<String>,
+}
+
+/// Computes world rectangles of all levels in the project, including levels
+/// of all worlds when the Multi-worlds option is enabled.
+///
+/// `GridVania` and `Free` layouts use the level's `worldX/worldY`, linear
+/// layouts place levels next to each other in the order they're defined in.
+/// One world unit is `pixels_per_unit` pixels, and the Y axis is flipped so
+/// that it points up like the rest of comfy.
+pub fn ldtk_level_bounds(
+    json: &LdtkJson,
+    pixels_per_unit: f32,
+) -> Vec<LdtkLevelBounds> {
+    let mut result = Vec::new();
+
+    let mut add_levels = |layout: Option<&WorldLayout>, levels: &[Level]| {
+        let mut linear_offset = 0;
+
+        for level in levels.iter() {
+            let (x, y) = match layout {
+                Some(WorldLayout::LinearHorizontal) => (linear_offset, 0),
+                Some(WorldLayout::LinearVertical) => (0, linear_offset),
+                _ => (level.world_x, level.world_y),
+            };
+
+            linear_offset += match layout {
+                Some(WorldLayout::LinearVertical) => level.px_hei,
+                _ => level.px_wid,
+            };
+
+            let min = vec2(x as f32, -(y + level.px_hei) as f32);
+            let size = vec2(level.px_wid as f32, level.px_hei as f32);
+
+            result.push(LdtkLevelBounds {
+                iid: level.iid.clone(),
+                identifier: level.identifier.clone(),
+                world_px: ivec2(x as i32, y as i32),
+                rect: AABB::new(
+                    min / pixels_per_unit,
+                    (min + size) / pixels_per_unit,
+                ),
+                world_depth: level.world_depth,
+                neighbours: level
+                    .neighbours
+                    .iter()
+                    .map(|x| x.level_iid.clone())
+                    .collect(),
+            });
+        }
+    };
+
+    if json.worlds.is_empty() {
+        add_levels(json.world_layout.as_ref(), &json.levels);
+    } else {
+        for world in json.worlds.iter() {
+            add_levels(world.world_layout.as_ref(), &world.levels);
+        }
+    }
+
+    result
+}
+
+/// Finds a level by its `iid`, searching all worlds.
+pub fn find_ldtk_level<'a>(json: &'a LdtkJson, iid: &str) -> Option<&'a Level> {
+    all_levels(json).find(|level| level.iid == iid)
+}
+
+/// Builds the tilemaps of a tile/auto layer, placed relative to `level_px`,
+/// which is the level position in LDtk world pixels.
+///
+/// Tiles are snapped to the layer's grid. Cells with several tiles stacked
+/// on top of each other, e.g. from overlapping auto layer rules, get one
+/// tilemap per stacked tile, as do tiles with a different opacity. Returns
+/// no tilemaps for layers without tiles.
+pub fn ldtk_layer_tilemaps(
+    layer: &LayerInstance,
+    tileset: &TilesetDefinition,
+    texture: TextureHandle,
+    level_px: IVec2,
+    pixels_per_unit: f32,
+    z_index: i32,
+) -> Vec<Tilemap> {
+    let grid_size = layer.grid_size.max(1);
+    let (width, height) = (layer.c_wid as u32, layer.c_hei as u32);
+
+    let bottom_left = level_px +
+        ivec2(
+            layer.px_total_offset_x as i32,
+            (layer.px_total_offset_y + layer.c_hei * grid_size) as i32,
+        );
+
+    let position =
+        vec2(bottom_left.x as f32, -bottom_left.y as f32) / pixels_per_unit;
+
+    // Tilemaps are keyed by how many tiles are below them in the same cell
+    // and by their opacity, which keeps the tile order within each cell.
+    let mut tilemaps = BTreeMap::<(u32, u8), Tilemap>::new();
+    let mut stacked = HashMap::<(i64, i64), u32>::default();
+
+    for tile in layer.grid_tiles.iter().chain(layer.auto_layer_tiles.iter()) {
+        let (cx, cy) = (tile.px[0] / grid_size, tile.px[1] / grid_size);
+
+        if cx < 0 || cy < 0 || cx >= layer.c_wid || cy >= layer.c_hei {
+            continue;
+        }
+
+        let depth = stacked.entry((cx, cy)).or_default();
+        let alpha = ((tile.a * layer.opacity) * 255.0).round() as u8;
+
+        let tilemap = tilemaps.entry((*depth, alpha)).or_insert_with(|| {
+            let mut tilemap = Tilemap::new(
+                texture,
+                UVec2::splat(tileset.tile_grid_size as u32),
+                width,
+                height,
+            );
+
+            tilemap.set_tileset_spacing(
+                tileset.padding as u32,
+                tileset.spacing as u32,
+            );
+            tilemap.set_position(position);
+            tilemap.set_tile_size(splat(grid_size as f32 / pixels_per_unit));
+            tilemap.set_color(WHITE.alpha(alpha as f32 / 255.0));
+            tilemap.set_z_index(z_index);
+            tilemap
+        });
+
+        *depth += 1;
+
+        tilemap.set_tile(
+            cx as u32,
+            height - 1 - cy as u32,
+            Some(Tile {
+                index: tile.t as u32,
+                flip_x: tile.f & 1 != 0,
+                flip_y: tile.f & 2 != 0,
+            }),
+        );
+    }
+
+    tilemaps.into_values().collect()
+}
+
+/// Keeps only the levels around the camera loaded.
+///
+/// Each frame `update` figures out which level the camera is in, loads the
+/// levels that get within `load_distance` of the camera view and unloads
+/// those that get further than `unload_distance`. When the current level
+/// knows its neighbours (GridVania/Free layouts) only the neighbours are
+/// considered for loading.
+///
+/// Loading a level spawns its entities through `registry` and builds
+/// tilemaps for each of its tile layers, which are then drawn by `draw`.
+/// Tile layers are only built for tilesets that have a texture assigned
+/// with `set_tileset_texture`.
+///
+/// Positions given to the spawners are offset by the level position and
+/// measured in units of `pixels_per_unit`, like the level bounds.
+pub struct LdtkWorldStreamer {
+    pub registry: LdtkEntityRegistry,
+    /// How many LDtk pixels make up one world unit. Call `reload` after
+    /// changing it to recompute the level positions.
+    pub pixels_per_unit: f32,
+    /// Levels closer than this to the camera view get loaded.
+    pub load_distance: f32,
+    /// Loaded levels further than this from the camera view get unloaded.
+    /// This should be larger than `load_distance` so that levels don't get
+    /// reloaded over and over when the camera moves along the edge.
+    pub unload_distance: f32,
+    /// Z index of the bottom-most tile layer, each layer above it is drawn
+    /// with the z index incremented by one.
+    pub z_index: i32,
+
+    levels: Vec<LdtkLevelBounds>,
+    loaded: HashMap<String, Vec<Tilemap>>,
+    current_level: Option<String>,
+    tileset_textures: HashMap<String, TextureHandle>,
+}
+
+impl LdtkWorldStreamer {
+    pub fn new(json: &LdtkJson, registry: LdtkEntityRegistry) -> Self {
+        let pixels_per_unit = json.default_grid_size as f32;
+
+        Self {
+            registry,
+            pixels_per_unit,
+            load_distance: 5.0,
+            unload_distance: 10.0,
+            z_index: 0,
+
+            levels: ldtk_level_bounds(json, pixels_per_unit),
+            loaded: HashMap::default(),
+            current_level: None,
+            tileset_textures: HashMap::default(),
+        }
+    }
+
+    /// Assigns a texture to the tileset with the given identifier.
+    pub fn set_tileset_texture(
+        &mut self,
+        tileset_identifier: &str,
+        texture: TextureHandle,
+    ) {
+        self.tileset_textures.insert(tileset_identifier.to_string(), texture);
+    }
+
+    pub fn levels(&self) -> &[LdtkLevelBounds] {
+        &self.levels
+    }
+
+    pub fn level_bounds(&self, iid: &str) -> Option<&LdtkLevelBounds> {
+        self.levels.iter().find(|x| x.iid == iid)
+    }
+
+    /// Returns the level at the given world position. When levels overlap
+    /// the one with the greatest world depth wins.
+    pub fn level_at(&self, position: Vec2) -> Option<&LdtkLevelBounds> {
+        self.levels
+            .iter()
+            .filter(|x| x.rect.contains(position))
+            .max_by_key(|x| x.world_depth)
+    }
+
+    /// `iid` of the level the camera is currently in.
+    pub fn current_level(&self) -> Option<&str> {
+        self.current_level.as_deref()
+    }
+
+    pub fn is_loaded(&self, iid: &str) -> bool {
+        self.loaded.contains_key(iid)
+    }
+
+    pub fn loaded_levels(&self) -> impl Iterator<Item = &str> {
+        self.loaded.keys().map(|x| x.as_str())
+    }
+
+    /// Streams levels around the main camera.
+    pub fn update(
+        &mut self,
+        json: &LdtkJson,
+        world: &mut hecs::World,
+    ) -> Vec<LdtkLevelEvent> {
+        self.update_with_view(json, world, main_camera_view())
+    }
+
+    /// Same as `update`, but also loads levels saved in separate `.ldtkl`
+    /// files before they're streamed in, and frees their data once they're
+    /// streamed out. Use this together with `LdtkWorldMap::new_lazy`.
+    ///
+    /// Levels whose file fails to load are skipped until the file changes.
+    pub fn update_map(
+        &mut self,
+        map: &mut LdtkWorldMap,
+        world: &mut hecs::World,
+    ) -> Vec<LdtkLevelEvent> {
+        let view = main_camera_view();
+
+        for iid in self.levels_to_load(view) {
+            if map.level_failed_to_load(&iid) {
+                continue;
+            }
+
+            if let Err(err) = map.load_level(&iid) {
+                error!("Failed to load LDtk level {iid}: {err:?}");
+            }
+        }
+
+        let events = self.update_levels(&map.json, world, view, |iid| {
+            map.is_level_loaded(iid)
+        });
+
+        for event in events.iter() {
+            if let LdtkLevelEvent::Unloaded(iid) = event {
+                map.unload_level(iid);
+            }
+        }
+
+        events
+    }
+
+    /// Streams levels around an arbitrary view rectangle, e.g. when the
+    /// camera isn't following the player.
+    pub fn update_with_view(
+        &mut self,
+        json: &LdtkJson,
+        world: &mut hecs::World,
+        view: AABB,
+    ) -> Vec<LdtkLevelEvent> {
+        self.update_levels(json, world, view, |_| true)
+    }
+
+    /// Levels which aren't loaded yet but should be for the given view.
+    fn levels_to_load(&self, view: AABB) -> Vec<String> {
+        let current = self.level_at(view.center());
+        let load_view = expand_aabb(view, self.load_distance);
+
+        self.levels
+            .iter()
+            .filter(|level| {
+                match current {
+                    Some(current) if current.iid == level.iid => true,
+                    Some(current) if !current.neighbours.is_empty() => {
+                        current.neighbours.contains(&level.iid) &&
+                            level.rect.intersects(&load_view)
+                    }
+                    _ => level.rect.intersects(&load_view),
+                }
+            })
+            .filter(|level| !self.loaded.contains_key(&level.iid))
+            .map(|level| level.iid.clone())
+            .collect_vec()
+    }
+
+    /// Only loads levels for which `can_load` returns `true`, the others
+    /// are tried again on the next update.
+    fn update_levels(
+        &mut self,
+        json: &LdtkJson,
+        world: &mut hecs::World,
+        view: AABB,
+        can_load: impl Fn(&str) -> bool,
+    ) -> Vec<LdtkLevelEvent> {
+        let mut events = Vec::new();
+
+        let current = self.level_at(view.center()).map(|x| x.iid.clone());
+
+        if current != self.current_level {
+            if let Some(previous) = self.current_level.take() {
+                events.push(LdtkLevelEvent::Left(previous));
+            }
+
+            if let Some(current) = current.as_ref() {
+                events.push(LdtkLevelEvent::Entered(current.clone()));
+            }
+
+            self.current_level = current.clone();
+        }
+
+        let unload_view = expand_aabb(view, self.unload_distance);
+
+        let to_load = self
+            .levels_to_load(view)
+            .into_iter()
+            .filter(|iid| can_load(iid))
+            .collect_vec();
+
+        let to_unload = self
+            .levels
+            .iter()
+            .filter(|level| self.loaded.contains_key(&level.iid))
+            .filter(|level| current.as_ref() != Some(&level.iid))
+            .filter(|level| !level.rect.intersects(&unload_view))
+            .map(|level| level.iid.clone())
+            .collect_vec();
+
+        for iid in to_unload {
+            self.unload_level(world, &iid);
+            events.push(LdtkLevelEvent::Unloaded(iid));
+        }
+
+        for iid in to_load {
+            if self.load_level(json, world, &iid) {
+                events.push(LdtkLevelEvent::Loaded(iid));
+            }
+        }
+
+        events
+    }
+
+    /// Loads a level regardless of where the camera is. Returns `false` if
+    /// the level doesn't exist.
+    pub fn load_level(
+        &mut self,
+        json: &LdtkJson,
+        world: &mut hecs::World,
+        iid: &str,
+    ) -> bool {
+        let (Some(level), Some(bounds)) =
+            (find_ldtk_level(json, iid), self.level_bounds(iid))
+        else {
+            return false;
+        };
+
+        let offset = bounds.rect.min;
+        let level_px = bounds.world_px;
+
+        let mut tilemaps = Vec::new();
+
+        for (i, layer) in
+            level.layer_instances.iter().flatten().rev().enumerate()
+        {
+            if !layer.visible {
+                continue;
+            }
+
+            let Some(tileset) =
+                layer.override_tileset_uid.or(layer.tileset_def_uid).and_then(
+                    |uid| json.defs.tilesets.iter().find(|t| t.uid == uid),
+                )
+            else {
+                continue;
+            };
+
+            let Some(texture) = self.tileset_textures.get(&tileset.identifier)
+            else {
+                continue;
+            };
+
+            tilemaps.extend(ldtk_layer_tilemaps(
+                layer,
+                tileset,
+                *texture,
+                level_px,
+                self.pixels_per_unit,
+                self.z_index + i as i32,
+            ));
+        }
+
+        self.registry.spawn_level_entities_at(
+            world,
+            level,
+            offset,
+            self.pixels_per_unit,
+        );
+        self.loaded.insert(iid.to_string(), tilemaps);
+
+        true
+    }
+
+    pub fn unload_level(&mut self, world: &mut hecs::World, iid: &str) {
+        self.registry.despawn_level_entities(world, iid);
+        self.loaded.remove(iid);
+    }
+
+    pub fn unload_all(&mut self, world: &mut hecs::World) {
+        self.registry.despawn_all(world);
+        self.loaded.clear();
+        self.current_level = None;
+    }
+
+    /// Recomputes level positions and reloads all loaded levels. Call this
+    /// after the project was hot reloaded.
+    pub fn reload(&mut self, json: &LdtkJson, world: &mut hecs::World) {
+        self.levels = ldtk_level_bounds(json, self.pixels_per_unit);
+
+        let loaded = self.loaded.keys().cloned().collect_vec();
+
+        for iid in loaded {
+            self.unload_level(world, &iid);
+            self.load_level(json, world, &iid);
+        }
+    }
+
+    /// Reloads a single level if it's loaded, e.g. after
+    /// `LdtkReloadEvent::Level` was returned by `LdtkWorldMap::maybe_reload`.
+    pub fn reload_level(
+        &mut self,
+        json: &LdtkJson,
+        world: &mut hecs::World,
+        iid: &str,
+    ) {
+        if self.loaded.contains_key(iid) {
+            self.unload_level(world, iid);
+            self.load_level(json, world, iid);
+        }
+    }
+
+    /// Draws tile layers of all loaded levels. Their tilemaps are only
+    /// uploaded to the GPU once, and only the chunks visible by the main
+    /// camera are drawn.
+    pub fn draw(&self) {
+        for tilemap in self.loaded.values().flatten() {
+            tilemap.draw();
+        }
+    }
+}
+
+fn main_camera_view() -> AABB {
+    let camera = main_camera();
+    AABB::from_center_size(camera.center, camera.world_viewport())
+}
+
+fn expand_aabb(aabb: AABB, amount: f32) -> AABB {
+    AABB::new(aabb.min - splat(amount), aabb.max + splat(amount))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A row of levels `a`, `b` and `c` from left to right, where only
+    /// neighbouring levels know about each other, and a level `d` below `a`
+    /// that isn't anyone's neighbour.
+    fn project() -> LdtkJson {
+        let (mut json, template) = test_project();
+
+        let level = |iid: &str, x: i64, y: i64, neighbours: &[&str]| {
+            let mut level = template.clone();
+            level.iid = iid.to_string();
+            level.world_x = x;
+            level.world_y = y;
+            level.neighbours = neighbours
+                .iter()
+                .map(|x| {
+                    NeighbourLevel {
+                        dir: "e".to_string(),
+                        level_iid: x.to_string(),
+                        level_uid: None,
+                    }
+                })
+                .collect();
+            level
+        };
+
+        json.levels = vec![
+            level("a", 0, 0, &["b"]),
+            level("b", 256, 0, &["a", "c"]),
+            level("c", 512, 0, &["b"]),
+            level("d", 0, 256, &[]),
+        ];
+
+        json
+    }
+
+    fn view(center: Vec2) -> AABB {
+        AABB::from_center_size(center, splat(2.0))
+    }
+
+    fn loaded(streamer: &LdtkWorldStreamer) -> Vec<&str> {
+        streamer.loaded_levels().sorted().collect()
+    }
+
+    #[test]
+    fn streams_neighbours() {
+        let json = project();
+        let mut world = hecs::World::new();
+        let mut streamer =
+            LdtkWorldStreamer::new(&json, LdtkEntityRegistry::new());
+
+        // Levels are 8x8 world units, `d` is within the load distance of the
+        // view but isn't a neighbour of `a`.
+        let events =
+            streamer.update_with_view(&json, &mut world, view(vec2(4.0, -4.0)));
+
+        assert_eq!(streamer.current_level(), Some("a"));
+        assert_eq!(loaded(&streamer), ["a", "b"]);
+        assert!(events.contains(&LdtkLevelEvent::Entered("a".to_string())));
+
+        let events = streamer.update_with_view(
+            &json,
+            &mut world,
+            view(vec2(20.0, -4.0)),
+        );
+
+        assert_eq!(streamer.current_level(), Some("c"));
+        assert_eq!(loaded(&streamer), ["b", "c"]);
+        assert_eq!(events, [
+            LdtkLevelEvent::Left("a".to_string()),
+            LdtkLevelEvent::Entered("c".to_string()),
+            LdtkLevelEvent::Unloaded("a".to_string()),
+            LdtkLevelEvent::Loaded("c".to_string()),
+        ]);
+
+        // Outside of all levels everything within the distance is loaded.
+        streamer.update_with_view(&json, &mut world, view(vec2(4.0, -20.0)));
+
+        assert_eq!(streamer.current_level(), None);
+        assert_eq!(loaded(&streamer), ["d"]);
+    }
+
+    #[test]
+    fn spawns_entities_in_world_units() {
+        let json = project();
+        let mut world = hecs::World::new();
+
+        let mut registry = LdtkEntityRegistry::new();
+        registry.register("Character", |ctx| {
+            let mut builder = hecs::EntityBuilder::new();
+            builder.add(ctx.world_pos());
+            Some(builder)
+        });
+
+        let mut streamer = LdtkWorldStreamer::new(&json, registry);
+        // Two world units per cell of the 32 pixel grid.
+        streamer.pixels_per_unit = 16.0;
+        streamer.reload(&json, &mut world);
+
+        streamer.load_level(&json, &mut world, "b");
+        let entity = streamer.registry.level_entities("b")[0];
+        let streamed = *world.get::<&Vec2>(entity).unwrap();
+
+        let mut cell_registry = LdtkEntityRegistry::new();
+        cell_registry.register("Character", |ctx| {
+            let mut builder = hecs::EntityBuilder::new();
+            builder.add(ctx.world_pos());
+            Some(builder)
+        });
+
+        let entity = cell_registry.spawn_level_entities(
+            &mut world,
+            find_ldtk_level(&json, "b").unwrap(),
+        )[0];
+        let in_cells = *world.get::<&Vec2>(entity).unwrap();
+
+        // `b` starts at 256 pixels, and the Y axis points up.
+        assert_eq!(streamed, vec2(16.0, -16.0) + in_cells * 2.0);
+    }
+}
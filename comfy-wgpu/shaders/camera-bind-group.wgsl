@@ -30,6 +30,8 @@ struct GlobalLightingParams {
     bloom_threshold: f32,
     bloom_lerp: f32,
     bloom_gamma: f32,
+    lut_weight: f32,
+    lut_crossfade: f32,
 }
 
 struct CameraUniform {
@@ -43,26 +45,51 @@ struct Light {
     screen_position: vec2<f32>,
     radius: f32,
     strength: f32,
-    _padding: vec2<f32>,
-}
-
-struct LightsUniform {
-    lights: array<Light, 128>,
-    light_count: u32,
+    height: f32,
+    casts_shadows: u32,
+    shadow_index: i32,
+    // 0 point, 1 spot, 2 area, see `LightShape`.
+    shape: u32,
+    rotation: f32,
+    inner_angle: f32,
+    outer_angle: f32,
+    cookie: i32,
+    size: vec2<f32>,
 }
 
 @group(1) @binding(0)
 var<uniform> camera: CameraUniform;
 
-@group(1) @binding(1)
-var<uniform> lights: LightsUniform;
+// `@group(1) @binding(1)` holds the lights, declared together with
+// `light_range` and `light_at` in `lights-storage.wgsl` or
+// `lights-uniform.wgsl` depending on the device.
 
 @group(1) @binding(2)
 var<uniform> params: GlobalLightingParams;
 
-@group(1) @binding(3)
-var color_lut_texture: texture_2d<f32>;
 
-@group(1) @binding(4)
-var color_lut_sampler: sampler;
+struct FrameData {
+    projection: mat4x4<f32>,
+    mouse_world: vec2<f32>,
+    mouse_screen: vec2<f32>,
+    time: f32,
+    delta: f32,
+    frame: i32,
+    fps: f32,
+    aspect_ratio: f32,
+}
+
+@group(1) @binding(5)
+var<uniform> frame: FrameData;
+
+// One row per shadow casting light with the distance to the closest occluder
+// in every direction, see `compute_shadow_map`.
+@group(1) @binding(6)
+var shadow_map: texture_2d<f32>;
+
+// Cookie textures of lights, one layer per texture in `light_cookies`.
+@group(1) @binding(9)
+var light_cookies: texture_2d_array<f32>;
 
+@group(1) @binding(10)
+var light_cookie_sampler: sampler;
@@ -77,7 +77,7 @@ fn fs_main(vertex: VertexOutput) -> @location(0) vec4<f32> {
     let center_boost: f32 = 0.2; // Change this value to control the minimum aberration at the center
 
     // Adjust the chromatic aberration strength based on the vignetting effect and center_boost
-    let off: f32 = (params.chromatic_aberration * vignette_strength + center_boost * params.chromatic_aberration) / params.resolution.x;
+    let off: f32 = (amount * vignette_strength + center_boost * amount) / f32(textureDimensions(r_color).x);
 
     var color: vec3<f32>;
 
@@ -0,0 +1,10 @@
+// Used instead of tonemapping when only color grading is enabled.
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(r_color, r_sampler, in.tex_coords);
+
+    let encoded = pow(max(color.rgb, vec3<f32>(0.0)), vec3<f32>(1.0 / 2.2));
+    let graded = apply_color_grading(encoded);
+
+    return vec4<f32>(pow(graded, vec3<f32>(2.2)), color.a);
+}
@@ -0,0 +1,18 @@
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Bend the image like an old curved screen.
+    let centered = in.tex_coords * 2.0 - 1.0;
+    let offset = centered.yx * centered.yx * curvature;
+    let uv = (centered + centered * offset) * 0.5 + 0.5;
+
+    if (uv.x < 0.0 || uv.x > 1.0 || uv.y < 0.0 || uv.y > 1.0) {
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
+
+    let color = textureSample(r_color, r_sampler, uv);
+
+    let height = f32(textureDimensions(r_color).y);
+    let scanline = sin(uv.y * height * 1.5) * 0.5 + 0.5;
+
+    return vec4<f32>(color.rgb * (1.0 - scanlines * scanline), color.a);
+}
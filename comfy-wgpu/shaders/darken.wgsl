@@ -2,9 +2,8 @@
 fn fs_main(vertex: VertexOutput) -> @location(0) vec4<f32> {
     var color = textureSample(r_color, r_sampler, vertex.tex_coords);
 
-    color.r *= 1.1;
-    color.g *= 0.9;
-    color.b *= 0.9;
+    let darkened = color.rgb * vec3<f32>(1.1, 0.9, 0.9);
+    color = vec4<f32>(mix(color.rgb, darkened, amount), color.a);
 
     color = clamp(
         color,
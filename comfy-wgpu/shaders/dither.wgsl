@@ -1,29 +1,23 @@
-@fragment
-fn fs_main(vertex: VertexOutput) -> @location(0) vec4<f32> {
-    let ratio = params.resolution.x / params.resolution.y;
-    let scale_val = 1920.0;
-    let scale = vec2<f32>(scale_val, scale_val / ratio);
-    let uv = scale / round(vertex.tex_coords * scale);
-
-    var color = textureSample(r_color, r_sampler, vertex.tex_coords);
+// Ordered dithering with a 4x4 Bayer matrix, quantizing every channel to
+// `levels` values.
+fn bayer_threshold(pixel: vec2<u32>) -> f32 {
+    var bayer = array<f32, 16>(
+        0.0, 8.0, 2.0, 10.0,
+        12.0, 4.0, 14.0, 6.0,
+        3.0, 11.0, 1.0, 9.0,
+        15.0, 7.0, 13.0, 5.0,
+    );
 
-    let noise = color.r * color.r * params.time * 0.01;
-    // let noise = color.r * color.r * color.r * params.time * 0.0001;
-    
-    // var randomValue = fract(sin(dot(uv + vec2<f32>(noise), vec2<f32>(12.9898, 78.233))) * 43758.5453);
-    var randomValue = fract(sin(dot(uv + vec2<f32>(uv), vec2<f32>(12.9898, 78.233))) * (43758.5453 + noise));
+    return (bayer[(pixel.y % 4u) * 4u + pixel.x % 4u] + 0.5) / 16.0 - 0.5;
+}
 
-    let dither_enabled = true;
+@fragment
+fn fs_main(vertex: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(r_color, r_sampler, vertex.tex_coords);
 
-    // return vec4<f32>(randomValue, randomValue, randomValue, 1.0);
+    let steps = f32(max(levels, 2u) - 1u);
+    let threshold = bayer_threshold(vec2<u32>(vertex.position.xy));
+    let rgb = floor(color.rgb * steps + threshold + 0.5) / steps;
 
-    if dither_enabled {
-      if (randomValue < color.r) {
-          return vec4<f32>(1.0, 1.0, 1.0, 1.0);
-      } else {
-          return vec4<f32>(0.0, 0.0, 0.0, 1.0);
-      }
-    } else {
-        return color;
-    }
+    return vec4<f32>(clamp(rgb, vec3<f32>(0.0), vec3<f32>(1.0)), color.a);
 }
@@ -21,7 +21,7 @@ fn perlin_noise(v: vec2<f32>) -> f32 {
     var xf = f32(fract(v.x));
     var yf = f32(fract(v.y));
     var u = fade(xf);
-    var v = fade(yf);
+    var w = fade(yf);
 
     let a  = perm[(xi + 0) & 255];
     let b  = perm[(xi + 1) & 255];
@@ -32,7 +32,7 @@ fn perlin_noise(v: vec2<f32>) -> f32 {
 
     let x1 = lerp(grad(aa, xf, yf), grad(ba, xf - 1.0, yf), u);
     let x2 = lerp(grad(ab, xf, yf - 1.0), grad(bb, xf - 1.0, yf - 1.0), u);
-    let y1 = lerp(x1, x2, v);
+    let y1 = lerp(x1, x2, w);
 
     return (y1 + 1.0) / 2.0;
 }
@@ -162,12 +162,12 @@ fn fs_main(vertex: VertexOutput) -> @location(0) vec4<f32> {
     // Noise
     let noise_scale = 500.0; // Controls the scale of the noise effect
     let noise_intensity = 0.52; // Controls the intensity of the noise effect
-    let noise = perlin_noise(vertex.tex_coords * noise_scale);
+    let noise = perlin_noise(vertex.tex_coords * noise_scale + vec2<f32>(params.time * 60.0));
 
     let noise_color = vec3<f32>(noise, noise, noise) * noise_intensity;
     // rgb = blend_overlay(rgb, mix(vec3<f32>(0.5, 0.5, 0.5), noise_color, 0.001));
     // rgb = blend_switch(blend_mode, rgb, mix(vec3<f32>(0.5, 0.5, 0.5), noise_color, 0.005));
-    rgb = blend_switch(blend_mode, rgb, noise_color, 0.5);
+    rgb = blend_switch(blend_mode, rgb, noise_color, strength);
 
     // rgb += mix(0.0, noise_intensity * (noise - 0.5), params.noise_strength);
 
@@ -178,7 +178,7 @@ fn fs_main(vertex: VertexOutput) -> @location(0) vec4<f32> {
     let vignette_dist = distance(vertex.tex_coords, vignette_center);
     let vignette_factor = smoothstep(vignette_radius, vignette_radius - vignette_softness, vignette_dist);
 
-    rgb *= mix(1.0, vignette_factor, params.vignette_intensity);
+    rgb *= mix(1.0, vignette_factor, vignette);
 
     return vec4<f32>(rgb, color.a);
 }
@@ -1,34 +1,6 @@
-@group(0) @binding(0)
-var t_diffuse: texture_2d<f32>;
-@group(0)@binding(1)
-var s_diffuse: sampler;
-
-// Vertex shader
-
-struct VertexInput {
-    @location(0) position: vec3<f32>,
-    @location(1) tex_coords: vec2<f32>,
-    @location(2) color: vec4<f32>,
-}
-
-struct VertexOutput {
-    @builtin(position) clip_position: vec4<f32>,
-    @location(0) tex_coords: vec2<f32>,
-    @location(1) color: vec4<f32>,
-    @location(2) world_position: vec3<f32>,
-}
-
 @vertex
 fn vs_main(
     model: VertexInput,
 ) -> VertexOutput {
-    var out: VertexOutput;
-
-    out.tex_coords = model.tex_coords;
-    out.clip_position = camera.view_proj * vec4<f32>(model.position, 1.0);
-    out.color = model.color;
-    out.world_position = model.position;
-
-    return out;
+    return sprite_vertex(model);
 }
-
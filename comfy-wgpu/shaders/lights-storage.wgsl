@@ -0,0 +1,43 @@
+
+struct LightGrid {
+    origin: vec2<f32>,
+    cell_size: vec2<f32>,
+    size: vec2<u32>,
+    light_count: u32,
+    _padding: u32,
+}
+
+@group(1) @binding(1)
+var<storage, read> lights: array<Light>;
+
+@group(1) @binding(7)
+var<uniform> light_grid: LightGrid;
+
+// Offset and light count of every grid cell, followed by the light indices,
+// see `LightGrid`.
+@group(1) @binding(8)
+var<storage, read> light_grid_cells: array<u32>;
+
+// Start and count of the `light_at` indices of lights which can reach
+// `world_position`.
+fn light_range(world_position: vec2<f32>) -> vec2<u32> {
+    if (light_grid.light_count == 0u) {
+        return vec2<u32>(0u, 0u);
+    }
+
+    let cell = floor((world_position - light_grid.origin) / light_grid.cell_size);
+
+    if (any(cell < vec2<f32>(0.0)) || any(cell >= vec2<f32>(light_grid.size))) {
+        return vec2<u32>(0u, 0u);
+    }
+
+    let index = (u32(cell.y) * light_grid.size.x + u32(cell.x)) * 2u;
+
+    return vec2<u32>(light_grid_cells[index], light_grid_cells[index + 1u]);
+}
+
+fn light_at(i: u32) -> Light {
+    let cell_count = light_grid.size.x * light_grid.size.y;
+
+    return lights[light_grid_cells[cell_count * 2u + i]];
+}
@@ -0,0 +1,17 @@
+
+struct LightsUniform {
+    lights: array<Light, 128>,
+    light_count: u32,
+}
+
+@group(1) @binding(1)
+var<uniform> lights: LightsUniform;
+
+// Without storage buffers every pixel goes through all of the lights.
+fn light_range(world_position: vec2<f32>) -> vec2<u32> {
+    return vec2<u32>(0u, lights.light_count);
+}
+
+fn light_at(i: u32) -> Light {
+    return lights.lights[i];
+}
@@ -0,0 +1,57 @@
+// Color grading with 3D LUTs, bound by the renderer for the final post
+// processing pass. `lut_previous` is faded out while `lut_current` fades in,
+// both are an identity LUT when not in use.
+
+@group(2) @binding(0)
+var lut_current: texture_3d<f32>;
+@group(2) @binding(1)
+var lut_previous: texture_3d<f32>;
+@group(2) @binding(2)
+var lut_sampler: sampler;
+
+// Input domains of the LUTs, colors are remapped from them before sampling.
+struct LutDomains {
+    current_min: vec4<f32>,
+    current_max: vec4<f32>,
+    previous_min: vec4<f32>,
+    previous_max: vec4<f32>,
+}
+
+@group(2) @binding(3)
+var<uniform> lut_domains: LutDomains;
+
+fn sample_lut(
+    lut: texture_3d<f32>,
+    color: vec3<f32>,
+    domain_min: vec3<f32>,
+    domain_max: vec3<f32>,
+) -> vec3<f32> {
+    let size = f32(textureDimensions(lut).x);
+    let coord = (color - domain_min) / (domain_max - domain_min);
+    let uvw = saturate(coord) * ((size - 1.0) / size) + 0.5 / size;
+
+    return textureSampleLevel(lut, lut_sampler, uvw, 0.0).rgb;
+}
+
+// Expects gamma encoded colors, which is what LUTs are authored for.
+fn apply_color_grading(color: vec3<f32>) -> vec3<f32> {
+    if (params.use_lut == 0u) {
+        return color;
+    }
+
+    let previous = sample_lut(
+        lut_previous,
+        color,
+        lut_domains.previous_min.xyz,
+        lut_domains.previous_max.xyz,
+    );
+    let current = sample_lut(
+        lut_current,
+        color,
+        lut_domains.current_min.xyz,
+        lut_domains.current_max.xyz,
+    );
+    let graded = mix(previous, current, params.lut_crossfade);
+
+    return mix(color, graded, params.lut_weight);
+}
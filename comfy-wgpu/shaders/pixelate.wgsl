@@ -0,0 +1,8 @@
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let size = vec2<f32>(textureDimensions(r_color));
+    let block = max(pixel_size, 1.0) / size;
+    let uv = (floor(in.tex_coords / block) + 0.5) * block;
+
+    return textureSample(r_color, r_sampler, uv);
+}
@@ -29,7 +29,7 @@ fn fs_main(vertex: VertexOutput) -> @location(0) vec4<f32> {
     let speed = 1.0;
 
     // Calculate the screen shake offset using two sine waves with different frequencies and the random value
-    var shake_offset = params.shake_amount * sin(frequency * params.time + vec2<f32>(0.0, 3.14159) * random_value) * speed;
+    var shake_offset = amount * sin(frequency * params.time + vec2<f32>(0.0, 3.14159) * random_value) * speed;
 
     // Displace the texture coordinates with the screen shake offset
     var displaced_tex_coords = vertex.tex_coords + shake_offset;
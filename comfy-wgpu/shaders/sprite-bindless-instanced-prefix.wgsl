@@ -0,0 +1,71 @@
+@group(0) @binding(0)
+var t_diffuse: binding_array<texture_2d<f32>>;
+@group(0) @binding(1)
+var s_diffuse: binding_array<sampler>;
+
+// Vertex shader
+
+struct InstanceInput {
+    @location(0) position: vec3<f32>,
+    @location(1) rotation: f32,
+    @location(2) size: vec2<f32>,
+    @location(3) uv_rect: vec4<f32>,
+    @location(4) color: vec4<f32>,
+    @location(5) texture_index: u32,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) world_position: vec3<f32>,
+    @location(3) @interpolate(flat) texture_index: u32,
+}
+
+// Same order as the [0, 2, 1, 0, 3, 2] indices used for regular quads.
+var<private> QUAD_CORNERS: array<vec2<f32>, 6> = array<vec2<f32>, 6>(
+    vec2<f32>(0.0, 0.0),
+    vec2<f32>(1.0, 1.0),
+    vec2<f32>(1.0, 0.0),
+    vec2<f32>(0.0, 0.0),
+    vec2<f32>(0.0, 1.0),
+    vec2<f32>(1.0, 1.0),
+);
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) vertex_index: u32,
+    sprite: InstanceInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+
+    let corner = QUAD_CORNERS[vertex_index];
+
+    let local = (corner - 0.5) * sprite.size;
+    let c = cos(sprite.rotation);
+    let s = sin(sprite.rotation);
+    let rotated = vec2<f32>(local.x * c - local.y * s, local.x * s + local.y * c);
+
+    let world_position = sprite.position + vec3<f32>(rotated, 0.0);
+
+    out.tex_coords = mix(sprite.uv_rect.xy, sprite.uv_rect.zw, corner);
+    out.clip_position = camera.view_proj * vec4<f32>(world_position, 1.0);
+    out.color = sprite.color;
+    out.world_position = world_position;
+    out.texture_index = sprite.texture_index;
+
+    return out;
+}
+
+fn sample_diffuse(in: VertexOutput) -> vec4<f32> {
+    return textureSample(
+        t_diffuse[in.texture_index],
+        s_diffuse[in.texture_index],
+        in.tex_coords
+    );
+}
+
+// Normal maps aren't supported with bindless textures.
+fn sample_normal(in: VertexOutput) -> vec4<f32> {
+    return vec4<f32>(0.5, 0.5, 1.0, 0.0);
+}
@@ -0,0 +1,49 @@
+@group(0) @binding(0)
+var t_diffuse: binding_array<texture_2d<f32>>;
+@group(0) @binding(1)
+var s_diffuse: binding_array<sampler>;
+
+// Vertex shader
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) color: vec4<f32>,
+    @location(3) texture_index: u32,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) world_position: vec3<f32>,
+    @location(3) @interpolate(flat) texture_index: u32,
+}
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+
+    out.tex_coords = model.tex_coords;
+    out.clip_position = camera.view_proj * vec4<f32>(model.position, 1.0);
+    out.color = model.color;
+    out.world_position = model.position;
+    out.texture_index = model.texture_index;
+
+    return out;
+}
+
+fn sample_diffuse(in: VertexOutput) -> vec4<f32> {
+    return textureSample(
+        t_diffuse[in.texture_index],
+        s_diffuse[in.texture_index],
+        in.tex_coords
+    );
+}
+
+// Normal maps aren't supported with bindless textures.
+fn sample_normal(in: VertexOutput) -> vec4<f32> {
+    return vec4<f32>(0.5, 0.5, 1.0, 0.0);
+}
@@ -0,0 +1,45 @@
+@group(0) @binding(0)
+var t_diffuse: texture_2d<f32>;
+@group(0)@binding(1)
+var s_diffuse: sampler;
+// A flat normal map with zero alpha for textures without one.
+@group(0) @binding(2)
+var t_normal: texture_2d<f32>;
+@group(0) @binding(3)
+var s_normal: sampler;
+
+// Vertex shader
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) color: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) world_position: vec3<f32>,
+}
+
+// The default sprite transform, custom vertex shaders can call this and
+// adjust the result instead of starting from scratch.
+fn sprite_vertex(model: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+
+    out.tex_coords = model.tex_coords;
+    out.clip_position = camera.view_proj * vec4<f32>(model.position, 1.0);
+    out.color = model.color;
+    out.world_position = model.position;
+
+    return out;
+}
+
+fn sample_diffuse(in: VertexOutput) -> vec4<f32> {
+    return textureSample(t_diffuse, s_diffuse, in.tex_coords);
+}
+
+fn sample_normal(in: VertexOutput) -> vec4<f32> {
+    return textureSample(t_normal, s_normal, in.tex_coords);
+}
@@ -1,5 +1,43 @@
-fn apply_light(in: VertexOutput, light: Light) -> vec4<f32> {
-    let light_to_frag = in.world_position.xy - light.world_position;
+// Fraction of the light blocked by occluders. Neighbouring directions of the
+// shadow map are averaged, which makes shadows softer further from the light.
+fn light_shadow(light: Light, light_to_frag: vec2<f32>, distance: f32) -> f32 {
+    if (light.shadow_index < 0) {
+        return 0.0;
+    }
+
+    let resolution = i32(textureDimensions(shadow_map).x);
+    let angle = atan2(light_to_frag.y, light_to_frag.x) / 6.28318530718;
+    let center = i32(fract(angle) * f32(resolution));
+
+    var shadow = 0.0;
+
+    for (var i: i32 = -2; i <= 2; i = i + 1) {
+        let x = (center + i + resolution) % resolution;
+        let occluder_distance =
+            textureLoad(shadow_map, vec2<i32>(x, light.shadow_index), 0).r;
+
+        shadow = shadow + select(0.0, 1.0, distance > occluder_distance);
+    }
+
+    return shadow / 5.0;
+}
+
+fn apply_light(in: VertexOutput, light: Light, normal: vec4<f32>) -> vec4<f32> {
+    let center_to_frag = in.world_position.xy - light.world_position;
+
+    // Position of the fragment with the light's rotation undone.
+    let axis = vec2<f32>(cos(light.rotation), sin(light.rotation));
+    let perp = vec2<f32>(-axis.y, axis.x);
+    let local = vec2<f32>(dot(center_to_frag, axis), dot(center_to_frag, perp));
+
+    // Area lights shine from the closest point of their rectangle.
+    var light_to_frag = center_to_frag;
+
+    if (light.shape == 2u) {
+        let closest = clamp(local, -light.size * 0.5, light.size * 0.5);
+        light_to_frag = center_to_frag - axis * closest.x - perp * closest.y;
+    }
+
     let distance = length(light_to_frag);
 
     if (distance > light.radius) {
@@ -12,22 +50,68 @@ fn apply_light(in: VertexOutput, light: Light) -> vec4<f32> {
     let modifier = select(attenuation, falloff, params.quadratic_falloff == 0u);
     var diffuse = light.strength * modifier;
 
-    return light.color * diffuse * params.global_light_intensity;
+    if (light.shape == 1u) {
+        let angle = acos(clamp(local.x / max(distance, 0.0001), -1.0, 1.0));
+        let cone = clamp(
+            (light.outer_angle - angle) /
+                max(light.outer_angle - light.inner_angle, 0.0001),
+            0.0,
+            1.0
+        );
+
+        diffuse = diffuse * cone * cone * (3.0 - 2.0 * cone);
+    }
+
+    // Sprites without a normal map have a zero alpha flat normal map, which
+    // leaves their lighting as it is.
+    let n = normalize(normal.rgb * 2.0 - 1.0);
+    let l = normalize(vec3<f32>(-light_to_frag, max(light.height, 0.001)));
+    diffuse = diffuse * mix(1.0, max(dot(n, l), 0.0), normal.a);
+
+    let shadow = light_shadow(light, center_to_frag, length(center_to_frag));
+    diffuse = diffuse * (1.0 - shadow * params.shadow_strength);
+
+    var color = light.color;
+
+    // Cookies cover everything the light reaches, rotated with the light.
+    if (light.cookie >= 0) {
+        let half_extent = select(
+            vec2<f32>(light.radius),
+            light.size * 0.5 + light.radius,
+            light.shape == 2u
+        );
+
+        let uv = vec2<f32>(local.x, -local.y) / (2.0 * half_extent) + 0.5;
+        let cookie = textureSampleLevel(
+            light_cookies,
+            light_cookie_sampler,
+            uv,
+            light.cookie,
+            0.0
+        );
+
+        color = color * vec4<f32>(cookie.rgb * cookie.a, 1.0);
+    }
+
+    return color * diffuse * params.global_light_intensity;
 }
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    let tex = textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    let tex = sample_diffuse(in);
     let base_color: vec4<f32> = tex * in.color;
+    let normal = sample_normal(in);
 
     // Ambient lighting
     var ambient_color: vec4<f32> = params.ambient_light_color * params.ambient_light_intensity;
     var final_color: vec4<f32> = base_color * ambient_color;
 
     if (params.lighting_enabled == 1u) {
-        for (var i: u32 = 0u; i < lights.light_count; i = i + 1u) {
-            let light = lights.lights[i];
-            final_color = final_color + (base_color * apply_light(in, light));
+        let range = light_range(in.world_position.xy);
+
+        for (var i: u32 = range.x; i < range.x + range.y; i = i + 1u) {
+            let light = light_at(i);
+            final_color = final_color + (base_color * apply_light(in, light, normal));
         }
     }
 
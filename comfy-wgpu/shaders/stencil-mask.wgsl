@@ -0,0 +1,12 @@
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let alpha = sample_diffuse(in).a * in.color.a;
+
+    // Only the stencil buffer is written, mostly transparent pixels are
+    // discarded so that they don't become part of the mask.
+    if (alpha < 0.5) {
+        discard;
+    }
+
+    return vec4<f32>(1.0);
+}
@@ -334,6 +334,7 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
 
 // #ifdef DEBAND_DITHER
     var output_rgb = powsafe(color.rgb, 1.0 / 2.2);
+        output_rgb = apply_color_grading(output_rgb);
         output_rgb = output_rgb + screen_space_dither(in.position.xy);
     // This conversion back to linear space is required because our output texture format is
     // SRGB; the GPU will assume our output is linear and will apply an SRGB conversion.
@@ -0,0 +1,11 @@
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(r_color, r_sampler, in.tex_coords);
+
+    let softness = 0.5;
+    let dist = distance(in.tex_coords, vec2<f32>(0.5, 0.5));
+    let factor = smoothstep(radius, radius - softness, dist);
+    let amount = (1.0 - factor) * intensity * vignette_color.a;
+
+    return vec4<f32>(mix(color.rgb, vignette_color.rgb, amount), color.a);
+}
@@ -1,5 +1,7 @@
 use crate::*;
 
+use std::{cell::Cell, collections::BTreeMap};
+
 pub fn run_batched_render_passes(
     c: &mut WgpuRenderer,
     surface_view: &wgpu::TextureView,
@@ -11,7 +13,52 @@ pub fn run_batched_render_passes(
 
     let mut is_first = true;
 
-    let queues = consume_render_queues();
+    let mut queues = consume_render_queues();
+    let mut instance_queues = consume_sprite_instance_queues();
+
+    // Sprite instances on y-sorted layers are turned back into regular
+    // meshes, since they need to be sorted together with them. The same goes
+    // for stencil masks, which are only drawn through the mesh path.
+    for key in instance_queues.keys().copied().collect_vec() {
+        if get_y_sort(key.z_index) ||
+            get_clip_state(key.clip).stencil == StencilMode::Write
+        {
+            let instances = instance_queues.remove(&key).unwrap();
+
+            queues
+                .entry(key)
+                .or_default()
+                .extend(instances.into_meshes(key.z_index, key.texture_id));
+        } else {
+            queues.entry(key).or_default();
+        }
+    }
+
+    release_tilemap_chunks(c);
+
+    // Tilemap chunks on y-sorted layers are split into regular meshes so
+    // that they get sorted together with everything else, the rest is drawn
+    // from cached chunk buffers before meshes of the same z-index.
+    let mut tilemap_layers = BTreeMap::<i32, Vec<TilemapChunkDraw>>::new();
+
+    for draw in consume_tilemap_queue() {
+        if get_y_sort(draw.z_index) {
+            queues
+                .entry(MeshGroupKey {
+                    z_index: draw.z_index,
+                    blend_mode: draw.blend_mode,
+                    texture_id: draw.texture,
+                    shader: draw.shader,
+                    render_target: draw.render_target,
+                    clip: draw.clip,
+                    layers: draw.layers,
+                })
+                .or_default()
+                .extend(draw.mesh.tile_meshes(draw.z_index, draw.texture));
+        } else {
+            tilemap_layers.entry(draw.z_index).or_default().push(draw);
+        }
+    }
 
     // let render_passes = {
     //     span_with_timing!("collect_render_passes");
@@ -33,60 +80,615 @@ pub fn run_batched_render_passes(
     //     render_passes
     // };
 
-    for (key, mut meshes) in
-        queues.into_iter().sorted_by_key(|(k, _)| k.z_index)
-    {
-        let _span = span!("blend/shader/target group");
+    let mut frame = FrameQueues {
+        meshes: queues,
+        instances: instance_queues,
+        tilemap_layers,
+        particles: params.particle_queues,
+    };
 
-        // TODO: add this back later
-        if get_y_sort(key.z_index) {
-            meshes.sort_by_key(|mesh| {
-                OrderedFloat::<f32>(-(mesh.origin.y + mesh.y_sort_offset))
-            });
+    // Render targets are drawn before anything else, so that sampling one
+    // always shows what was drawn into it in the current frame.
+    for (id, params) in render_targets() {
+        if !c.render_targets.borrow().contains_key(&id) {
+            continue;
+        }
+
+        let _span = span!("render target");
+
+        let target = {
+            let shaders = c.shaders.borrow();
+
+            frame.take_render_target(id, &params, |shader| {
+                shader_samples_texture(
+                    &shaders,
+                    shader,
+                    TextureHandle::RenderTarget(id),
+                )
+            })
+        };
+
+        let mut uniform = c.camera_uniform;
+
+        // The target's camera always covers the whole target.
+        if let Some(camera) = params.camera {
+            uniform.update_view_proj_for_camera(
+                &Camera { viewport: Viewport::FULL, ..camera },
+                params.size.as_vec2(),
+            );
+        }
+
+        let clear_color = params.clear_color.unwrap_or(TRANSPARENT);
+        let mut target_is_first = params.clear_color.is_some();
+
+        // Targets keeping their color still start every frame with empty
+        // depth and stencil buffers.
+        if target_is_first {
+            c.stencil_cleared = false;
+        } else {
+            clear_depth_stencil(c, id);
+        }
+
+        c.camera_viewport = Viewport::FULL;
+        c.camera_view_proj = uniform.view_proj();
+        c.context.queue.write_buffer(
+            &c.camera_buffer,
+            0,
+            bytemuck::cast_slice(&[uniform]),
+        );
+
+        render_camera_view(
+            c,
+            &mut target_is_first,
+            clear_color,
+            target,
+            surface_view,
+            sprite_shader_id,
+            error_shader_id,
+        );
+
+        // Targets are cleared even when nothing was drawn into them.
+        if target_is_first {
+            render_meshes(
+                c,
+                true,
+                clear_color,
+                MeshDrawData {
+                    blend_mode: BlendMode::Alpha,
+                    texture: TextureHandle::from_path("1px"),
+                    shader: ShaderInstanceId::default(),
+                    render_target: id,
+                    clip: ClipId::default(),
+                    data: Default::default(),
+                },
+                surface_view,
+                sprite_shader_id,
+                error_shader_id,
+            );
+        }
+    }
+
+    let views = collect_camera_views(c);
+
+    let last = views.len().saturating_sub(1);
+
+    for (i, view) in views.iter().enumerate() {
+        let _span = span!("camera");
+
+        let visible = |render_target: RenderTargetId, layers: RenderLayers| {
+            render_target.0 == 0 && view.layers.intersects(layers)
+        };
+
+        // Only the last camera can take the queues, the others copy just the
+        // draws they can see.
+        let frame = if i == last {
+            std::mem::take(&mut frame).filter(visible)
+        } else {
+            frame.cloned_filter(visible)
+        };
+
+        // Depth and masks of the previous cameras could overlap this one's
+        // viewport, while the color is only cleared by the first draw.
+        if is_first {
+            c.stencil_cleared = false;
+        } else {
+            clear_depth_stencil(c, RenderTargetId::default());
         }
 
-        perf_counter_inc("render passes", 1);
-        perf_counter_inc("meshes", meshes.len() as u64);
+        c.camera_viewport = view.viewport;
+        c.camera_view_proj = view.uniform.view_proj();
+        c.context.queue.write_buffer(
+            &c.camera_buffer,
+            0,
+            bytemuck::cast_slice(&[view.uniform]),
+        );
 
+        render_camera_view(
+            c,
+            &mut is_first,
+            params.clear_color,
+            frame,
+            surface_view,
+            sprite_shader_id,
+            error_shader_id,
+        );
+    }
+
+    // Everything drawn after this (post processing, egui) uses the main
+    // camera again.
+    c.camera_viewport = Viewport::FULL;
+    c.camera_view_proj = c.camera_uniform.view_proj();
+    c.context.queue.write_buffer(
+        &c.camera_buffer,
+        0,
+        bytemuck::cast_slice(&[c.camera_uniform]),
+    );
+
+    if is_first {
         render_meshes(
             c,
             is_first,
             params.clear_color,
             MeshDrawData {
-                blend_mode: key.blend_mode,
-                texture: key.texture_id,
-                shader: key.shader,
-                render_target: key.render_target,
-                data: meshes,
+                blend_mode: BlendMode::Alpha,
+                texture: TextureHandle::from_path("1px"),
+                shader: ShaderInstanceId::default(),
+                render_target: RenderTargetId::default(),
+                clip: ClipId::default(),
+                data: Default::default(),
             },
             surface_view,
             sprite_shader_id,
             error_shader_id,
         );
 
+        // MeshGroupKey {
+        //     z_index: 0,
+        //     blend_mode: BlendMode::Alpha,
+        //     texture_id: TextureHandle::from_path("1px"),
+        //     shader: None,
+        //     render_target: None,
+        // },
+        // RenderPassData {
+        //     z_index: 0,
+        //     blend_mode: BlendMode::Alpha,
+        //     texture: TextureHandle::from_path("1px"),
+        //     shader: None,
+        //     render_target: None,
+        //     data: SmallVec::new(),
+        // },
+    }
+}
+
+/// Everything queued for drawing in the current frame, drawn by every
+/// camera.
+#[derive(Default)]
+struct FrameQueues {
+    meshes: BTreeMap<MeshGroupKey, RenderQueue>,
+    instances: BTreeMap<MeshGroupKey, SpriteInstanceQueue>,
+    tilemap_layers: BTreeMap<i32, Vec<TilemapChunkDraw>>,
+    particles: HashMap<MeshGroupKey, Vec<ParticleDraw>>,
+}
+
+impl FrameQueues {
+    /// Keeps only the draws for which `visible(render_target, layers)` is
+    /// true.
+    fn filter(
+        mut self,
+        visible: impl Fn(RenderTargetId, RenderLayers) -> bool,
+    ) -> Self {
+        self.meshes.retain(|key, _| visible(key.render_target, key.layers));
+        self.instances.retain(|key, _| visible(key.render_target, key.layers));
+        self.particles.retain(|key, _| visible(key.render_target, key.layers));
+
+        self.tilemap_layers.retain(|_, draws| {
+            draws.retain(|draw| visible(draw.render_target, draw.layers));
+            !draws.is_empty()
+        });
 
-        is_first = false;
+        self
+    }
+
+    /// Like `filter`, but copies the visible draws and keeps the rest.
+    fn cloned_filter(
+        &self,
+        visible: impl Fn(RenderTargetId, RenderLayers) -> bool,
+    ) -> Self {
+        fn cloned<V: Clone>(
+            map: &BTreeMap<MeshGroupKey, V>,
+            visible: &impl Fn(RenderTargetId, RenderLayers) -> bool,
+        ) -> BTreeMap<MeshGroupKey, V> {
+            map.iter()
+                .filter(|(key, _)| visible(key.render_target, key.layers))
+                .map(|(key, value)| (*key, value.clone()))
+                .collect()
+        }
+
+        Self {
+            meshes: cloned(&self.meshes, &visible),
+            instances: cloned(&self.instances, &visible),
+            tilemap_layers: self
+                .tilemap_layers
+                .iter()
+                .filter_map(|(z_index, draws)| {
+                    let draws = draws
+                        .iter()
+                        .filter(|x| visible(x.render_target, x.layers))
+                        .cloned()
+                        .collect_vec();
+
+                    (!draws.is_empty()).then_some((*z_index, draws))
+                })
+                .collect(),
+            particles: self
+                .particles
+                .iter()
+                .filter(|(key, _)| visible(key.render_target, key.layers))
+                .map(|(key, value)| (*key, value.clone()))
+                .collect(),
+        }
+    }
+
+    /// Takes out the draws routed into the render target. When the target
+    /// has its own camera, the draws on the screen are copied into it as
+    /// well, clipped with the target's camera and its own stencil buffer.
+    ///
+    /// Screen draws that sample the target itself, either as their texture
+    /// or through a texture uniform of their shader (checked with
+    /// `samples_target`), are not copied, since a texture can't be drawn
+    /// into while it's being sampled. They are still drawn on the screen.
+    fn take_render_target(
+        &mut self,
+        id: RenderTargetId,
+        params: &RenderTargetParams,
+        samples_target: impl Fn(ShaderInstanceId) -> bool,
+    ) -> Self {
+        let mut target = Self::default();
+
+        for (key, meshes) in std::mem::take(&mut self.meshes) {
+            if key.render_target == id {
+                target.meshes.insert(key, meshes);
+            } else {
+                self.meshes.insert(key, meshes);
+            }
+        }
+
+        for (key, instances) in std::mem::take(&mut self.instances) {
+            if key.render_target == id {
+                target.instances.insert(key, instances);
+            } else {
+                self.instances.insert(key, instances);
+            }
+        }
+
+        for (key, particles) in std::mem::take(&mut self.particles) {
+            if key.render_target == id {
+                target.particles.insert(key, particles);
+            } else {
+                self.particles.insert(key, particles);
+            }
+        }
+
+        for (z_index, draws) in std::mem::take(&mut self.tilemap_layers) {
+            let (ours, rest): (Vec<_>, Vec<_>) =
+                draws.into_iter().partition(|draw| draw.render_target == id);
+
+            if !ours.is_empty() {
+                target.tilemap_layers.insert(z_index, ours);
+            }
+
+            if !rest.is_empty() {
+                self.tilemap_layers.insert(z_index, rest);
+            }
+        }
+
+        if params.camera.is_some() {
+            let skipped = Cell::new(false);
+
+            let is_feedback = |texture: TextureHandle, shader| {
+                let result = texture == TextureHandle::RenderTarget(id) ||
+                    samples_target(shader);
+
+                if result {
+                    skipped.set(true);
+                }
+
+                result
+            };
+
+            let reroute = |key: &MeshGroupKey| {
+                (key.render_target.0 == 0 &&
+                    !is_feedback(key.texture_id, key.shader))
+                .then_some(MeshGroupKey { render_target: id, ..*key })
+            };
+
+            for (key, meshes) in self.meshes.iter() {
+                if let Some(key) = reroute(key) {
+                    target
+                        .meshes
+                        .entry(key)
+                        .or_default()
+                        .extend_from_slice(meshes);
+                }
+            }
+
+            for (key, instances) in self.instances.iter() {
+                if let Some(key) = reroute(key) {
+                    let queue = target.instances.entry(key).or_default();
+                    queue.instances.extend_from_slice(&instances.instances);
+                    queue
+                        .sort_origins
+                        .extend_from_slice(&instances.sort_origins);
+                }
+            }
+
+            for (key, particles) in self.particles.iter() {
+                if let Some(key) = reroute(key) {
+                    target
+                        .particles
+                        .entry(key)
+                        .or_default()
+                        .extend_from_slice(particles);
+                }
+            }
+
+            for (z_index, draws) in self.tilemap_layers.iter() {
+                for draw in draws {
+                    if draw.render_target.0 == 0 &&
+                        !is_feedback(draw.texture, draw.shader)
+                    {
+                        target
+                            .tilemap_layers
+                            .entry(*z_index)
+                            .or_default()
+                            .push(TilemapChunkDraw {
+                                render_target: id,
+                                ..draw.clone()
+                            });
+                    }
+                }
+            }
+
+            if skipped.get() && WARNED_FEEDBACK_TARGETS.borrow_mut().insert(id)
+            {
+                warn!(
+                    "Draws sampling render target {:?} are not drawn into it \
+                     by its camera, since a target can't sample itself",
+                    id
+                );
+            }
+        }
+
+        target.filter(|_, layers| params.layers.intersects(layers))
+    }
+}
+
+/// Render targets for which skipping draws that sample them was already
+/// reported, so that it's only logged once.
+static WARNED_FEEDBACK_TARGETS: Lazy<AtomicRefCell<HashSet<RenderTargetId>>> =
+    Lazy::new(|| AtomicRefCell::new(HashSet::default()));
+
+/// A camera drawn in the current frame, either the main camera or one added
+/// with `add_camera`.
+struct CameraView {
+    uniform: CameraUniform,
+    viewport: Viewport,
+    layers: RenderLayers,
+    order: i32,
+}
+
+fn collect_camera_views(c: &WgpuRenderer) -> Vec<CameraView> {
+    let mut views = vec![];
+    // In pixel perfect mode the cameras draw into the low resolution.
+    let screen_size = {
+        let config = c.context.config.borrow();
+        render_resolution(vec2(config.width as f32, config.height as f32))
+    };
+
+    {
+        let main = main_camera();
+
+        views.push(CameraView {
+            uniform: c.camera_uniform,
+            viewport: main.viewport,
+            layers: main.layers,
+            order: 0,
+        });
+    }
+
+    for (_, camera) in cameras() {
+        if camera.enabled {
+            let mut uniform = c.camera_uniform;
+            uniform.update_view_proj_for_camera(&camera, screen_size);
+
+            views.push(CameraView {
+                uniform,
+                viewport: camera.viewport,
+                layers: camera.layers,
+                order: camera.order,
+            });
+        }
+    }
+
+    // The sort is stable, which keeps the main camera first among cameras
+    // of the same order.
+    views.sort_by_key(|view| view.order);
+    views
+}
+
+/// Draws the whole scene once through the camera whose uniform and viewport
+/// are currently set on the renderer.
+fn render_camera_view(
+    c: &mut WgpuRenderer,
+    is_first: &mut bool,
+    clear_color: Color,
+    mut frame: FrameQueues,
+    surface_view: &wgpu::TextureView,
+    sprite_shader_id: ShaderId,
+    error_shader_id: ShaderId,
+) {
+    // With bindless batching, consecutive groups which only differ in their
+    // texture are collected here and drawn together.
+    let mut bindless_batch: Option<BindlessBatch> = None;
+
+    // Stencil masks are drawn before everything else on the same z-index so
+    // that content masked by them can be drawn at the same z-index.
+    for (key, mut meshes) in frame.meshes.into_iter().sorted_by_key(|(k, _)| {
+        (k.z_index, get_clip_state(k.clip).stencil != StencilMode::Write)
+    }) {
+        let _span = span!("blend/shader/target group");
+
+        if bindless_batch.as_ref().is_some_and(|x| !x.accepts(&key)) {
+            flush_bindless_batch(
+                c,
+                is_first,
+                clear_color,
+                &mut bindless_batch,
+                surface_view,
+            );
+        }
+
+        while let Some(entry) = frame
+            .tilemap_layers
+            .first_entry()
+            .filter(|x| *x.key() <= key.z_index)
+        {
+            flush_bindless_batch(
+                c,
+                is_first,
+                clear_color,
+                &mut bindless_batch,
+                surface_view,
+            );
+
+            render_tilemap_chunks(
+                c,
+                *is_first,
+                clear_color,
+                entry.remove(),
+                surface_view,
+                sprite_shader_id,
+            );
+
+            *is_first = false;
+        }
+
+        // TODO: add this back later
+        if get_y_sort(key.z_index) {
+            meshes.sort_by_key(|mesh| {
+                OrderedFloat::<f32>(-(mesh.origin.y + mesh.y_sort_offset))
+            });
+        }
+
+        let instances = frame.instances.remove(&key);
+
+        if c.bindless.is_some() && BindlessBatch::can_batch(&key) {
+            perf_counter_inc("meshes", meshes.len() as u64);
+
+            bindless_batch
+                .get_or_insert_with(|| BindlessBatch::new(&key))
+                .push(
+                    key.texture_id,
+                    meshes,
+                    instances.map(|x| x.instances).unwrap_or_default(),
+                );
+
+            continue;
+        }
+
+        if !meshes.is_empty() {
+            perf_counter_inc("render passes", 1);
+            perf_counter_inc("meshes", meshes.len() as u64);
+
+            render_meshes(
+                c,
+                *is_first,
+                clear_color,
+                MeshDrawData {
+                    blend_mode: key.blend_mode,
+                    texture: key.texture_id,
+                    shader: key.shader,
+                    render_target: key.render_target,
+                    clip: key.clip,
+                    data: meshes,
+                },
+                surface_view,
+                sprite_shader_id,
+                error_shader_id,
+            );
+
+            *is_first = false;
+        }
+
+        // Sprites queued together with meshes of the same group are already
+        // queued as meshes (see `queue_sprite_instance`) to keep their order.
+        if let Some(instances) = instances {
+            perf_counter_inc("render passes", 1);
+            perf_counter_inc(
+                "sprite instances",
+                instances.instances.len() as u64,
+            );
+
+            render_sprite_instances(
+                c,
+                *is_first,
+                clear_color,
+                SpriteInstanceDrawData {
+                    blend_mode: key.blend_mode,
+                    texture: key.texture_id,
+                    render_target: key.render_target,
+                    clip: key.clip,
+                    data: instances.instances,
+                },
+                surface_view,
+            );
+
+            *is_first = false;
+        }
+    }
+
+    flush_bindless_batch(
+        c,
+        is_first,
+        clear_color,
+        &mut bindless_batch,
+        surface_view,
+    );
+
+    for (_, draws) in frame.tilemap_layers.into_iter() {
+        render_tilemap_chunks(
+            c,
+            *is_first,
+            clear_color,
+            draws,
+            surface_view,
+            sprite_shader_id,
+        );
+
+        *is_first = false;
     }
 
     {
         span_with_timing!("prepare_particles");
 
-        for (key, queue) in params.particle_queues.into_iter() {
+        for (key, queue) in frame.particles.into_iter() {
             render_particles(
                 c,
-                is_first,
+                *is_first,
                 ParticleDrawData {
                     blend_mode: key.blend_mode,
                     texture: key.texture_id,
+                    render_target: key.render_target,
+                    clip: key.clip,
                     data: queue,
                 },
-                params.clear_color,
+                clear_color,
                 surface_view,
                 sprite_shader_id,
             );
 
             perf_counter("particle draws", 1);
-            is_first = false;
+            *is_first = false;
         }
 
         // for (blend_mode, group) in
@@ -114,51 +716,17 @@ pub fn run_batched_render_passes(
         //                     texture: tex_handle,
         //                     data: vec![*draw],
         //                 },
-        //                 params.clear_color,
+        //                 clear_color,
         //                 surface_view,
         //                 sprite_shader_id,
         //             );
         //
         //             perf_counter("particle draws", 1);
-        //             is_first = false;
+        //             *is_first = false;
         //         }
         //     }
         // }
     }
-
-    if is_first {
-        render_meshes(
-            c,
-            is_first,
-            params.clear_color,
-            MeshDrawData {
-                blend_mode: BlendMode::Alpha,
-                texture: TextureHandle::from_path("1px"),
-                shader: ShaderInstanceId::default(),
-                render_target: RenderTargetId::default(),
-                data: Default::default(),
-            },
-            surface_view,
-            sprite_shader_id,
-            error_shader_id,
-        );
-
-        // MeshGroupKey {
-        //     z_index: 0,
-        //     blend_mode: BlendMode::Alpha,
-        //     texture_id: TextureHandle::from_path("1px"),
-        //     shader: None,
-        //     render_target: None,
-        // },
-        // RenderPassData {
-        //     z_index: 0,
-        //     blend_mode: BlendMode::Alpha,
-        //     texture: TextureHandle::from_path("1px"),
-        //     shader: None,
-        //     render_target: None,
-        //     data: SmallVec::new(),
-        // },
-    }
 }
 
 // TODO: Pass shader separately
@@ -175,6 +743,10 @@ pub fn render_meshes(
 
     let pipeline_name = ensure_pipeline_exists(c, &pass_data, sprite_shader_id);
 
+    let clip = get_clip_state(pass_data.clip);
+    let clear_stencil = take_stencil_clear(c, clip.stencil);
+    let target_size = render_target_size(c, pass_data.render_target);
+
     perf_counter_inc("batch-count", 1);
 
     let tex_handle = pass_data.texture;
@@ -204,21 +776,24 @@ pub fn render_meshes(
     let textures = c.textures.lock();
     let render_targets = c.render_targets.borrow();
 
+    let tex_bind_group = c.sprite_textures.get(
+        &c.context.device,
+        tex_handle,
+        &textures,
+        &render_targets,
+    );
+
     let mut encoder = c.context.device.simple_encoder("Mesh Render Encoder");
 
     {
         let clear_color = if is_first { Some(clear_color) } else { None };
 
-        let target_view = if pass_data.render_target.0 > 0 {
-            &render_targets
-                .get(&pass_data.render_target)
-                .expect("user render target must exist when used")
-                .view
-        } else if c.post_processing_effects.borrow().iter().any(|x| x.enabled) {
-            &c.first_pass_texture.texture.view
-        } else {
-            surface_view
-        };
+        let (target_view, depth_view) = render_target_views(
+            c,
+            &render_targets,
+            surface_view,
+            pass_data.render_target,
+        );
 
         let mut render_pass =
             encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
@@ -231,10 +806,12 @@ pub fn render_meshes(
                         store: wgpu::StoreOp::Store,
                     },
                 })],
-                depth_stencil_attachment: depth_stencil_attachment(
+                depth_stencil_attachment: clip_depth_stencil_attachment(
                     c.enable_z_buffer,
-                    &c.depth_texture.view,
+                    depth_view,
                     is_first,
+                    clip.stencil,
+                    clear_stencil,
                 ),
                 timestamp_writes: None,
                 occlusion_query_set: None,
@@ -259,6 +836,20 @@ pub fn render_meshes(
             }
         }
 
+        apply_clip_state(
+            &mut render_pass,
+            &clip,
+            target_size,
+            c.camera_view_proj,
+            c.camera_viewport,
+        );
+        apply_camera_viewport(
+            &mut render_pass,
+            c.camera_viewport,
+            pass_data.render_target,
+            target_size,
+        );
+
         render_pass.set_vertex_buffer(0, c.vertex_buffer.buffer.slice(..));
 
         if !all_indices.is_empty() {
@@ -268,23 +859,7 @@ pub fn render_meshes(
             );
         }
 
-        let tex_bind_group = match tex_handle {
-            TextureHandle::RenderTarget(render_target_id) => {
-                &render_targets.get(&render_target_id).unwrap().bind_group
-            }
-            _ => {
-                &textures
-                    .get(&tex_handle)
-                    .unwrap_or_else(|| {
-                        textures
-                            .get(&texture_id("error"))
-                            .expect("error texture must exist")
-                    })
-                    .bind_group
-            }
-        };
-
-        render_pass.set_bind_group(0, tex_bind_group, &[]);
+        render_pass.set_bind_group(0, &tex_bind_group, &[]);
         render_pass.set_bind_group(1, &c.camera_bind_group, &[]);
 
         match &mesh_pipeline {
@@ -314,19 +889,71 @@ pub fn render_particles(
 ) {
     let _span = span!("render_particles");
 
-    let target_view =
-        if c.post_processing_effects.borrow().iter().any(|x| x.enabled) {
-            &c.first_pass_texture.texture.view
-        } else {
-            surface_view
-        };
+    if get_sprite_instancing() {
+        let tex_size = ASSETS
+            .borrow()
+            .texture_image_map
+            .lock()
+            .get(&pass_data.texture)
+            .map(|image| vec2(image.width() as f32, image.height() as f32))
+            .unwrap_or(Vec2::ONE);
+
+        let instances = pass_data
+            .data
+            .iter()
+            .map(|draw| {
+                SpriteInstance::from_raw_params(
+                    draw.position,
+                    RawDrawParams {
+                        dest_size: Some(draw.size),
+                        rotation: draw.rotation,
+                        source_rect: draw.source_rect,
+                        ..Default::default()
+                    },
+                    tex_size.x,
+                    tex_size.y,
+                    draw.color,
+                    Vec2::ZERO,
+                )
+            })
+            .collect_vec();
 
+        render_sprite_instances(
+            c,
+            is_first,
+            clear_color,
+            SpriteInstanceDrawData {
+                blend_mode: pass_data.blend_mode,
+                texture: pass_data.texture,
+                render_target: pass_data.render_target,
+                clip: pass_data.clip,
+                data: instances,
+            },
+            surface_view,
+        );
+
+        return;
+    }
+
+    let clip = get_clip_state(pass_data.clip);
+    let clear_stencil = take_stencil_clear(c, clip.stencil);
+    let target_size = render_target_size(c, pass_data.render_target);
+    let format = render_target_format(c, pass_data.render_target);
+
+    let render_targets = c.render_targets.borrow();
     let textures = c.textures.lock();
 
-    let particle_pipeline = {
+    let tex_bind_group = c.sprite_textures.get(
+        &c.context.device,
+        pass_data.texture,
+        &textures,
+        &render_targets,
+    );
+
+    let pipeline_name = {
         let name = format!(
-            "Particle {:?} {:?}",
-            pass_data.blend_mode, c.enable_z_buffer
+            "Particle {:?} {:?} {:?} {:?}",
+            pass_data.blend_mode, c.enable_z_buffer, clip.stencil, format
         );
 
         c.pipelines.entry(name.clone()).or_insert_with(|| {
@@ -334,15 +961,18 @@ pub fn render_particles(
                 &name,
                 &c.context.device,
                 // c.config.format,
-                wgpu::TextureFormat::Rgba16Float,
-                &[&c.texture_layout, &c.camera_bind_group_layout],
+                format,
+                &[&c.sprite_textures.layout, &c.camera_bind_group_layout],
                 &[SpriteVertex::desc()],
                 &c.shaders.borrow().get(sprite_shader_id).unwrap().clone(),
                 pass_data.blend_mode,
                 c.enable_z_buffer,
+                clip.stencil,
             )
             .expect("particle pipeline creation failed")
-        })
+        });
+
+        name
     };
 
     let mut all_vertices: Vec<SpriteVertex> = vec![];
@@ -409,6 +1039,13 @@ pub fn render_particles(
     {
         let clear_color = if is_first { Some(clear_color) } else { None };
 
+        let (target_view, depth_view) = render_target_views(
+            c,
+            &render_targets,
+            surface_view,
+            pass_data.render_target,
+        );
+
         let mut render_pass =
             encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Particle Render Pass"),
@@ -430,18 +1067,34 @@ pub fn render_particles(
                 //         stencil_ops: None,
                 //     },
                 // ),
-                depth_stencil_attachment: depth_stencil_attachment(
+                depth_stencil_attachment: clip_depth_stencil_attachment(
                     c.enable_z_buffer,
-                    &c.depth_texture.view,
+                    depth_view,
                     is_first,
+                    clip.stencil,
+                    clear_stencil,
                 ),
                 timestamp_writes: None,
                 occlusion_query_set: None,
             });
 
-        render_pass.set_pipeline(particle_pipeline);
+        render_pass.set_pipeline(&c.pipelines[&pipeline_name]);
         render_pass.set_vertex_buffer(0, c.vertex_buffer.buffer.slice(..));
 
+        apply_clip_state(
+            &mut render_pass,
+            &clip,
+            target_size,
+            c.camera_view_proj,
+            c.camera_viewport,
+        );
+        apply_camera_viewport(
+            &mut render_pass,
+            c.camera_viewport,
+            pass_data.render_target,
+            target_size,
+        );
+
         if !all_indices.is_empty() {
             render_pass.set_index_buffer(
                 c.index_buffer.buffer.slice(..),
@@ -449,12 +1102,7 @@ pub fn render_particles(
             );
         }
 
-        let tex_bind_group = &textures
-            .get(&pass_data.texture)
-            .unwrap_or_else(|| textures.get(&texture_id("error")).unwrap())
-            .bind_group;
-
-        render_pass.set_bind_group(0, tex_bind_group, &[]);
+        render_pass.set_bind_group(0, &tex_bind_group, &[]);
         render_pass.set_bind_group(1, &c.camera_bind_group, &[]);
 
         if all_indices.is_empty() {
@@ -466,3 +1114,189 @@ pub fn render_particles(
 
     c.context.queue.submit(std::iter::once(encoder.finish()));
 }
+
+/// Draws sprites through the instanced path, uploading one `SpriteInstance`
+/// per sprite and letting the vertex shader expand it into a quad.
+pub fn render_sprite_instances(
+    c: &mut WgpuRenderer,
+    is_first: bool,
+    clear_color: Color,
+    pass_data: SpriteInstanceDrawData,
+    surface_view: &wgpu::TextureView,
+) {
+    let _span = span!("render_sprite_instances");
+
+    perf_counter_inc("batch-count", 1);
+
+    let clip = get_clip_state(pass_data.clip);
+    let clear_stencil = take_stencil_clear(c, clip.stencil);
+    let target_size = render_target_size(c, pass_data.render_target);
+    let format = render_target_format(c, pass_data.render_target);
+
+    let pipeline_name = format!(
+        "Sprite Instanced {:?} {:?} {:?} {:?}",
+        pass_data.blend_mode, c.enable_z_buffer, clip.stencil, format
+    );
+
+    if !c.pipelines.contains_key(&pipeline_name) {
+        let pipeline = create_render_pipeline_with_layout(
+            &pipeline_name,
+            &c.context.device,
+            format,
+            &[&c.sprite_textures.layout, &c.camera_bind_group_layout],
+            &[SpriteInstance::desc()],
+            c.shaders.borrow().get(c.sprite_instanced_shader_id).unwrap(),
+            pass_data.blend_mode,
+            c.enable_z_buffer,
+            clip.stencil,
+        )
+        .expect("instanced sprite pipeline creation failed");
+
+        c.pipelines.insert(pipeline_name.clone(), pipeline);
+    }
+
+    c.instance_buffer.ensure_size_and_copy(
+        &c.context.device,
+        &c.context.queue,
+        bytemuck::cast_slice(pass_data.data.as_slice()),
+    );
+
+    let textures = c.textures.lock();
+    let render_targets = c.render_targets.borrow();
+
+    let tex_bind_group = c.sprite_textures.get(
+        &c.context.device,
+        pass_data.texture,
+        &textures,
+        &render_targets,
+    );
+
+    let mut encoder =
+        c.context.device.simple_encoder("Sprite Instance Render Encoder");
+
+    {
+        let clear_color = if is_first { Some(clear_color) } else { None };
+
+        let (target_view, depth_view) = render_target_views(
+            c,
+            &render_targets,
+            surface_view,
+            pass_data.render_target,
+        );
+
+        let mut render_pass =
+            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Sprite Instance Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: target_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: color_to_clear_op(clear_color),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: clip_depth_stencil_attachment(
+                    c.enable_z_buffer,
+                    depth_view,
+                    is_first,
+                    clip.stencil,
+                    clear_stencil,
+                ),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+        render_pass.set_pipeline(&c.pipelines[&pipeline_name]);
+        render_pass.set_vertex_buffer(0, c.instance_buffer.buffer.slice(..));
+
+        apply_clip_state(
+            &mut render_pass,
+            &clip,
+            target_size,
+            c.camera_view_proj,
+            c.camera_viewport,
+        );
+        apply_camera_viewport(
+            &mut render_pass,
+            c.camera_viewport,
+            pass_data.render_target,
+            target_size,
+        );
+
+        render_pass.set_bind_group(0, &tex_bind_group, &[]);
+        render_pass.set_bind_group(1, &c.camera_bind_group, &[]);
+
+        render_pass.draw(0..6, 0..pass_data.data.len() as u32);
+    }
+
+    c.context.queue.submit(std::iter::once(encoder.finish()));
+}
+
+#[test]
+fn test_take_render_target() {
+    let id = RenderTargetId(1);
+
+    let key = |texture_id, shader, render_target| {
+        MeshGroupKey {
+            z_index: 0,
+            blend_mode: BlendMode::Alpha,
+            texture_id,
+            shader,
+            render_target,
+            clip: ClipId::default(),
+            layers: RenderLayers::DEFAULT,
+        }
+    };
+
+    let sprite = TextureHandle::from_path("sprite");
+    let screen = RenderTargetId::default();
+
+    let routed = key(sprite, ShaderInstanceId(0), id);
+    let plain = key(sprite, ShaderInstanceId(0), screen);
+    let minimap =
+        key(TextureHandle::RenderTarget(id), ShaderInstanceId(0), screen);
+    let uniform = key(sprite, ShaderInstanceId(7), screen);
+
+    let frame = || {
+        FrameQueues {
+            meshes: [routed, plain, minimap, uniform]
+                .into_iter()
+                .map(|key| (key, vec![Mesh::default()]))
+                .collect(),
+            ..Default::default()
+        }
+    };
+
+    let samples_target = |shader: ShaderInstanceId| shader.0 == 7;
+
+    // Without a camera only the draws routed into the target are taken.
+    let mut rest = frame();
+    let target = rest.take_render_target(
+        id,
+        &RenderTargetParams::new("target", uvec2(16, 16)),
+        samples_target,
+    );
+
+    assert_eq!(target.meshes.keys().copied().collect_vec(), vec![routed]);
+    assert_eq!(rest.meshes.len(), 3);
+    assert!(!rest.meshes.contains_key(&routed));
+
+    // With a camera the screen draws are copied, except for the ones
+    // sampling the target.
+    let mut rest = frame();
+    let target = rest.take_render_target(
+        id,
+        &RenderTargetParams::new("target", uvec2(16, 16))
+            .with_camera(Camera::new(Vec2::ZERO, 10.0, Viewport::FULL)),
+        samples_target,
+    );
+
+    // The copy of `plain` shares its key with `routed`.
+    assert_eq!(target.meshes.keys().copied().collect_vec(), vec![routed]);
+    assert_eq!(target.meshes[&routed].len(), 2);
+
+    // Everything not routed into the target is still drawn on the screen.
+    assert_eq!(rest.meshes.keys().copied().collect_vec(), vec![
+        plain, uniform, minimap
+    ]);
+}
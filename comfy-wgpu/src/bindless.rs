@@ -0,0 +1,503 @@
+use crate::*;
+
+/// Number of textures bound at once by a single bindless batch.
+pub const MAX_BINDLESS_TEXTURES: usize = 8;
+
+pub fn bindless_features() -> wgpu::Features {
+    wgpu::Features::TEXTURE_BINDING_ARRAY |
+        wgpu::Features::SAMPLED_TEXTURE_AND_STORAGE_BUFFER_ARRAY_NON_UNIFORM_INDEXING
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct BindlessSpriteVertex {
+    pub position: [f32; 3],
+    pub tex_coords: [f32; 2],
+    pub color: [f32; 4],
+    pub texture_index: u32,
+}
+
+const BINDLESS_ATTRIBS: [wgpu::VertexAttribute; 4] = wgpu::vertex_attr_array![
+    0 => Float32x3,
+    1 => Float32x2,
+    2 => Float32x4,
+    3 => Uint32,
+];
+
+impl Vertex for BindlessSpriteVertex {
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<BindlessSpriteVertex>()
+                as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &BINDLESS_ATTRIBS,
+        }
+    }
+}
+
+/// Meshes and sprite instances of several `MeshGroupKey`s which only differ
+/// in their texture, drawn with a single bind group.
+pub struct BindlessBatch {
+    pub z_index: i32,
+    pub blend_mode: BlendMode,
+    pub render_target: RenderTargetId,
+    pub textures: Vec<TextureHandle>,
+    pub meshes: Vec<(u32, Mesh)>,
+    pub instances: Vec<SpriteInstance>,
+}
+
+impl BindlessBatch {
+    pub fn new(key: &MeshGroupKey) -> Self {
+        Self {
+            z_index: key.z_index,
+            blend_mode: key.blend_mode,
+            render_target: key.render_target,
+            textures: Vec::new(),
+            meshes: Vec::new(),
+            instances: Vec::new(),
+        }
+    }
+
+    /// Whether the group can be drawn in a bindless batch at all. Custom
+    /// shaders, clipping and normal maps need their own bind groups.
+    pub fn can_batch(key: &MeshGroupKey) -> bool {
+        key.shader == ShaderInstanceId::default() &&
+            key.clip == ClipId::default() &&
+            normal_map(key.texture_id).is_none()
+    }
+
+    /// Returns `true` if the group can be added to this batch, i.e. it only
+    /// differs in the texture and there's still room for it. Groups which
+    /// aren't accepted are drawn after the batch is flushed, which keeps the
+    /// draw order.
+    pub fn accepts(&self, key: &MeshGroupKey) -> bool {
+        Self::can_batch(key) &&
+            key.z_index == self.z_index &&
+            key.blend_mode == self.blend_mode &&
+            key.render_target == self.render_target &&
+            (self.textures.len() < MAX_BINDLESS_TEXTURES ||
+                self.textures.contains(&key.texture_id))
+    }
+
+    pub fn push(
+        &mut self,
+        texture: TextureHandle,
+        meshes: Vec<Mesh>,
+        instances: Vec<SpriteInstance>,
+    ) {
+        let index = match self.textures.iter().position(|x| *x == texture) {
+            Some(index) => index,
+            None => {
+                self.textures.push(texture);
+                self.textures.len() - 1
+            }
+        } as u32;
+
+        self.meshes.extend(meshes.into_iter().map(|mesh| (index, mesh)));
+        self.instances.extend(instances.into_iter().map(|mut instance| {
+            instance.texture_index = index;
+            instance
+        }));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.meshes.is_empty() && self.instances.is_empty()
+    }
+}
+
+pub struct BindlessBatching {
+    pub layout: wgpu::BindGroupLayout,
+    pub mesh_shader_id: ShaderId,
+    pub instanced_shader_id: ShaderId,
+}
+
+impl BindlessBatching {
+    /// Returns `None` if the device wasn't created with `bindless_features`.
+    pub fn new(
+        device: &wgpu::Device,
+        shaders: &mut ShaderMap,
+        fragment_source: &str,
+    ) -> Option<Self> {
+        if !device.features().contains(bindless_features()) {
+            return None;
+        }
+
+        info!("Using bindless batching");
+
+        let count = NonZeroU32::new(MAX_BINDLESS_TEXTURES as u32);
+
+        let layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Bindless Texture Bind Group Layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Float {
+                                filterable: true,
+                            },
+                        },
+                        count,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(
+                            wgpu::SamplerBindingType::Filtering,
+                        ),
+                        count,
+                    },
+                ],
+            });
+
+        let mesh_shader_id = create_shader(
+            shaders,
+            "sprite-bindless",
+            &format!(
+                "{}{}{}",
+                camera_bind_group_prefix(),
+                SPRITE_BINDLESS_PREFIX,
+                fragment_source
+            ),
+            HashMap::new(),
+        )
+        .ok()?;
+
+        let instanced_shader_id = create_shader(
+            shaders,
+            "sprite-bindless-instanced",
+            &format!(
+                "{}{}{}",
+                camera_bind_group_prefix(),
+                SPRITE_BINDLESS_INSTANCED_PREFIX,
+                fragment_source
+            ),
+            HashMap::new(),
+        )
+        .ok()?;
+
+        Some(Self { layout, mesh_shader_id, instanced_shader_id })
+    }
+
+    /// Binds all textures of the batch. Every slot of the array has to be
+    /// filled, unused ones get the error texture.
+    pub fn create_bind_group(
+        &self,
+        device: &wgpu::Device,
+        textures: &TextureMap,
+        render_targets: &RenderTargetMap,
+        handles: &[TextureHandle],
+    ) -> wgpu::BindGroup {
+        let error_texture = textures
+            .get(&texture_id("error"))
+            .expect("error texture must exist");
+
+        let (views, samplers): (Vec<_>, Vec<_>) = handles
+            .iter()
+            .map(|handle| {
+                match handle {
+                    TextureHandle::RenderTarget(id) => {
+                        let target = render_targets.get(id).unwrap();
+                        (&target.view, &target.sampler)
+                    }
+                    _ => {
+                        let texture = &textures
+                            .get(handle)
+                            .unwrap_or(error_texture)
+                            .texture;
+                        (&texture.view, &texture.sampler)
+                    }
+                }
+            })
+            .chain(std::iter::repeat((
+                &error_texture.texture.view,
+                &error_texture.texture.sampler,
+            )))
+            .take(MAX_BINDLESS_TEXTURES)
+            .unzip();
+
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Bindless Texture Bind Group"),
+            layout: &self.layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureViewArray(&views),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::SamplerArray(&samplers),
+                },
+            ],
+        })
+    }
+}
+
+fn ensure_bindless_pipeline(
+    c: &mut WgpuRenderer,
+    name: &str,
+    vertex_layout: wgpu::VertexBufferLayout,
+    shader_id: ShaderId,
+    blend_mode: BlendMode,
+    format: wgpu::TextureFormat,
+) {
+    if c.pipelines.contains_key(name) {
+        return;
+    }
+
+    let bindless = c.bindless.as_ref().unwrap();
+
+    let pipeline = create_render_pipeline_with_layout(
+        name,
+        &c.context.device,
+        format,
+        &[&bindless.layout, &c.camera_bind_group_layout],
+        &[vertex_layout],
+        c.shaders.borrow().get(shader_id).unwrap(),
+        blend_mode,
+        c.enable_z_buffer,
+        StencilMode::None,
+    )
+    .expect("bindless pipeline creation failed");
+
+    c.pipelines.insert(name.to_string(), pipeline);
+}
+
+/// Renders the pending batch, if any, leaving `None` in its place.
+pub fn flush_bindless_batch(
+    c: &mut WgpuRenderer,
+    is_first: &mut bool,
+    clear_color: Color,
+    batch: &mut Option<BindlessBatch>,
+    surface_view: &wgpu::TextureView,
+) {
+    if let Some(batch) = batch.take() {
+        if !batch.is_empty() {
+            render_bindless_batch(
+                c,
+                *is_first,
+                clear_color,
+                batch,
+                surface_view,
+            );
+            *is_first = false;
+        }
+    }
+}
+
+/// Draws a whole bindless batch in a single render pass, meshes first and
+/// sprite instances after them.
+pub fn render_bindless_batch(
+    c: &mut WgpuRenderer,
+    is_first: bool,
+    clear_color: Color,
+    mut batch: BindlessBatch,
+    surface_view: &wgpu::TextureView,
+) {
+    let _span = span!("render_bindless_batch");
+
+    perf_counter_inc("batch-count", 1);
+    perf_counter_inc("bindless textures", batch.textures.len() as u64);
+
+    if get_y_sort(batch.z_index) {
+        batch.meshes.sort_by_key(|(_, mesh)| {
+            OrderedFloat::<f32>(-(mesh.origin.y + mesh.y_sort_offset))
+        });
+    }
+
+    let (mesh_shader_id, instanced_shader_id) = {
+        let bindless = c.bindless.as_ref().unwrap();
+        (bindless.mesh_shader_id, bindless.instanced_shader_id)
+    };
+
+    let format = render_target_format(c, batch.render_target);
+
+    let mesh_pipeline_name = format!(
+        "Bindless Mesh {:?} {:?} {:?}",
+        batch.blend_mode, c.enable_z_buffer, format
+    );
+    let instanced_pipeline_name = format!(
+        "Bindless Instanced {:?} {:?} {:?}",
+        batch.blend_mode, c.enable_z_buffer, format
+    );
+
+    ensure_bindless_pipeline(
+        c,
+        &mesh_pipeline_name,
+        BindlessSpriteVertex::desc(),
+        mesh_shader_id,
+        batch.blend_mode,
+        format,
+    );
+    ensure_bindless_pipeline(
+        c,
+        &instanced_pipeline_name,
+        SpriteInstance::desc(),
+        instanced_shader_id,
+        batch.blend_mode,
+        format,
+    );
+
+    let target_size = render_target_size(c, batch.render_target);
+
+    let mut all_vertices: Vec<BindlessSpriteVertex> = vec![];
+    let mut all_indices: Vec<u32> = vec![];
+
+    for (texture_index, mesh) in batch.meshes.iter() {
+        let offset = all_vertices.len() as u32;
+
+        all_vertices.extend(mesh.vertices.iter().map(|v| {
+            BindlessSpriteVertex {
+                position: v.position,
+                tex_coords: v.tex_coords,
+                color: v.color,
+                texture_index: *texture_index,
+            }
+        }));
+        all_indices.extend(mesh.indices.iter().map(|x| *x + offset));
+    }
+
+    c.vertex_buffer.ensure_size_and_copy(
+        &c.context.device,
+        &c.context.queue,
+        bytemuck::cast_slice(all_vertices.as_slice()),
+    );
+
+    c.index_buffer.ensure_size_and_copy(
+        &c.context.device,
+        &c.context.queue,
+        bytemuck::cast_slice(all_indices.as_slice()),
+    );
+
+    c.instance_buffer.ensure_size_and_copy(
+        &c.context.device,
+        &c.context.queue,
+        bytemuck::cast_slice(batch.instances.as_slice()),
+    );
+
+    let textures = c.textures.lock();
+    let render_targets = c.render_targets.borrow();
+
+    let bind_group = c.bindless.as_ref().unwrap().create_bind_group(
+        &c.context.device,
+        &textures,
+        &render_targets,
+        &batch.textures,
+    );
+
+    let mut encoder =
+        c.context.device.simple_encoder("Bindless Batch Render Encoder");
+
+    {
+        let clear_color = if is_first { Some(clear_color) } else { None };
+
+        let (target_view, depth_view) = render_target_views(
+            c,
+            &render_targets,
+            surface_view,
+            batch.render_target,
+        );
+
+        let mut render_pass =
+            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Bindless Batch Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: target_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: color_to_clear_op(clear_color),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: depth_stencil_attachment(
+                    c.enable_z_buffer,
+                    depth_view,
+                    is_first,
+                ),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+        apply_camera_viewport(
+            &mut render_pass,
+            c.camera_viewport,
+            batch.render_target,
+            target_size,
+        );
+
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        render_pass.set_bind_group(1, &c.camera_bind_group, &[]);
+
+        if !all_indices.is_empty() {
+            render_pass.set_pipeline(&c.pipelines[&mesh_pipeline_name]);
+            render_pass.set_vertex_buffer(0, c.vertex_buffer.buffer.slice(..));
+            render_pass.set_index_buffer(
+                c.index_buffer.buffer.slice(..),
+                wgpu::IndexFormat::Uint32,
+            );
+            render_pass.draw_indexed(0..all_indices.len() as u32, 0, 0..1);
+        } else if !all_vertices.is_empty() {
+            render_pass.set_pipeline(&c.pipelines[&mesh_pipeline_name]);
+            render_pass.set_vertex_buffer(0, c.vertex_buffer.buffer.slice(..));
+            render_pass.draw(0..all_vertices.len() as u32, 0..1);
+        }
+
+        if !batch.instances.is_empty() {
+            render_pass.set_pipeline(&c.pipelines[&instanced_pipeline_name]);
+            render_pass
+                .set_vertex_buffer(0, c.instance_buffer.buffer.slice(..));
+            render_pass.draw(0..6, 0..batch.instances.len() as u32);
+        }
+    }
+
+    c.context.queue.submit(std::iter::once(encoder.finish()));
+}
+
+#[test]
+fn test_bindless_batch_order_with_normal_maps() {
+    let plain_a = TextureHandle::from_path("bindless-order-a");
+    let lit = TextureHandle::from_path("bindless-order-lit");
+    let plain_b = TextureHandle::from_path("bindless-order-b");
+
+    set_normal_map(lit, Some(TextureHandle::from_path("bindless-order-lit_n")));
+
+    let key = |texture_id| {
+        MeshGroupKey {
+            z_index: 0,
+            blend_mode: BlendMode::Alpha,
+            texture_id,
+            shader: ShaderInstanceId::default(),
+            render_target: RenderTargetId::default(),
+            clip: ClipId::default(),
+            layers: RenderLayers::DEFAULT,
+        }
+    };
+
+    // Same as `render_camera_view`, the batch is flushed before every group
+    // it doesn't accept.
+    let mut batch: Option<BindlessBatch> = None;
+    let mut drawn = vec![];
+
+    for key in [key(plain_a), key(lit), key(plain_b)] {
+        if batch.as_ref().is_some_and(|x| !x.accepts(&key)) {
+            drawn.extend(batch.take().unwrap().textures);
+        }
+
+        if BindlessBatch::can_batch(&key) {
+            batch.get_or_insert_with(|| BindlessBatch::new(&key)).push(
+                key.texture_id,
+                vec![],
+                vec![],
+            );
+        } else {
+            drawn.push(key.texture_id);
+        }
+    }
+
+    drawn.extend(batch.take().unwrap().textures);
+
+    assert_eq!(drawn, vec![plain_a, lit, plain_b]);
+}
@@ -59,6 +59,8 @@ impl Bloom {
                 "bloom-threshold"
             );
 
+            let source_hash = default_hash(&shader.source);
+
             PostProcessingEffect {
                 id: shader.id,
                 name: "Bloom Threshold".into(),
@@ -77,6 +79,8 @@ impl Bloom {
                     shader,
                     wgpu::BlendState::REPLACE,
                 ),
+                uniforms: Default::default(),
+                source_hash,
             }
         };
 
@@ -241,6 +245,7 @@ impl Bloom {
             &self.threshold.pipeline,
             first_pass_bind_group,
             &self.lighting_params,
+            None,
             &self.threshold.render_texture.view,
             true,
             None,
@@ -400,6 +405,7 @@ impl Bloom {
                     &self.mip_blur_pipeline,
                     &mip_bind_group,
                     &self.lighting_params,
+                    None,
                     &self.blur_texture.texture.view,
                     i == 0,
                     Some(blend),
@@ -453,6 +459,7 @@ impl Bloom {
                 &self.pingpong[0].bind_group
             },
             &self.lighting_params,
+            None,
             output_view,
             false,
             Some(params.bloom_lerp as f64),
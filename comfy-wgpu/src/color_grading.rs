@@ -0,0 +1,189 @@
+use crate::*;
+
+pub const LUT_SHADER_SRC: &str =
+    include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/shaders/lut.wgsl"));
+
+/// Input domains of the current and previous LUT, `LutDomains` in lut.wgsl.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct LutDomainsUniform {
+    current_min: [f32; 4],
+    current_max: [f32; 4],
+    previous_min: [f32; 4],
+    previous_max: [f32; 4],
+}
+
+/// GPU side of `color_grading()`, the uploaded LUTs and the layout of the
+/// bind group the final post processing pass samples them with.
+pub struct ColorGradingResources {
+    pub luts: HashMap<ColorLutHandle, Texture>,
+    /// Input domain of each uploaded LUT, see `ColorLut::domain_min`.
+    pub domains: HashMap<ColorLutHandle, (Vec3, Vec3)>,
+    /// Used in place of a missing LUT, e.g. when fading in from no grading.
+    pub identity: Texture,
+    pub layout: wgpu::BindGroupLayout,
+    domains_buffer: wgpu::Buffer,
+}
+
+impl ColorGradingResources {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let texture_entry = |binding| {
+            wgpu::BindGroupLayoutEntry {
+                binding,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D3,
+                    sample_type: wgpu::TextureSampleType::Float {
+                        filterable: true,
+                    },
+                },
+                count: None,
+            }
+        };
+
+        let layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Color Grading Bind Group Layout"),
+                entries: &[
+                    texture_entry(0),
+                    texture_entry(1),
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(
+                            wgpu::SamplerBindingType::Filtering,
+                        ),
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+
+        let domains_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Color Grading Domains UB"),
+            size: std::mem::size_of::<LutDomainsUniform>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let identity = Texture::from_color_lut(
+            device,
+            queue,
+            &ColorLut::identity(2),
+            Some("Identity LUT"),
+        );
+
+        Self {
+            luts: HashMap::new(),
+            domains: HashMap::new(),
+            identity,
+            layout,
+            domains_buffer,
+        }
+    }
+
+    /// Uploads LUTs loaded with `load_color_lut` since the last frame.
+    pub fn upload_new_luts(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) {
+        for (handle, lut) in consume_color_lut_creations() {
+            let label = format!("Color LUT {}", handle.0);
+
+            self.domains.insert(handle, (lut.domain_min, lut.domain_max));
+            self.luts.insert(
+                handle,
+                Texture::from_color_lut(device, queue, &lut, Some(&label)),
+            );
+        }
+    }
+
+    fn lut(&self, handle: Option<ColorLutHandle>) -> &Texture {
+        handle.and_then(|x| self.luts.get(&x)).unwrap_or(&self.identity)
+    }
+
+    fn domain(&self, handle: Option<ColorLutHandle>) -> (Vec3, Vec3) {
+        handle
+            .and_then(|x| self.domains.get(&x).copied())
+            .unwrap_or((Vec3::ZERO, Vec3::ONE))
+    }
+
+    pub fn bind_group(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        state: &ColorGradingState,
+    ) -> wgpu::BindGroup {
+        let (current_min, current_max) = self.domain(state.current);
+        let (previous_min, previous_max) = self.domain(state.previous);
+
+        queue.write_buffer(
+            &self.domains_buffer,
+            0,
+            bytemuck::bytes_of(&LutDomainsUniform {
+                current_min: current_min.extend(0.0).to_array(),
+                current_max: current_max.extend(0.0).to_array(),
+                previous_min: previous_min.extend(0.0).to_array(),
+                previous_max: previous_max.extend(0.0).to_array(),
+            }),
+        );
+
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Color Grading Bind Group"),
+            layout: &self.layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(
+                        &self.lut(state.current).view,
+                    ),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(
+                        &self.lut(state.previous).view,
+                    ),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(
+                        &self.identity.sampler,
+                    ),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: self.domains_buffer.as_entire_binding(),
+                },
+            ],
+        })
+    }
+}
+
+/// Creates one of the shaders of the final post processing pass, which have
+/// the LUT bindings and `apply_color_grading` available.
+pub fn create_color_grading_shader(
+    shaders: &mut ShaderMap,
+    name: &str,
+    fragment_source: &str,
+) -> Shader {
+    let source = post_process_shader_from_fragment(&format!(
+        "{}\n{}",
+        LUT_SHADER_SRC, fragment_source
+    ));
+
+    let shader_id = create_shader(shaders, name, &source, HashMap::new())
+        .expect("color grading shader must be valid");
+
+    shaders.get(shader_id).unwrap().clone()
+}
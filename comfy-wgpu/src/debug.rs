@@ -50,6 +50,7 @@ pub fn render_debug(
                 shaders.get(debug_shader_id).unwrap(),
                 BlendMode::Alpha,
                 enable_z_buffer,
+                StencilMode::None,
             )
             .expect("debug pipeline creation failed")
         });
@@ -56,10 +56,24 @@ pub async fn create_graphics_context(
         ..wgpu::Limits::downlevel_webgl2_defaults()
     };
 
+    let required_features = if game_config().bindless_batching {
+        if adapter.features().contains(bindless_features()) {
+            bindless_features()
+        } else {
+            warn!(
+                "Bindless batching is not supported by the adapter, falling \
+                 back to per texture batching"
+            );
+            wgpu::Features::empty()
+        }
+    } else {
+        wgpu::Features::empty()
+    };
+
     let (device, queue) = adapter
         .request_device(
             &wgpu::DeviceDescriptor {
-                required_features: wgpu::Features::empty(),
+                required_features,
                 required_limits: limits,
                 label: None,
             },
@@ -68,6 +82,8 @@ pub async fn create_graphics_context(
         .await
         .expect("failed to create wgpu adapter");
 
+    detect_storage_lights(&adapter, &device);
+
     #[cfg(fature = "ci-release")]
     device.on_uncaptured_error(Box::new(|err| {
         error!("WGPU ERROR: {:?}", err);
@@ -180,6 +196,9 @@ pub async fn create_graphics_context(
     let queue = Arc::new(queue);
     let texture_layout = Arc::new(texture_bind_group_layout);
 
+    // Used for textures loaded with `TextureSettings::mipmaps`.
+    let mipmap_generators = Arc::new(MipmapGenerators::default());
+
     let texture_creator = Arc::new(AtomicRefCell::new(WgpuTextureCreator {
         textures: textures.clone(),
         layout: texture_layout.clone(),
@@ -197,5 +216,6 @@ pub async fn create_graphics_context(
         config: Arc::new(AtomicRefCell::new(config)),
         texture_creator,
         textures,
+        mipmap_generators,
     }
 }
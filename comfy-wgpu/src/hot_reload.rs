@@ -20,25 +20,39 @@ macro_rules! reloadable_shader_source {
 pub fn watch_shader_path(
     path: &str,
     shader_id: ShaderId,
+) -> notify::Result<()> {
+    watch_shader_path_with(path, shader_id, sprite_shader_from_fragment)
+}
+
+/// Same as `watch_shader_path`, but the reloaded file is turned into a full
+/// shader with `build_source`, e.g. `post_process_shader_from_fragment`.
+pub fn watch_shader_path_with(
+    path: &str,
+    shader_id: ShaderId,
+    build_source: BuildShaderSource,
 ) -> notify::Result<()> {
     let path = Path::new(path).canonicalize().unwrap().to_path_buf();
 
     let mut hot_reload = HOT_RELOAD.lock();
     hot_reload.watch_path(path.as_path())?;
-    hot_reload.shader_paths.insert(path, shader_id);
+    hot_reload.shader_paths.insert(path, (shader_id, build_source));
 
     Ok(())
 }
 
 /// Internal use only, checks for shader hot reloads and reloads them if needed.
-pub fn maybe_reload_shaders(shaders: &mut ShaderMap) {
-    HOT_RELOAD.lock().maybe_reload_shaders(shaders);
+/// Returns `true` if any shader was reloaded.
+pub fn maybe_reload_shaders(shaders: &mut ShaderMap) -> bool {
+    HOT_RELOAD.lock().maybe_reload_shaders(shaders)
 }
 
+/// Turns the contents of a reloaded file into a full shader source.
+pub type BuildShaderSource = fn(&str) -> String;
+
 pub struct HotReload {
     rx: Receiver<Result<Event, notify::Error>>,
     watcher: notify::RecommendedWatcher,
-    pub shader_paths: HashMap<PathBuf, ShaderId>,
+    pub shader_paths: HashMap<PathBuf, (ShaderId, BuildShaderSource)>,
 }
 
 impl HotReload {
@@ -94,11 +108,11 @@ impl HotReload {
     fn reload_path_bufs(&self, shaders: &mut ShaderMap, paths: &[PathBuf]) {
         for path in paths.iter().filter(|x| !x.to_string_lossy().ends_with('~'))
         {
-            if let Some(shader_id) = self.shader_paths.get(path) {
+            if let Some((shader_id, build_source)) = self.shader_paths.get(path)
+            {
                 match std::fs::read_to_string(path) {
                     Ok(source) => {
-                        let fragment_source =
-                            &sprite_shader_from_fragment(&source);
+                        let fragment_source = &build_source(&source);
 
                         checked_update_shader(
                             shaders,
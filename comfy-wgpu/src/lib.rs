@@ -9,40 +9,58 @@ pub use winit::event::{ElementState, Event, MouseScrollDelta, WindowEvent};
 pub use fontdue;
 
 mod batching;
+mod bindless;
 mod blood_canvas;
 mod bloom;
+mod color_grading;
 mod debug;
 mod device;
 mod egui_integration;
 #[cfg(not(target_arch = "wasm32"))]
 mod hot_reload;
 mod instance;
+mod light_buffers;
+mod light_cookies;
+mod normal_maps;
 mod pipelines;
+mod pixel_perfect;
+mod post_fx;
 mod post_processing;
 mod render_pass;
 mod renderer;
 mod screenshot;
+mod shadows;
 mod text;
 mod texture;
+mod tilemap;
 mod utils;
 mod y_sort;
 
 pub use crate::batching::*;
+pub use crate::bindless::*;
 pub use crate::blood_canvas::*;
 pub use crate::bloom::*;
+pub use crate::color_grading::*;
 pub use crate::debug::*;
 pub use crate::device::*;
 pub use crate::egui_integration::*;
 #[cfg(not(target_arch = "wasm32"))]
 pub use crate::hot_reload::*;
 pub use crate::instance::*;
+pub use crate::light_buffers::*;
+pub use crate::light_cookies::*;
+pub use crate::normal_maps::*;
 pub use crate::pipelines::*;
+pub use crate::pixel_perfect::*;
+pub use crate::post_fx::*;
 pub use crate::post_processing::*;
 pub use crate::render_pass::*;
 pub use crate::renderer::*;
 pub use crate::screenshot::*;
+pub use crate::shadows::*;
 pub use crate::text::*;
 pub use crate::texture::*;
+pub use crate::tilemap::*;
 pub use crate::utils::*;
 pub use crate::y_sort::*;
 
@@ -70,6 +88,26 @@ impl Vertex for SpriteVertex {
     }
 }
 
+const INSTANCE_ATTRIBS: [wgpu::VertexAttribute; 6] = wgpu::vertex_attr_array![
+    0 => Float32x3,
+    1 => Float32,
+    2 => Float32x2,
+    3 => Float32x4,
+    4 => Float32x4,
+    5 => Uint32,
+];
+
+impl Vertex for SpriteInstance {
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<SpriteInstance>()
+                as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &INSTANCE_ATTRIBS,
+        }
+    }
+}
+
 pub enum BufferType {
     Vertex,
     Index,
@@ -98,7 +136,7 @@ impl BufferType {
                 wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ
             }
             BufferType::Storage => {
-                todo!()
+                wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST
             }
         }
     }
@@ -333,12 +371,27 @@ impl CameraUniform {
         }
     }
 
+    pub fn view_proj(&self) -> Mat4 {
+        Mat4::from_cols_array_2d(&self.view_proj)
+    }
+
     pub fn update_view_proj(&mut self, camera: &MainCamera) {
         // Using Vec4 because of uniform 16 byte spacing requirement.
         self.view_position = camera.center.extend(0.0).extend(1.0).into();
         self.view_proj =
             camera.build_view_projection_matrix().to_cols_array_2d();
     }
+
+    pub fn update_view_proj_for_camera(
+        &mut self,
+        camera: &Camera,
+        target_size: Vec2,
+    ) {
+        self.view_position = camera.center.extend(0.0).extend(1.0).into();
+        self.view_proj = camera
+            .build_view_projection_matrix_in(target_size)
+            .to_cols_array_2d();
+    }
 }
 
 pub fn create_render_pipeline(
@@ -350,6 +403,7 @@ pub fn create_render_pipeline(
     vertex_layouts: &[wgpu::VertexBufferLayout],
     shader: &Shader,
     blend_mode: BlendMode,
+    stencil: StencilMode,
 ) -> Result<wgpu::RenderPipeline> {
     // let module = naga::front::wgsl::parse_str(&shader.source)?;
     //
@@ -360,44 +414,32 @@ pub fn create_render_pipeline(
     //
     // validator.validate(&module)?;
 
+    let premultiplied;
+
+    let shader = if blend_mode_premultiplies(blend_mode) {
+        let source =
+            premultiply_fragment_output(&shader.source).map_err(|err| {
+                anyhow::anyhow!(
+                    "Shader {} can't be premultiplied for {:?}: {}",
+                    shader.name,
+                    blend_mode,
+                    err
+                )
+            })?;
+
+        premultiplied = Shader { source, ..shader.clone() };
+        &premultiplied
+    } else {
+        shader
+    };
+
     let wgpu_shader = shader_to_wgpu(shader);
 
     let shader = device.create_shader_module(wgpu_shader);
 
     info!("CREATED SHADER, GOT {:?}", shader);
 
-    let blend_state = match blend_mode {
-        BlendMode::Alpha => Some(wgpu::BlendState::ALPHA_BLENDING),
-        // BlendMode::Additive => Some(wgpu::BlendState::ALPHA_BLENDING),
-        // BlendMode::Additive => Some(wgpu::BlendState {
-        //     color: wgpu::BlendComponent {
-        //         src_factor: wgpu::BlendFactor::SrcAlpha,
-        //         dst_factor: wgpu::BlendFactor::DstAlpha,
-        //         operation: wgpu::BlendOperation::Add,
-        //     },
-        //     alpha: wgpu::BlendComponent {
-        //         src_factor: wgpu::BlendFactor::One,
-        //         dst_factor: wgpu::BlendFactor::One,
-        //         operation: wgpu::BlendOperation::Add,
-        //     }
-        // }),
-        BlendMode::Additive => {
-            Some(wgpu::BlendState {
-                color: wgpu::BlendComponent {
-                    src_factor: wgpu::BlendFactor::One,
-                    dst_factor: wgpu::BlendFactor::One,
-                    operation: wgpu::BlendOperation::Add,
-                },
-                // alpha: wgpu::BlendComponent::REPLACE,
-                alpha: wgpu::BlendComponent {
-                    src_factor: wgpu::BlendFactor::One,
-                    dst_factor: wgpu::BlendFactor::One,
-                    operation: wgpu::BlendOperation::Add,
-                },
-            })
-        }
-        BlendMode::None => Some(wgpu::BlendState::ALPHA_BLENDING),
-    };
+    let blend_state = Some(blend_mode_to_wgpu(blend_mode));
 
     // let blend_state = Some(wgpu::BlendState {
     //     color: wgpu::BlendComponent {
@@ -428,7 +470,11 @@ pub fn create_render_pipeline(
                 targets: &[Some(wgpu::ColorTargetState {
                     format: color_format,
                     blend: blend_state,
-                    write_mask: wgpu::ColorWrites::ALL,
+                    write_mask: if stencil == StencilMode::Write {
+                        wgpu::ColorWrites::empty()
+                    } else {
+                        wgpu::ColorWrites::ALL
+                    },
                 })],
             }),
 
@@ -447,15 +493,7 @@ pub fn create_render_pipeline(
                 conservative: false,
             },
 
-            depth_stencil: depth_format.map(|format| {
-                wgpu::DepthStencilState {
-                    format,
-                    depth_write_enabled: true,
-                    depth_compare: wgpu::CompareFunction::Less,
-                    stencil: wgpu::StencilState::default(),
-                    bias: wgpu::DepthBiasState::default(),
-                }
-            }),
+            depth_stencil: depth_stencil_state(depth_format, stencil),
 
             multisample: wgpu::MultisampleState {
                 count: 1,
@@ -477,6 +515,7 @@ pub fn create_render_pipeline_with_layout(
     shader: &Shader,
     blend_mode: BlendMode,
     enable_z_buffer: bool,
+    stencil: StencilMode,
 ) -> Result<wgpu::RenderPipeline> {
     let layout =
         device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
@@ -494,5 +533,58 @@ pub fn create_render_pipeline_with_layout(
         vertex_layouts,
         shader,
         blend_mode,
+        stencil,
     )
 }
+
+/// Depth/stencil state of a pipeline. Pipelines using a stencil mask always
+/// need the depth/stencil attachment, even with the z buffer disabled, in
+/// which case depth testing is turned off.
+pub fn depth_stencil_state(
+    depth_format: Option<wgpu::TextureFormat>,
+    stencil: StencilMode,
+) -> Option<wgpu::DepthStencilState> {
+    let face = |compare, pass_op| {
+        wgpu::StencilFaceState {
+            compare,
+            fail_op: wgpu::StencilOperation::Keep,
+            depth_fail_op: wgpu::StencilOperation::Keep,
+            pass_op,
+        }
+    };
+
+    let stencil_face = match stencil {
+        StencilMode::None => wgpu::StencilFaceState::IGNORE,
+        StencilMode::Write => {
+            face(wgpu::CompareFunction::Always, wgpu::StencilOperation::Replace)
+        }
+        StencilMode::Inside => {
+            face(wgpu::CompareFunction::Equal, wgpu::StencilOperation::Keep)
+        }
+        StencilMode::Outside => {
+            face(wgpu::CompareFunction::NotEqual, wgpu::StencilOperation::Keep)
+        }
+    };
+
+    if depth_format.is_none() && stencil == StencilMode::None {
+        return None;
+    }
+
+    Some(wgpu::DepthStencilState {
+        format: depth_format.unwrap_or(Texture::DEPTH_FORMAT),
+        depth_write_enabled: depth_format.is_some() &&
+            stencil != StencilMode::Write,
+        depth_compare: if depth_format.is_some() {
+            wgpu::CompareFunction::Less
+        } else {
+            wgpu::CompareFunction::Always
+        },
+        stencil: wgpu::StencilState {
+            front: stencil_face,
+            back: stencil_face,
+            read_mask: 0xff,
+            write_mask: if stencil == StencilMode::Write { 0xff } else { 0 },
+        },
+        bias: wgpu::DepthBiasState::default(),
+    })
+}
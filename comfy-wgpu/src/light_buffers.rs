@@ -0,0 +1,252 @@
+use crate::*;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static STORAGE_LIGHTS_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Whether lights are stored in storage buffers and binned into a
+/// `LightGrid`, which allows any number of lights. Devices without storage
+/// buffers in fragment shaders (WebGL2) use a `LightUniform` instead, limited
+/// to `MAX_UNIFORM_LIGHTS`.
+///
+/// Decided once the device is created, shaders built with
+/// `camera_bind_group_prefix` declare the lights to match.
+pub fn storage_lights_enabled() -> bool {
+    STORAGE_LIGHTS_ENABLED.load(Ordering::SeqCst)
+}
+
+pub fn detect_storage_lights(adapter: &wgpu::Adapter, device: &wgpu::Device) {
+    let supported = device.limits().max_storage_buffers_per_shader_stage >= 2 &&
+        adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(wgpu::DownlevelFlags::FRAGMENT_STORAGE);
+
+    info!("Storage buffer lights: {}", supported);
+
+    STORAGE_LIGHTS_ENABLED.store(supported, Ordering::SeqCst);
+}
+
+/// Buffers bound as the lights of the camera bind group, see
+/// `storage_lights_enabled`.
+pub enum LightBuffers {
+    Storage {
+        lights: SizedBuffer,
+        grid: wgpu::Buffer,
+        /// `LightGrid::cells` followed by `LightGrid::indices`.
+        cells: Box<SizedBuffer>,
+    },
+    Uniform {
+        lights: wgpu::Buffer,
+    },
+}
+
+impl LightBuffers {
+    pub fn new(device: &wgpu::Device) -> Self {
+        if storage_lights_enabled() {
+            Self::Storage {
+                lights: SizedBuffer::new(
+                    "Lights Buffer",
+                    device,
+                    1024 * std::mem::size_of::<LightRaw>(),
+                    BufferType::Storage,
+                ),
+                grid: device.create_buffer_init(
+                    &wgpu::util::BufferInitDescriptor {
+                        label: Some("Light Grid Buffer"),
+                        contents: bytemuck::cast_slice(&[
+                            LightGridUniform::default(),
+                        ]),
+                        usage: wgpu::BufferUsages::UNIFORM |
+                            wgpu::BufferUsages::COPY_DST,
+                    },
+                ),
+                cells: Box::new(SizedBuffer::new(
+                    "Light Grid Cells Buffer",
+                    device,
+                    64 * 1024 * std::mem::size_of::<u32>(),
+                    BufferType::Storage,
+                )),
+            }
+        } else {
+            Self::Uniform {
+                lights: device.create_buffer_init(
+                    &wgpu::util::BufferInitDescriptor {
+                        label: Some("Lights Buffer"),
+                        contents: bytemuck::cast_slice(&[
+                            LightUniform::default(),
+                        ]),
+                        usage: wgpu::BufferUsages::UNIFORM |
+                            wgpu::BufferUsages::COPY_DST,
+                    },
+                ),
+            }
+        }
+    }
+
+    pub fn layout_entries(&self) -> Vec<wgpu::BindGroupLayoutEntry> {
+        let buffer_entry = |binding, ty| {
+            wgpu::BindGroupLayoutEntry {
+                binding,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }
+        };
+
+        let storage = wgpu::BufferBindingType::Storage { read_only: true };
+
+        match self {
+            Self::Storage { .. } => {
+                vec![
+                    buffer_entry(1, storage),
+                    buffer_entry(7, wgpu::BufferBindingType::Uniform),
+                    buffer_entry(8, storage),
+                ]
+            }
+            Self::Uniform { .. } => {
+                vec![buffer_entry(1, wgpu::BufferBindingType::Uniform)]
+            }
+        }
+    }
+
+    pub fn bind_group_entries(&self) -> Vec<wgpu::BindGroupEntry<'_>> {
+        match self {
+            Self::Storage { lights, grid, cells } => {
+                vec![
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: lights.buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 7,
+                        resource: grid.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 8,
+                        resource: cells.buffer.as_entire_binding(),
+                    },
+                ]
+            }
+            Self::Uniform { lights } => {
+                vec![wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: lights.as_entire_binding(),
+                }]
+            }
+        }
+    }
+
+    /// Uploads the lights, returning `true` when the buffers had to grow and
+    /// the camera bind group needs to be recreated. Only lights reaching into
+    /// `visible_bounds` are put into the `LightGrid`.
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        lights: &[Light],
+        visible_bounds: AABB,
+    ) -> bool {
+        let _span = span!("light buffers");
+
+        let raw_lights = lights.iter().map(Light::to_raw).collect_vec();
+
+        match self {
+            Self::Storage { lights: lights_buffer, grid, cells } => {
+                let light_grid =
+                    LightGrid::new(lights, visible_bounds, LIGHT_GRID_SIZE);
+
+                queue.write_buffer(
+                    grid,
+                    0,
+                    bytemuck::cast_slice(&[light_grid.uniform(lights.len())]),
+                );
+
+                let cell_data = light_grid
+                    .cells
+                    .iter()
+                    .flatten()
+                    .chain(light_grid.indices.iter())
+                    .copied()
+                    .collect_vec();
+
+                let lights_grew = ensure_capacity(
+                    lights_buffer,
+                    device,
+                    std::mem::size_of_val(raw_lights.as_slice()),
+                );
+
+                let cells_grew = ensure_capacity(
+                    cells,
+                    device,
+                    std::mem::size_of_val(cell_data.as_slice()),
+                );
+
+                if !raw_lights.is_empty() {
+                    queue.write_buffer(
+                        &lights_buffer.buffer,
+                        0,
+                        bytemuck::cast_slice(&raw_lights),
+                    );
+                }
+
+                if !cell_data.is_empty() {
+                    queue.write_buffer(
+                        &cells.buffer,
+                        0,
+                        bytemuck::cast_slice(&cell_data),
+                    );
+                }
+
+                lights_grew || cells_grew
+            }
+            Self::Uniform { lights: lights_buffer } => {
+                let mut light_uniform = LightUniform::default();
+
+                for (i, light) in
+                    raw_lights.iter().take(MAX_UNIFORM_LIGHTS).enumerate()
+                {
+                    light_uniform.lights[i] = *light;
+                    light_uniform.num_lights += 1;
+                }
+
+                queue.write_buffer(
+                    lights_buffer,
+                    0,
+                    bytemuck::cast_slice(&[light_uniform]),
+                );
+
+                false
+            }
+        }
+    }
+}
+
+/// Replaces the buffer with one of at least `size` bytes, rounded up to a
+/// power of two to avoid recreating it every frame while the number of lights
+/// grows. The old buffer is not destroyed as bind groups created before
+/// still use it.
+fn ensure_capacity(
+    buffer: &mut SizedBuffer,
+    device: &wgpu::Device,
+    size: usize,
+) -> bool {
+    if size <= buffer.size {
+        return false;
+    }
+
+    let label = buffer.label.clone();
+
+    *buffer = SizedBuffer::new(
+        &label,
+        device,
+        size.next_power_of_two(),
+        BufferType::Storage,
+    );
+
+    true
+}
@@ -0,0 +1,246 @@
+use crate::*;
+
+/// Cookie textures of lights, one array layer of `LIGHT_COOKIE_SIZE` per
+/// texture in `light_cookies`.
+pub struct LightCookies {
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+    pub sampler: wgpu::Sampler,
+    /// What each layer contains, so that layers are updated when their
+    /// cookie changes or its texture is reloaded.
+    pub layers: [CookieLayer; MAX_LIGHT_COOKIES],
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum CookieLayer {
+    /// Plain white, leaving the light as it is.
+    #[default]
+    Empty,
+    /// White while waiting for a texture which isn't loaded or can't be used
+    /// as a cookie.
+    Missing(TextureHandle),
+    /// Contains the texture, `id` tells apart reloaded textures.
+    Uploaded { texture: TextureHandle, id: u64 },
+}
+
+impl LightCookies {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let size = wgpu::Extent3d {
+            width: LIGHT_COOKIE_SIZE,
+            height: LIGHT_COOKIE_SIZE,
+            depth_or_array_layers: MAX_LIGHT_COOKIES as u32,
+        };
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Light Cookies"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING |
+                wgpu::TextureUsages::RENDER_ATTACHMENT |
+                wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        // Cookies which are still loading leave the light as it is.
+        let white = vec![
+            255u8;
+            (4 * size.width * size.height * size.depth_or_array_layers)
+                as usize
+        ];
+
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            &white,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * LIGHT_COOKIE_SIZE),
+                rows_per_image: Some(LIGHT_COOKIE_SIZE),
+            },
+            size,
+        );
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("Light Cookies View"),
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Light Cookie Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Self {
+            texture,
+            view,
+            sampler,
+            layers: [CookieLayer::Empty; MAX_LIGHT_COOKIES],
+        }
+    }
+
+    /// Resizes newly used or reloaded cookie textures into their layer, and
+    /// clears the layers of freed cookies.
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        mipmap_generators: &MipmapGenerators,
+        textures: &TextureMap,
+    ) {
+        let _span = span!("light cookies");
+
+        let blit = mipmap_generators.get(device, self.texture.format());
+
+        let mut encoder = None;
+
+        let cookies = light_cookies();
+
+        for i in 0..MAX_LIGHT_COOKIES {
+            let cookie = cookies.get(i).copied().flatten();
+
+            let (layer_state, source) = match cookie {
+                None => (CookieLayer::Empty, None),
+                Some(texture @ TextureHandle::RenderTarget(_)) => {
+                    (CookieLayer::Missing(texture), None)
+                }
+                Some(texture) => {
+                    match textures.get(&texture) {
+                        Some(source) => {
+                            let id = default_hash(
+                                &source.texture.texture.global_id(),
+                            );
+
+                            (
+                                CookieLayer::Uploaded { texture, id },
+                                Some(source),
+                            )
+                        }
+                        None => (CookieLayer::Missing(texture), None),
+                    }
+                }
+            };
+
+            if self.layers[i] == layer_state {
+                continue;
+            }
+
+            if let CookieLayer::Missing(texture) = layer_state {
+                report_missing_cookie(texture);
+            }
+
+            self.layers[i] = layer_state;
+
+            let layer =
+                self.texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some("Light Cookie Layer"),
+                    dimension: Some(wgpu::TextureViewDimension::D2),
+                    base_array_layer: i as u32,
+                    array_layer_count: Some(1),
+                    ..Default::default()
+                });
+
+            let bind_group = source.map(|source| {
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("Light Cookie Blit Bind Group"),
+                    layout: &blit.blit_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(
+                                &source.texture.view,
+                            ),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::Sampler(
+                                &self.sampler,
+                            ),
+                        },
+                    ],
+                })
+            });
+
+            let encoder = encoder
+                .get_or_insert_with(|| device.simple_encoder("Light Cookies"));
+
+            let mut pass =
+                encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("Light Cookie Blit"),
+                    color_attachments: &[Some(
+                        wgpu::RenderPassColorAttachment {
+                            view: &layer,
+                            resolve_target: None,
+                            ops: wgpu::Operations {
+                                load: wgpu::LoadOp::Clear(wgpu::Color::WHITE),
+                                store: wgpu::StoreOp::Store,
+                            },
+                        },
+                    )],
+                    depth_stencil_attachment: None,
+                    timestamp_writes: None,
+                    occlusion_query_set: None,
+                });
+
+            // Layers without a texture are only cleared to white.
+            if let Some(bind_group) = &bind_group {
+                pass.set_pipeline(&blit.blit_pipeline);
+                pass.set_bind_group(0, bind_group, &[]);
+                pass.draw(0..3, 0..1);
+            }
+        }
+
+        if let Some(encoder) = encoder {
+            queue.submit(std::iter::once(encoder.finish()));
+        }
+    }
+}
+
+/// Logs why a cookie can't be uploaded, unless its texture is still loading.
+fn report_missing_cookie(texture: TextureHandle) {
+    match texture {
+        TextureHandle::RenderTarget(_) => {
+            error!(
+                "Render targets can't be used as light cookies, ignoring {:?}",
+                texture
+            );
+        }
+        TextureHandle::Path(_) => {
+            let assets = ASSETS.borrow();
+
+            let loading = assets.textures.values().any(|x| *x == texture) ||
+                assets
+                    .asset_loader
+                    .pending_textures
+                    .iter()
+                    .any(|x| texture_id_unchecked(x) == texture);
+
+            if !loading {
+                warn!(
+                    "Light cookie {:?} was never loaded, drawing the light \
+                     without it",
+                    texture
+                );
+            }
+        }
+        TextureHandle::Raw(_) => {
+            warn!(
+                "Light cookie {:?} doesn't exist, drawing the light without it",
+                texture
+            );
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use crate::*;
+
+/// Bind groups sprites are drawn with, which pair the sprite's texture with
+/// its normal map set with `set_normal_map`.
+pub struct SpriteTextureBindGroups {
+    pub layout: Arc<wgpu::BindGroupLayout>,
+    /// Bound for textures without a normal map. Its alpha is zero, which
+    /// turns off normal mapped shading in the sprite shader.
+    pub flat_normal_map: Texture,
+    cache: RefCell<HashMap<TextureHandle, (u64, Arc<wgpu::BindGroup>)>>,
+}
+
+impl SpriteTextureBindGroups {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let texture_entry = |binding| {
+            wgpu::BindGroupLayoutEntry {
+                binding,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Float {
+                        filterable: true,
+                    },
+                },
+                count: None,
+            }
+        };
+
+        let sampler_entry = |binding| {
+            wgpu::BindGroupLayoutEntry {
+                binding,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(
+                    wgpu::SamplerBindingType::Filtering,
+                ),
+                count: None,
+            }
+        };
+
+        let layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("sprite_texture_bind_group_layout"),
+                entries: &[
+                    texture_entry(0),
+                    sampler_entry(1),
+                    texture_entry(2),
+                    sampler_entry(3),
+                ],
+            });
+
+        let flat_normal_map = Texture::from_image_data_with_format(
+            device,
+            queue,
+            &[128, 128, 255, 0],
+            Some("Flat Normal Map"),
+            wgpu::AddressMode::ClampToEdge,
+            wgpu::TextureFormat::Rgba8Unorm,
+            (1, 1),
+            4,
+        )
+        .expect("flat normal map must be valid");
+
+        Self {
+            layout: Arc::new(layout),
+            flat_normal_map,
+            cache: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Bind group for drawing sprites with `texture`. Bind groups are cached
+    /// and re-created when the texture, its normal map or either of their
+    /// samplers change.
+    pub fn get(
+        &self,
+        device: &wgpu::Device,
+        texture: TextureHandle,
+        textures: &TextureMap,
+        render_targets: &RenderTargetMap,
+    ) -> Arc<wgpu::BindGroup> {
+        let (view, sampler) = match texture {
+            TextureHandle::RenderTarget(render_target_id) => {
+                let target = render_targets.get(&render_target_id).unwrap();
+                (&target.view, &target.sampler)
+            }
+            _ => {
+                let bindable = textures.get(&texture).unwrap_or_else(|| {
+                    textures
+                        .get(&texture_id("error"))
+                        .expect("error texture must exist")
+                });
+
+                (&bindable.texture.view, &bindable.texture.sampler)
+            }
+        };
+
+        let normal_map = normal_map(texture)
+            .and_then(|x| textures.get(&x))
+            .map(|x| &x.texture)
+            .unwrap_or(&self.flat_normal_map);
+
+        let key = default_hash(&(
+            view.global_id(),
+            sampler.global_id(),
+            normal_map.view.global_id(),
+            normal_map.sampler.global_id(),
+        ));
+
+        let mut cache = self.cache.borrow_mut();
+
+        if let Some((cached_key, bind_group)) = cache.get(&texture) {
+            if *cached_key == key {
+                return bind_group.clone();
+            }
+        }
+
+        let bind_group =
+            Arc::new(device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Sprite Texture Bind Group"),
+                layout: &self.layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::TextureView(
+                            &normal_map.view,
+                        ),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: wgpu::BindingResource::Sampler(
+                            &normal_map.sampler,
+                        ),
+                    },
+                ],
+            }));
+
+        cache.insert(texture, (key, bind_group.clone()));
+
+        bind_group
+    }
+}
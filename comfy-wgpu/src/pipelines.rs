@@ -1,26 +1,11 @@
-use std::sync::atomic::AtomicU32;
-
 use crate::*;
 
-static GENERATED_RENDER_TARGET_IDS: AtomicU32 = AtomicU32::new(1);
-
-#[derive(Clone, Debug)]
-pub struct RenderTargetParams {
-    pub label: String,
-    pub size: UVec2,
-    pub filter_mode: wgpu::FilterMode,
-}
-
-/// Creates a new render target with given dimensions. Among other parameters a label is
-/// required so that graphic debuggers like RenderDoc can display its name properly.
-pub fn create_render_target(
-    renderer: &mut WgpuRenderer,
+/// Creates the texture of a render target created with
+/// `create_render_target`. Intended for internal use by the renderer.
+pub fn create_user_render_target(
+    c: &GraphicsContext,
     params: &RenderTargetParams,
-) -> RenderTargetId {
-    let id = gen_render_target();
-
-    let c = &renderer.context;
-
+) -> UserRenderTarget {
     let size = wgpu::Extent3d {
         width: params.size.x,
         height: params.size.y,
@@ -33,7 +18,7 @@ pub fn create_render_target(
         mip_level_count: 1,
         sample_count: 1,
         dimension: wgpu::TextureDimension::D2,
-        format: wgpu::TextureFormat::Rgba16Float,
+        format: render_target_format_to_wgpu(params.format),
         usage: wgpu::TextureUsages::TEXTURE_BINDING |
             wgpu::TextureUsages::RENDER_ATTACHMENT,
         view_formats: &[],
@@ -50,14 +35,19 @@ pub fn create_render_target(
         array_layer_count: None,
     });
 
+    let filter_mode = match params.filter {
+        TextureFilter::Nearest => wgpu::FilterMode::Nearest,
+        TextureFilter::Linear => wgpu::FilterMode::Linear,
+    };
+
     let sampler = c.device.create_sampler(&wgpu::SamplerDescriptor {
         label: Some(&format!("{} Sampler", params.label)),
         address_mode_u: wgpu::AddressMode::ClampToEdge,
         address_mode_v: wgpu::AddressMode::ClampToEdge,
         address_mode_w: wgpu::AddressMode::ClampToEdge,
-        mag_filter: params.filter_mode,
-        min_filter: params.filter_mode,
-        mipmap_filter: params.filter_mode,
+        mag_filter: filter_mode,
+        min_filter: filter_mode,
+        mipmap_filter: filter_mode,
         ..Default::default()
     });
 
@@ -76,15 +66,31 @@ pub fn create_render_target(
         ],
     });
 
-    renderer.render_targets.borrow_mut().insert(id, UserRenderTarget {
+    let depth_texture = Texture::create_depth_texture_with_size(
+        &c.device,
+        params.size,
+        &format!("{} Depth Texture", params.label),
+    );
+
+    UserRenderTarget {
         creation_params: params.clone(),
         texture,
         view,
         sampler,
         bind_group,
-    });
+        depth_texture,
+    }
+}
 
-    id
+pub fn render_target_format_to_wgpu(
+    format: RenderTargetFormat,
+) -> wgpu::TextureFormat {
+    match format {
+        RenderTargetFormat::Rgba16Float => wgpu::TextureFormat::Rgba16Float,
+        RenderTargetFormat::Rgba8UnormSrgb => {
+            wgpu::TextureFormat::Rgba8UnormSrgb
+        }
+    }
 }
 
 pub struct UserRenderTarget {
@@ -93,14 +99,8 @@ pub struct UserRenderTarget {
     pub view: wgpu::TextureView,
     pub sampler: wgpu::Sampler,
     pub bind_group: wgpu::BindGroup,
-}
-
-/// Allocates a new render target id
-fn gen_render_target() -> RenderTargetId {
-    let id = GENERATED_RENDER_TARGET_IDS
-        .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
-
-    RenderTargetId(id)
+    /// Depth and stencil buffer of the target's size.
+    pub depth_texture: Texture,
 }
 
 pub fn ensure_pipeline_exists(
@@ -108,6 +108,7 @@ pub fn ensure_pipeline_exists(
     pass_data: &MeshDrawData,
     sprite_shader_id: ShaderId,
 ) -> String {
+    let format = render_target_format(c, pass_data.render_target);
     let shaders = c.shaders.borrow();
 
     let maybe_shader_instance_id = pass_data.shader;
@@ -121,8 +122,18 @@ pub fn ensure_pipeline_exists(
         }
     };
 
+    let stencil = get_clip_state(pass_data.clip).stencil;
+
+    // Stencil masks are drawn with a shader that discards transparent pixels
+    // so that the mask follows the shape of the sprite.
+    let builtin_shader_id = if stencil == StencilMode::Write {
+        c.stencil_mask_shader_id
+    } else {
+        sprite_shader_id
+    };
+
     let name = format!(
-        "{} {:?} {:?} {:?}",
+        "{} {:?} {:?} {:?} {:?} {:?}",
         if maybe_shader_instance_id.0 > 0 {
             "USER(Mesh)"
         } else {
@@ -130,7 +141,9 @@ pub fn ensure_pipeline_exists(
         },
         pass_data.blend_mode,
         maybe_shader,
-        c.enable_z_buffer
+        c.enable_z_buffer,
+        stencil,
+        format,
     );
 
     let mesh_pipeline = if let Some(shader) = maybe_shader {
@@ -141,9 +154,11 @@ pub fn ensure_pipeline_exists(
                     pass_data,
                     shader,
                     &c.context,
-                    &c.texture_layout,
+                    &c.sprite_textures.layout,
                     &c.camera_bind_group_layout,
                     c.enable_z_buffer,
+                    format,
+                    &c.render_targets.borrow(),
                 )
             }),
         )
@@ -153,12 +168,13 @@ pub fn ensure_pipeline_exists(
                 create_render_pipeline_with_layout(
                     &name,
                     &c.context.device,
-                    wgpu::TextureFormat::Rgba16Float,
-                    &[&c.texture_layout, &c.camera_bind_group_layout],
+                    format,
+                    &[&c.sprite_textures.layout, &c.camera_bind_group_layout],
                     &[SpriteVertex::desc()],
-                    shaders.get(sprite_shader_id).unwrap(),
+                    shaders.get(builtin_shader_id).unwrap(),
                     pass_data.blend_mode,
                     c.enable_z_buffer,
+                    stencil,
                 )
                 .unwrap()
             },
@@ -173,32 +189,171 @@ pub fn ensure_pipeline_exists(
             for (buffer_name, buffer) in
                 user_pipeline.buffers.iter().sorted_by_key(|x| x.0)
             {
-                if let Some(Uniform::F32(OrderedFloat(value))) =
-                    shader_instance.uniforms.get(buffer_name)
-                {
-                    c.context.queue.write_buffer(
-                        buffer,
-                        0,
-                        bytemuck::cast_slice(&[*value]),
-                    );
-                } else if let UniformDef::F32(Some(default_value)) =
-                    shader.uniform_defs.get(buffer_name).unwrap()
-                {
-                    c.context.queue.write_buffer(
-                        buffer,
-                        0,
-                        bytemuck::cast_slice(&[*default_value]),
-                    );
-                } else {
-                    panic!("No uniform value or default for {buffer_name}");
+                let uniform_def = shader.uniform_defs.get(buffer_name).unwrap();
+
+                let value = shader_instance
+                    .uniforms
+                    .get(buffer_name)
+                    .cloned()
+                    .or_else(|| uniform_def.default_value())
+                    .unwrap_or_else(|| {
+                        panic!("No uniform value or default for {buffer_name}")
+                    });
+
+                match uniform_def.encode(&value) {
+                    Some(bytes) => {
+                        c.context.queue.write_buffer(buffer, 0, &bytes);
+                    }
+                    None => {
+                        error!(
+                            "Value {:?} of uniform {} doesn't match its \
+                             definition {:?}",
+                            value, buffer_name, uniform_def
+                        );
+                    }
                 }
             }
         }
     }
 
+    // Textures can differ between shader instances, so the bind group of a
+    // shader with texture uniforms is rebuilt for every draw.
+    if let Some(shader) = maybe_shader {
+        if shader.uniform_defs.values().any(|x| x.is_texture()) {
+            let shader_instance = get_shader_instance(maybe_shader_instance_id);
+            let textures = c.textures.lock();
+            let render_targets = c.render_targets.borrow();
+
+            let user_pipeline = c.user_pipelines.get_mut(&name).unwrap();
+
+            user_pipeline.bind_group = create_user_bind_group(
+                &c.context.device,
+                &user_pipeline.layout,
+                shader,
+                &user_pipeline.buffers,
+                &resolve_user_textures(
+                    shader,
+                    Some(&shader_instance.uniforms),
+                    &textures,
+                    &render_targets,
+                ),
+            );
+        }
+    }
+
     name
 }
 
+/// Textures bound to the shader's `UniformDef::Texture` uniforms, taken from
+/// the given uniform values or their defaults. Missing textures show the error
+/// texture.
+pub fn resolve_user_textures<'a>(
+    shader: &Shader,
+    values: Option<&HashMap<String, Uniform>>,
+    textures: &'a TextureMap,
+    render_targets: &'a RenderTargetMap,
+) -> HashMap<String, (&'a wgpu::TextureView, &'a wgpu::Sampler)> {
+    let error_texture =
+        textures.get(&texture_id("error")).expect("error texture must exist");
+
+    shader
+        .uniform_defs
+        .iter()
+        .filter_map(|(name, uniform_def)| {
+            let UniformDef::Texture(default) = uniform_def else {
+                return None;
+            };
+
+            let handle = match values.and_then(|x| x.get(name)) {
+                Some(Uniform::Texture(handle)) => Some(*handle),
+                _ => *default,
+            };
+
+            let binding = match handle {
+                Some(TextureHandle::RenderTarget(id)) => {
+                    render_targets.get(&id).map(|x| (&x.view, &x.sampler))
+                }
+                Some(handle) => {
+                    textures
+                        .get(&handle)
+                        .map(|x| (&x.texture.view, &x.texture.sampler))
+                }
+                None => None,
+            }
+            .unwrap_or((
+                &error_texture.texture.view,
+                &error_texture.texture.sampler,
+            ));
+
+            Some((name.clone(), binding))
+        })
+        .collect()
+}
+
+/// Whether the shader instance binds `texture` to one of its texture
+/// uniforms, either as a value or through the uniform's default.
+pub fn shader_samples_texture(
+    shaders: &ShaderMap,
+    instance_id: ShaderInstanceId,
+    texture: TextureHandle,
+) -> bool {
+    if instance_id.0 == 0 {
+        return false;
+    }
+
+    let instance = get_shader_instance(instance_id);
+
+    let Some(shader) = shaders.get(instance.id) else {
+        return false;
+    };
+
+    shader.uniform_defs.iter().any(|(name, uniform_def)| {
+        let UniformDef::Texture(default) = uniform_def else {
+            return false;
+        };
+
+        match instance.uniforms.get(name) {
+            Some(Uniform::Texture(handle)) => *handle == texture,
+            _ => *default == Some(texture),
+        }
+    })
+}
+
+pub fn create_user_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    shader: &Shader,
+    buffers: &HashMap<String, wgpu::Buffer>,
+    textures: &HashMap<String, (&wgpu::TextureView, &wgpu::Sampler)>,
+) -> wgpu::BindGroup {
+    let mut entries = Vec::new();
+
+    for (name, binding) in shader.bindings.iter() {
+        if let Some((view, sampler)) = textures.get(name) {
+            entries.push(wgpu::BindGroupEntry {
+                binding: *binding,
+                resource: wgpu::BindingResource::TextureView(view),
+            });
+
+            entries.push(wgpu::BindGroupEntry {
+                binding: *binding + 1,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            });
+        } else {
+            entries.push(wgpu::BindGroupEntry {
+                binding: *binding,
+                resource: buffers.get(name).unwrap().as_entire_binding(),
+            });
+        }
+    }
+
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("User Bind Group"),
+        layout,
+        entries: &entries,
+    })
+}
+
 pub fn create_user_pipeline(
     name: &str,
     pass_data: &MeshDrawData,
@@ -207,19 +362,49 @@ pub fn create_user_pipeline(
     texture_layout: &Arc<wgpu::BindGroupLayout>,
     camera_bind_group_layout: &wgpu::BindGroupLayout,
     enable_z_buffer: bool,
+    format: wgpu::TextureFormat,
+    render_targets: &RenderTargetMap,
 ) -> UserRenderPipeline {
     info!("Creating pipeline for shader: {:?}", shader.id);
 
     let mut layout_entries = Vec::new();
-    let mut bind_group_entries = Vec::new();
     let mut buffers = HashMap::new();
 
     for (uniform_name, binding) in shader.bindings.iter() {
         let uniform_def = shader.uniform_defs.get(uniform_name).unwrap();
 
+        if uniform_def.is_texture() {
+            layout_entries.push(wgpu::BindGroupLayoutEntry {
+                binding: *binding,
+                visibility: wgpu::ShaderStages::VERTEX |
+                    wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Float {
+                        filterable: true,
+                    },
+                },
+                count: None,
+            });
+
+            layout_entries.push(wgpu::BindGroupLayoutEntry {
+                binding: *binding + 1,
+                visibility: wgpu::ShaderStages::VERTEX |
+                    wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(
+                    wgpu::SamplerBindingType::Filtering,
+                ),
+                count: None,
+            });
+
+            continue;
+        }
+
         layout_entries.push(wgpu::BindGroupLayoutEntry {
             binding: *binding,
-            visibility: wgpu::ShaderStages::FRAGMENT,
+            visibility: wgpu::ShaderStages::VERTEX |
+                wgpu::ShaderStages::FRAGMENT,
             ty: wgpu::BindingType::Buffer {
                 ty: wgpu::BufferBindingType::Uniform,
                 has_dynamic_offset: false,
@@ -231,47 +416,15 @@ pub fn create_user_pipeline(
         let uniform_buffer_usage =
             wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST;
 
-        match uniform_def {
-            UniformDef::F32(maybe_default) => {
-                if let Some(value) = maybe_default {
-                    let buffer = context.device.create_buffer_init(
-                        &wgpu::util::BufferInitDescriptor {
-                            label: Some(&format!(
-                                "User UB: {} (default={})",
-                                uniform_name, value
-                            )),
-                            contents: bytemuck::cast_slice(&[*value]),
-                            usage: uniform_buffer_usage,
-                        },
-                    );
-
-                    buffers.insert(uniform_name.to_string(), buffer);
-                } else {
-                    let buffer =
-                        context.device.create_buffer(&wgpu::BufferDescriptor {
-                            label: Some(&format!(
-                                "User UB: {} (no-default)",
-                                uniform_name
-                            )),
-                            size: std::mem::size_of::<f32>() as u64,
-                            usage: uniform_buffer_usage,
-                            mapped_at_creation: false,
-                        });
-
-                    buffers.insert(uniform_name.to_string(), buffer);
-                }
-            }
-            UniformDef::Custom { .. } => {
-                unimplemented!("custom uniforms aren't available yet");
-            }
-        };
-    }
-
-    for (name, binding) in shader.bindings.iter() {
-        bind_group_entries.push(wgpu::BindGroupEntry {
-            binding: *binding,
-            resource: buffers.get(name).unwrap().as_entire_binding(),
+        // Values (or their defaults) are written before every draw.
+        let buffer = context.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(&format!("User UB: {}", uniform_name)),
+            size: uniform_def.size() as u64,
+            usage: uniform_buffer_usage,
+            mapped_at_creation: false,
         });
+
+        buffers.insert(uniform_name.to_string(), buffer);
     }
 
     let user_layout = context.device.create_bind_group_layout(
@@ -284,21 +437,547 @@ pub fn create_user_pipeline(
     let pipeline = create_render_pipeline_with_layout(
         name,
         &context.device,
-        wgpu::TextureFormat::Rgba16Float,
+        format,
         &[&texture_layout, &camera_bind_group_layout, &user_layout],
         &[SpriteVertex::desc()],
         shader,
         pass_data.blend_mode,
         enable_z_buffer,
+        get_clip_state(pass_data.clip).stencil,
     )
     .unwrap();
 
-    let bind_group =
-        context.device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("User Bind Group"),
-            layout: &user_layout,
-            entries: &bind_group_entries,
-        });
+    let bind_group = create_user_bind_group(
+        &context.device,
+        &user_layout,
+        shader,
+        &buffers,
+        &resolve_user_textures(
+            shader,
+            None,
+            &context.textures.lock(),
+            render_targets,
+        ),
+    );
 
     UserRenderPipeline { pipeline, layout: user_layout, bind_group, buffers }
 }
+
+/// Blend state used by pipelines drawing with the given `BlendMode`.
+/// Pipelines are cached by a name containing the blend mode, so every
+/// `BlendMode::Custom` state gets its own pipeline.
+pub fn blend_mode_to_wgpu(blend_mode: BlendMode) -> wgpu::BlendState {
+    match blend_mode {
+        BlendMode::None | BlendMode::Alpha => wgpu::BlendState::ALPHA_BLENDING,
+        BlendMode::Additive => {
+            wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::One,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::One,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+            }
+        }
+        BlendMode::Multiply => {
+            wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::Dst,
+                    dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::Zero,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+            }
+        }
+        BlendMode::Screen => {
+            wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::One,
+                    dst_factor: wgpu::BlendFactor::OneMinusSrc,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: wgpu::BlendComponent::OVER,
+            }
+        }
+        BlendMode::Subtract => {
+            wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::SrcAlpha,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::ReverseSubtract,
+                },
+                alpha: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::Zero,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+            }
+        }
+        BlendMode::PremultipliedAlpha => {
+            wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING
+        }
+        BlendMode::Custom(state) => {
+            wgpu::BlendState {
+                color: blend_component_to_wgpu(state.color),
+                alpha: blend_component_to_wgpu(state.alpha),
+            }
+        }
+    }
+}
+
+fn blend_component_to_wgpu(component: BlendComponent) -> wgpu::BlendComponent {
+    wgpu::BlendComponent {
+        src_factor: blend_factor_to_wgpu(component.src_factor),
+        dst_factor: blend_factor_to_wgpu(component.dst_factor),
+        operation: match component.operation {
+            BlendOperation::Add => wgpu::BlendOperation::Add,
+            BlendOperation::Subtract => wgpu::BlendOperation::Subtract,
+            BlendOperation::ReverseSubtract => {
+                wgpu::BlendOperation::ReverseSubtract
+            }
+            BlendOperation::Min => wgpu::BlendOperation::Min,
+            BlendOperation::Max => wgpu::BlendOperation::Max,
+        },
+    }
+}
+
+fn blend_factor_to_wgpu(factor: BlendFactor) -> wgpu::BlendFactor {
+    match factor {
+        BlendFactor::Zero => wgpu::BlendFactor::Zero,
+        BlendFactor::One => wgpu::BlendFactor::One,
+        BlendFactor::Src => wgpu::BlendFactor::Src,
+        BlendFactor::OneMinusSrc => wgpu::BlendFactor::OneMinusSrc,
+        BlendFactor::SrcAlpha => wgpu::BlendFactor::SrcAlpha,
+        BlendFactor::OneMinusSrcAlpha => wgpu::BlendFactor::OneMinusSrcAlpha,
+        BlendFactor::Dst => wgpu::BlendFactor::Dst,
+        BlendFactor::OneMinusDst => wgpu::BlendFactor::OneMinusDst,
+        BlendFactor::DstAlpha => wgpu::BlendFactor::DstAlpha,
+        BlendFactor::OneMinusDstAlpha => wgpu::BlendFactor::OneMinusDstAlpha,
+        BlendFactor::SrcAlphaSaturated => wgpu::BlendFactor::SrcAlphaSaturated,
+    }
+}
+
+/// `Multiply` and `Screen` can't be expressed with blend factors for
+/// straight alpha colors, their pipelines premultiply the fragment output
+/// with `premultiply_fragment_output` instead.
+pub fn blend_mode_premultiplies(blend_mode: BlendMode) -> bool {
+    matches!(blend_mode, BlendMode::Multiply | BlendMode::Screen)
+}
+
+/// Turns the `fs_main` entry point of the shader into a plain function and
+/// wraps it in a new `fs_main` which multiplies its color by its alpha. The
+/// whole body is kept, so any number of returns works.
+///
+/// The entry point is looked up with naga, which fails if the shader doesn't
+/// parse, has no `@fragment fn fs_main`, or its `fs_main` doesn't return a
+/// single `@location(0) vec4<f32>` (e.g. it returns a struct). The wrapper
+/// passes on the arguments naga found, and the result is validated, so a
+/// rewrite which doesn't apply is an error rather than a broken pipeline.
+pub fn premultiply_fragment_output(source: &str) -> Result<String> {
+    let module = naga::front::wgsl::parse_str(source)
+        .map_err(|err| anyhow::anyhow!(err.emit_to_string(source)))?;
+
+    let Some(entry_point) = module.entry_points.iter().find(|x| {
+        x.name == "fs_main" && x.stage == naga::ShaderStage::Fragment
+    }) else {
+        bail!("The shader has no @fragment fn fs_main");
+    };
+
+    let vec4_f32 = naga::TypeInner::Vector {
+        size: naga::VectorSize::Quad,
+        scalar: naga::Scalar::F32,
+    };
+
+    let returns_color = entry_point.function.result.as_ref().is_some_and(|x| {
+        let location_0 = matches!(
+            x.binding,
+            Some(naga::Binding::Location { location: 0, .. })
+        );
+
+        location_0 && module.types[x.ty].inner == vec4_f32
+    });
+
+    if !returns_color {
+        bail!("fs_main must return a single @location(0) vec4<f32>");
+    }
+
+    // Comments are blanked out so that they can't be mistaken for code,
+    // offsets into both strings are the same.
+    let code = blank_wgsl_comments(source);
+
+    let start = code
+        .match_indices("fs_main")
+        .map(|(i, _)| i)
+        .find(|&i| {
+            let before = code[..i].trim_end();
+            let after = &code[i + "fs_main".len()..];
+
+            before.len() < i &&
+                before.strip_suffix("fn").is_some_and(|x| {
+                    !x.ends_with(|c: char| c.is_alphanumeric() || c == '_')
+                }) &&
+                after.trim_start().starts_with('(')
+        })
+        .ok_or_else(|| anyhow::anyhow!("fn fs_main not found"))?;
+
+    // The attributes are everything between the previous declaration and
+    // the function.
+    let prefix_end = code[..start].rfind(['}', ';']).map_or(0, |x| x + 1);
+    let fn_start = code[..start].trim_end().len() - "fn".len();
+
+    let params_start = start + code[start..].find('(').unwrap() + 1;
+    let mut depth = 1;
+
+    let params_end = params_start +
+        code[params_start..]
+            .find(|c| {
+                match c {
+                    '(' => depth += 1,
+                    ')' => depth -= 1,
+                    _ => {}
+                }
+
+                depth == 0
+            })
+            .ok_or_else(|| {
+                anyhow::anyhow!("Unclosed parameters of fs_main")
+            })?;
+
+    let body_start = params_end +
+        code[params_end..]
+            .find('{')
+            .ok_or_else(|| anyhow::anyhow!("fs_main has no body"))?;
+
+    let params = &code[params_start..params_end];
+
+    let names = entry_point
+        .function
+        .arguments
+        .iter()
+        .map(|x| x.name.as_deref().unwrap_or_default())
+        .collect_vec();
+
+    let params_split = split_params(params);
+
+    if params_split.len() != names.len() {
+        bail!(
+            "Found {} parameters of fs_main, expected {:?}",
+            params_split.len(),
+            names
+        );
+    }
+
+    let mut plain_params = vec![];
+
+    // The attributes are dropped, they're only allowed on entry points.
+    for (param, name) in params_split.into_iter().zip(&names) {
+        let name_start = param
+            .match_indices(name)
+            .map(|(i, _)| i)
+            .find(|&i| param[i + name.len()..].trim_start().starts_with(':'))
+            .ok_or_else(|| {
+                anyhow::anyhow!("Can't parse parameter {:?} of fs_main", param)
+            })?;
+
+        plain_params.push(&param[name_start..]);
+    }
+
+    let result = format!(
+        r#"{prefix}
+fn fs_main_straight({plain_params}) -> vec4<f32> {body}
+
+{attributes}
+fn fs_main({params}) {output} {{
+    let fs_main_color = fs_main_straight({names});
+    return vec4<f32>(fs_main_color.rgb * fs_main_color.a, fs_main_color.a);
+}}
+"#,
+        prefix = &source[..prefix_end],
+        plain_params = plain_params.join(", "),
+        body = &source[body_start..],
+        attributes = code[prefix_end..fn_start].trim(),
+        output = code[params_end + 1..body_start].trim(),
+        names = names.join(", "),
+    );
+
+    let module = naga::front::wgsl::parse_str(&result).map_err(|err| {
+        anyhow::anyhow!(
+            "The premultiplied fs_main doesn't parse: {}",
+            err.emit_to_string(&result)
+        )
+    })?;
+
+    naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::all(),
+    )
+    .validate(&module)
+    .map_err(|err| {
+        anyhow::anyhow!("The premultiplied fs_main is invalid: {:?}", err)
+    })?;
+
+    Ok(result)
+}
+
+/// Replaces WGSL comments with spaces, keeping line breaks so that offsets
+/// and line numbers stay the same. Block comments can be nested.
+fn blank_wgsl_comments(source: &str) -> String {
+    let mut result = String::with_capacity(source.len());
+    let mut chars = source.chars().peekable();
+    let mut block_depth = 0;
+    let mut in_line_comment = false;
+
+    // Multi-byte characters become several spaces to keep the offsets.
+    let blank = |result: &mut String, c: char| {
+        if c == '\n' {
+            result.push(c);
+        } else {
+            result.extend(std::iter::repeat_n(' ', c.len_utf8()));
+        }
+    };
+
+    while let Some(c) = chars.next() {
+        let next = chars.peek().copied();
+
+        if in_line_comment {
+            in_line_comment = c != '\n';
+            blank(&mut result, c);
+        } else if c == '/' && next == Some('*') {
+            chars.next();
+            block_depth += 1;
+            result.push_str("  ");
+        } else if block_depth > 0 && c == '*' && next == Some('/') {
+            chars.next();
+            block_depth -= 1;
+            result.push_str("  ");
+        } else if block_depth > 0 {
+            blank(&mut result, c);
+        } else if c == '/' && next == Some('/') {
+            in_line_comment = true;
+            result.push(' ');
+        } else {
+            result.push(c);
+        }
+    }
+
+    result
+}
+
+/// Splits function parameters on commas which aren't nested in attribute
+/// arguments or template lists.
+fn split_params(params: &str) -> Vec<&str> {
+    let mut result = vec![];
+    let mut depth = 0;
+    let mut last = 0;
+
+    for (i, c) in params.char_indices() {
+        match c {
+            '(' | '<' => depth += 1,
+            ')' | '>' => depth -= 1,
+            ',' if depth == 0 => {
+                result.push(params[last..i].trim());
+                last = i + 1;
+            }
+            _ => {}
+        }
+    }
+
+    result.push(params[last..].trim());
+    result.retain(|x| !x.is_empty());
+    result
+}
+
+#[cfg(test)]
+fn blend_color(
+    blend_mode: BlendMode,
+    src: [f32; 4],
+    dst: [f32; 4],
+) -> [f32; 4] {
+    let state = blend_mode_to_wgpu(blend_mode);
+
+    let src = if blend_mode_premultiplies(blend_mode) {
+        [src[0] * src[3], src[1] * src[3], src[2] * src[3], src[3]]
+    } else {
+        src
+    };
+
+    let factor = |factor: wgpu::BlendFactor, i: usize| {
+        match factor {
+            wgpu::BlendFactor::Zero => 0.0,
+            wgpu::BlendFactor::One => 1.0,
+            wgpu::BlendFactor::Src => src[i],
+            wgpu::BlendFactor::OneMinusSrc => 1.0 - src[i],
+            wgpu::BlendFactor::SrcAlpha => src[3],
+            wgpu::BlendFactor::OneMinusSrcAlpha => 1.0 - src[3],
+            wgpu::BlendFactor::Dst => dst[i],
+            wgpu::BlendFactor::OneMinusDst => 1.0 - dst[i],
+            factor => panic!("unexpected blend factor {:?}", factor),
+        }
+    };
+
+    let mut result = [0.0; 4];
+
+    for (i, value) in result.iter_mut().enumerate() {
+        let component = if i == 3 { state.alpha } else { state.color };
+        let s = src[i] * factor(component.src_factor, i);
+        let d = dst[i] * factor(component.dst_factor, i);
+
+        *value = match component.operation {
+            wgpu::BlendOperation::Add => s + d,
+            wgpu::BlendOperation::Subtract => s - d,
+            wgpu::BlendOperation::ReverseSubtract => d - s,
+            wgpu::BlendOperation::Min => s.min(d),
+            wgpu::BlendOperation::Max => s.max(d),
+        };
+    }
+
+    result
+}
+
+#[test]
+fn test_blend_mode_states() {
+    let dst = [0.5, 0.25, 1.0, 1.0];
+    let src = [0.5, 1.0, 0.0, 1.0];
+
+    let modes = [
+        BlendMode::Alpha,
+        BlendMode::Multiply,
+        BlendMode::Screen,
+        BlendMode::Subtract,
+    ];
+
+    // Fully transparent pixels leave the destination unchanged.
+    for mode in modes {
+        assert_eq!(
+            blend_color(mode, [0.3, 0.6, 0.9, 0.0], dst),
+            dst,
+            "{mode:?}"
+        );
+    }
+
+    assert_eq!(blend_color(BlendMode::Multiply, src, dst), [
+        0.25, 0.25, 0.0, 1.0
+    ]);
+
+    assert_eq!(blend_color(BlendMode::Screen, src, dst), [0.75, 1.0, 1.0, 1.0]);
+
+    // Half transparent pixels only go halfway.
+    let half = [src[0], src[1], src[2], 0.5];
+
+    assert_eq!(blend_color(BlendMode::Multiply, half, dst), [
+        0.375, 0.25, 0.5, 1.0
+    ]);
+
+    assert_eq!(blend_color(BlendMode::Screen, half, dst), [
+        0.625, 0.625, 1.0, 1.0
+    ]);
+}
+
+#[test]
+fn test_premultiply_fragment_output() {
+    let validate = |source: &str| {
+        let module = naga::front::wgsl::parse_str(source).unwrap();
+
+        naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::all(),
+        )
+        .validate(&module)
+        .unwrap();
+    };
+
+    let sprite = sprite_shader_from_fragment(engine_shader_source!("sprite"));
+    let premultiplied = premultiply_fragment_output(&sprite).unwrap();
+
+    validate(&sprite);
+    validate(&premultiplied);
+
+    assert!(premultiplied.contains("fs_main_straight(in)"));
+
+    let attributes = "
+        // @fragment
+        // fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {}
+
+        @fragment
+        fn fs_main(
+            @builtin(position) position: vec4<f32>,
+            @location(0) @interpolate(flat) color: vec4<f32>,
+        ) -> @location(0) vec4<f32> {
+            return color * position.x;
+        }
+    ";
+
+    let premultiplied = premultiply_fragment_output(attributes).unwrap();
+
+    validate(&premultiplied);
+    assert!(premultiplied.contains("fs_main_straight(position, color)"));
+
+    // Type aliases, and entry points in block comments.
+    let alias = "
+        /* @fragment
+           fn fs_main() -> @location(0) vec4f { return vec4f(0.0); } */
+
+        @fragment /* fn fs_main(in: VertexOutput) */
+        fn fs_main(@location(0) color: vec4f) -> @location(0) vec4f {
+            return color;
+        }
+    ";
+
+    let premultiplied = premultiply_fragment_output(alias).unwrap();
+
+    validate(&premultiplied);
+    assert!(premultiplied.contains("fs_main_straight(color)"));
+
+    // A user sprite shader with several returns and unusual formatting.
+    let user = sprite_shader_from_fragment(
+        "@fragment
+        fn
+        fs_main ( in : VertexOutput )
+            -> @location(0)
+            vec4<f32>
+        {
+            let color = textureSample(t_diffuse, s_diffuse, in.tex_coords);
+
+            if (color.a < 0.5) {
+                return vec4<f32>(1.0, 0.0, 0.0, 0.5);
+            }
+
+            return color * in.color;
+        }",
+    );
+
+    validate(&user);
+
+    let premultiplied = premultiply_fragment_output(&user).unwrap();
+
+    validate(&premultiplied);
+    assert!(premultiplied.contains("fs_main_straight(in)"));
+
+    // Both returns stay in `fs_main_straight`, the wrapper adds one.
+    assert_eq!(
+        premultiplied.matches("return ").count(),
+        user.matches("return ").count() + 1
+    );
+
+    let errors = [
+        "@fragment fn fs_main() -> @location(0) vec3<f32> { return vec3(1.0); \
+         }",
+        "struct Out { @location(0) color: vec4<f32> }
+
+         @fragment fn fs_main() -> Out { return Out(vec4(1.0)); }",
+        "/* @fragment fn fs_main() -> @location(0) vec4f { return vec4f(1.0); \
+         } */",
+    ];
+
+    for source in errors {
+        assert!(premultiply_fragment_output(source).is_err(), "{source}");
+    }
+}
@@ -0,0 +1,79 @@
+use crate::*;
+
+/// Scales the low resolution image of pixel perfect mode up to the window by
+/// an integer factor, with black bars around it. Everything after this (post
+/// processing, egui) is drawn in the window's resolution.
+pub fn upscale_pixel_perfect(
+    c: &mut WgpuRenderer,
+    surface_view: &wgpu::TextureView,
+) {
+    let Some(texture) = c.pixel_perfect_texture.as_ref() else {
+        return;
+    };
+
+    span_with_timing!("upscale_pixel_perfect");
+
+    let (window_size, surface_format) = {
+        let config = c.context.config.borrow();
+        (vec2(config.width as f32, config.height as f32), config.format)
+    };
+
+    let Some(rect) = pixel_perfect_rect(window_size) else {
+        return;
+    };
+
+    let (target_view, format) =
+        if c.post_processing_effects.borrow().iter().any(|x| x.enabled) {
+            (&c.first_pass_texture.texture.view, c.render_texture_format)
+        } else {
+            (surface_view, surface_format)
+        };
+
+    let pipeline = c
+        .pipelines
+        .entry(format!("Pixel Perfect Upscale {:?}", format))
+        .or_insert_with(|| {
+            let shaders = &mut c.shaders.borrow_mut();
+
+            create_post_processing_pipeline(
+                "Pixel Perfect Upscale",
+                &c.context.device,
+                format,
+                &[&c.texture_layout, &c.camera_bind_group_layout],
+                create_engine_post_processing_shader!(shaders, "copy"),
+                wgpu::BlendState::REPLACE,
+            )
+        });
+
+    let mut encoder =
+        c.context.device.simple_encoder("Pixel Perfect Upscale Encoder");
+
+    {
+        let mut render_pass =
+            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Pixel Perfect Upscale"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: target_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+        let min = rect.min.clamp(Vec2::ZERO, window_size - 1.0);
+        let size = (rect.max.min(window_size) - min).max(Vec2::ONE);
+
+        render_pass.set_viewport(min.x, min.y, size.x, size.y, 0.0, 1.0);
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, &texture.bind_group, &[]);
+        render_pass.set_bind_group(1, &c.camera_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    c.context.queue.submit(std::iter::once(encoder.finish()));
+}
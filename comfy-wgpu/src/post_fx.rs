@@ -0,0 +1,246 @@
+use crate::*;
+
+/// Built-in post processing effects, added to the post processing stack with
+/// `add_post_fx`.
+///
+/// Every variant has a constructor with sensible defaults, e.g.
+/// `PostFx::vignette()`, which can be used as a starting point.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PostFx {
+    /// Splits the color channels, more towards the edges of the screen.
+    /// `amount` is the offset in pixels.
+    ChromaticAberration { amount: f32 },
+    /// Ordered dithering which quantizes every color channel to `levels`
+    /// values.
+    Dither { levels: u32 },
+    /// Animated noise over the whole screen with darkened edges. `strength`
+    /// and `vignette` are between 0 and 1, a `vignette` of 0 disables the
+    /// darkening.
+    FilmGrain { strength: f32, vignette: f32 },
+    /// Inverts all colors.
+    Invert,
+    /// Replaces every pixel with the closest color of the palette texture,
+    /// read from its first row left to right.
+    Palette { texture: TextureHandle },
+    /// Wobbles the whole image, `amount` is in UV units.
+    ScreenShake { amount: f32 },
+    /// Darkens the green and blue channels, `amount` is between 0 and 1.
+    Darken { amount: f32 },
+    /// Curved screen with scanlines, `scanlines` is between 0 and 1.
+    Crt { curvature: f32, scanlines: f32 },
+    /// Renders the screen in blocks of `pixel_size` pixels.
+    Pixelate { pixel_size: f32 },
+    /// Fades the edges of the screen into `color`.
+    Vignette { intensity: f32, radius: f32, color: Color },
+}
+
+impl PostFx {
+    pub fn chromatic_aberration() -> Self {
+        Self::ChromaticAberration { amount: 3.0 }
+    }
+
+    pub fn dither() -> Self {
+        Self::Dither { levels: 4 }
+    }
+
+    pub fn film_grain() -> Self {
+        Self::FilmGrain { strength: 0.2, vignette: 0.5 }
+    }
+
+    pub fn palette(texture: TextureHandle) -> Self {
+        Self::Palette { texture }
+    }
+
+    pub fn screen_shake() -> Self {
+        Self::ScreenShake { amount: 0.005 }
+    }
+
+    pub fn darken() -> Self {
+        Self::Darken { amount: 1.0 }
+    }
+
+    pub fn crt() -> Self {
+        Self::Crt { curvature: 0.1, scanlines: 0.3 }
+    }
+
+    pub fn pixelate() -> Self {
+        Self::Pixelate { pixel_size: 4.0 }
+    }
+
+    pub fn vignette() -> Self {
+        Self::Vignette { intensity: 0.6, radius: 0.75, color: BLACK }
+    }
+
+    /// Name of the effect in the post processing stack. Built-in effects
+    /// are prefixed with `post-fx/`, so they never replace or get confused
+    /// with user effects of the same name.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::ChromaticAberration { .. } => "post-fx/chromatic-aberration",
+            Self::Dither { .. } => "post-fx/dither",
+            Self::FilmGrain { .. } => "post-fx/film-grain",
+            Self::Invert => "post-fx/invert",
+            Self::Palette { .. } => "post-fx/palette",
+            Self::ScreenShake { .. } => "post-fx/screen-shake",
+            Self::Darken { .. } => "post-fx/darken",
+            Self::Crt { .. } => "post-fx/crt",
+            Self::Pixelate { .. } => "post-fx/pixelate",
+            Self::Vignette { .. } => "post-fx/vignette",
+        }
+    }
+
+    fn fragment_source(&self) -> &'static str {
+        match self {
+            Self::ChromaticAberration { .. } => {
+                engine_shader_source!("chromatic-aberration")
+            }
+            Self::Dither { .. } => engine_shader_source!("dither"),
+            Self::FilmGrain { .. } => engine_shader_source!("film-grain"),
+            Self::Invert => engine_shader_source!("invert"),
+            Self::Palette { .. } => engine_shader_source!("palette"),
+            Self::ScreenShake { .. } => engine_shader_source!("screen-shake"),
+            Self::Darken { .. } => engine_shader_source!("darken"),
+            Self::Crt { .. } => engine_shader_source!("crt"),
+            Self::Pixelate { .. } => engine_shader_source!("pixelate"),
+            Self::Vignette { .. } => engine_shader_source!("vignette"),
+        }
+    }
+
+    /// Uniform values of the effect, these are also used as the defaults of
+    /// the shader's uniforms.
+    pub fn uniforms(&self) -> Vec<(&'static str, Uniform)> {
+        match *self {
+            Self::ChromaticAberration { amount } => {
+                vec![("amount", amount.into())]
+            }
+            Self::Dither { levels } => vec![("levels", levels.into())],
+            Self::FilmGrain { strength, vignette } => {
+                vec![
+                    ("strength", strength.into()),
+                    ("vignette", vignette.into()),
+                ]
+            }
+            Self::Invert => vec![],
+            Self::Palette { texture } => {
+                vec![("palette", Uniform::Texture(texture))]
+            }
+            Self::ScreenShake { amount } => vec![("amount", amount.into())],
+            Self::Darken { amount } => vec![("amount", amount.into())],
+            Self::Crt { curvature, scanlines } => {
+                vec![
+                    ("curvature", curvature.into()),
+                    ("scanlines", scanlines.into()),
+                ]
+            }
+            Self::Pixelate { pixel_size } => {
+                vec![("pixel_size", pixel_size.into())]
+            }
+            Self::Vignette { intensity, radius, color } => {
+                vec![
+                    ("intensity", intensity.into()),
+                    ("radius", radius.into()),
+                    ("vignette_color", color.into()),
+                ]
+            }
+        }
+    }
+
+    fn uniform_defs(&self) -> Result<UniformDefs> {
+        self.uniforms()
+            .into_iter()
+            .map(|(name, value)| {
+                let def = match value {
+                    Uniform::F32(value) => UniformDef::F32(Some(value.0)),
+                    Uniform::U32(value) => UniformDef::U32(Some(value)),
+                    Uniform::Vec4(value) => {
+                        UniformDef::Vec4(Some(Vec4::from_array(
+                            value.map(|x| x.0),
+                        )))
+                    }
+                    Uniform::Texture(texture) => {
+                        UniformDef::Texture(Some(texture))
+                    }
+                    other => {
+                        bail!(
+                            "Unsupported PostFx uniform {}: {:?}",
+                            name,
+                            other
+                        )
+                    }
+                };
+
+                Ok((name.to_string(), def))
+            })
+            .collect()
+    }
+}
+
+/// Adds a built-in effect at the end of the post processing stack. If the
+/// effect is already in the stack its parameters are updated and it gets
+/// enabled, which means this can be called every frame.
+pub fn add_post_fx(renderer: &WgpuRenderer, fx: PostFx) {
+    let name = fx.name();
+
+    if !renderer.post_processing_effects.borrow().contains(name) {
+        let shader_id = fx.uniform_defs().and_then(|uniform_defs| {
+            create_shader(
+                &mut renderer.shaders.borrow_mut(),
+                name,
+                &post_process_shader_from_fragment(fx.fragment_source()),
+                uniform_defs,
+            )
+        });
+
+        match shader_id {
+            Ok(shader_id) => {
+                add_post_processing_effect(renderer, name, shader_id);
+            }
+            Err(err) => {
+                error!(
+                    "Failed to add post processing effect {}: {}",
+                    name, err
+                );
+                return;
+            }
+        }
+    }
+
+    let mut stack = renderer.post_processing();
+
+    stack.set_enabled(name, true);
+
+    for (uniform, value) in fx.uniforms() {
+        stack.set_uniform(name, uniform, value);
+    }
+}
+
+#[test]
+fn test_post_fx_shaders_are_valid() {
+    let effects = [
+        PostFx::chromatic_aberration(),
+        PostFx::dither(),
+        PostFx::film_grain(),
+        PostFx::Invert,
+        PostFx::palette(TextureHandle::from_path("1px")),
+        PostFx::screen_shake(),
+        PostFx::darken(),
+        PostFx::crt(),
+        PostFx::pixelate(),
+        PostFx::vignette(),
+    ];
+
+    for fx in effects {
+        let uniform_defs = fx.uniform_defs().unwrap();
+        let bindings = uniform_defs_to_bindings(&uniform_defs);
+
+        let source = build_shader_source(
+            &post_process_shader_from_fragment(fx.fragment_source()),
+            &bindings,
+            &uniform_defs,
+        );
+
+        if let Err(err) = check_shader_with_naga(&source) {
+            panic!("{} is invalid: {:?}", fx.name(), err);
+        }
+    }
+}
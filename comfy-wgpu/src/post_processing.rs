@@ -1,5 +1,7 @@
 use crate::*;
 
+/// Inserts a new effect at the given index, `-1` appends it at the end of the
+/// stack. An existing effect with the same name is replaced.
 pub fn insert_post_processing_effect(
     renderer: &WgpuRenderer,
     index: i32,
@@ -9,7 +11,7 @@ pub fn insert_post_processing_effect(
     let effect = PostProcessingEffect::new(
         name.to_string(),
         &renderer.context.device,
-        &[&renderer.context.texture_layout],
+        &[&renderer.context.texture_layout, &renderer.camera_bind_group_layout],
         &renderer.context.config.borrow(),
         renderer.render_texture_format,
         shader_id,
@@ -27,6 +29,423 @@ pub fn insert_post_processing_effect(
     }
 }
 
+/// Appends a new effect at the end of the post processing stack. Use
+/// `PostProcessingStack::move_before` and `PostProcessingStack::move_after`
+/// to change its position afterwards.
+pub fn add_post_processing_effect(
+    renderer: &WgpuRenderer,
+    name: &str,
+    shader_id: ShaderId,
+) {
+    insert_post_processing_effect(renderer, -1, name, shader_id);
+}
+
+fn effect_name(effect: &PostProcessingEffect) -> &str {
+    &effect.name
+}
+
+/// Key of the cached pipeline drawing the effect into a texture of the given
+/// format. The shader and its source are a part of the key, so that an effect
+/// replaced under the same name or a hot reloaded shader never reuse an old
+/// pipeline.
+pub fn post_processing_pipeline_key(
+    effect: &PostProcessingEffect,
+    format: wgpu::TextureFormat,
+) -> String {
+    pipeline_key(&effect.name, effect.id, effect.source_hash, format)
+}
+
+fn pipeline_key(
+    name: &str,
+    shader_id: ShaderId,
+    source_hash: u64,
+    format: wgpu::TextureFormat,
+) -> String {
+    format!("{}-{}-{:x}-{:?}", name, shader_id.0, source_hash, format)
+}
+
+/// Inserts the item at `index` (clamped to the length), removing an existing
+/// item with the same name first. Returns `true` if an item was replaced.
+fn insert_by_name<T>(
+    items: &mut Vec<T>,
+    index: usize,
+    item: T,
+    name_of: impl Fn(&T) -> &str,
+) -> bool {
+    let existing = items.iter().position(|x| name_of(x) == name_of(&item));
+    let mut index = index;
+
+    if let Some(existing) = existing {
+        items.remove(existing);
+
+        // Items after the replaced one moved back by one.
+        if existing < index {
+            index -= 1;
+        }
+    }
+
+    let index = index.min(items.len());
+    items.insert(index, item);
+
+    existing.is_some()
+}
+
+/// Moves the item `name` right before (`offset` 0) or after (`offset` 1) the
+/// item `other`. Returns `false` if either of them doesn't exist.
+fn move_by_name<T>(
+    items: &mut Vec<T>,
+    name: &str,
+    other: &str,
+    offset: usize,
+    name_of: impl Fn(&T) -> &str,
+) -> bool {
+    let position =
+        |items: &[T], name: &str| items.iter().position(|x| name_of(x) == name);
+
+    if name == other || position(items, other).is_none() {
+        return false;
+    }
+
+    let Some(from) = position(items, name) else {
+        return false;
+    };
+
+    let item = items.remove(from);
+    let index = position(items, other).unwrap() + offset;
+    items.insert(index, item);
+
+    true
+}
+
+/// Ordered list of post processing effects, applied from first to last.
+///
+/// Effects are addressed by their name. The engine always inserts a `copy`
+/// effect at the start of the stack which moves the rendered frame into the
+/// post processing chain.
+#[derive(Default)]
+pub struct PostProcessingStack {
+    effects: Vec<PostProcessingEffect>,
+}
+
+impl PostProcessingStack {
+    pub fn iter(&self) -> impl Iterator<Item = &PostProcessingEffect> {
+        self.effects.iter()
+    }
+
+    pub fn iter_mut(
+        &mut self,
+    ) -> impl Iterator<Item = &mut PostProcessingEffect> {
+        self.effects.iter_mut()
+    }
+
+    pub fn len(&self) -> usize {
+        self.effects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.effects.is_empty()
+    }
+
+    pub fn any_enabled(&self) -> bool {
+        self.effects.iter().any(|x| x.enabled)
+    }
+
+    /// Names of all effects in the order they're applied.
+    pub fn names(&self) -> Vec<&str> {
+        self.effects.iter().map(|x| x.name.as_str()).collect()
+    }
+
+    pub fn position(&self, name: &str) -> Option<usize> {
+        self.effects.iter().position(|x| x.name == name)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.position(name).is_some()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&PostProcessingEffect> {
+        self.effects.iter().find(|x| x.name == name)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut PostProcessingEffect> {
+        self.effects.iter_mut().find(|x| x.name == name)
+    }
+
+    pub fn push(&mut self, effect: PostProcessingEffect) {
+        self.insert(self.effects.len(), effect);
+    }
+
+    /// Inserts the effect at the given index, replacing an existing effect
+    /// with the same name.
+    pub fn insert(&mut self, index: usize, effect: PostProcessingEffect) {
+        let name = effect.name.clone();
+
+        if insert_by_name(&mut self.effects, index, effect, effect_name) {
+            warn!("Replacing post processing effect {}", name);
+        }
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<PostProcessingEffect> {
+        self.position(name).map(|index| self.effects.remove(index))
+    }
+
+    /// Moves the effect `name` right before the effect `other`. Returns
+    /// `false` if either of them doesn't exist.
+    pub fn move_before(&mut self, name: &str, other: &str) -> bool {
+        self.move_relative(name, other, 0)
+    }
+
+    /// Moves the effect `name` right after the effect `other`. Returns
+    /// `false` if either of them doesn't exist.
+    pub fn move_after(&mut self, name: &str, other: &str) -> bool {
+        self.move_relative(name, other, 1)
+    }
+
+    fn move_relative(
+        &mut self,
+        name: &str,
+        other: &str,
+        offset: usize,
+    ) -> bool {
+        move_by_name(&mut self.effects, name, other, offset, effect_name)
+    }
+
+    /// Returns `false` if there is no effect with the given name.
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) -> bool {
+        if let Some(effect) = self.get_mut(name) {
+            effect.enabled = enabled;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Sets a uniform of the effect's shader. The value is kept until changed,
+    /// uniforms which were never set use the default from their `UniformDef`.
+    pub fn set_uniform(
+        &mut self,
+        effect: &str,
+        name: impl Into<String>,
+        value: impl Into<Uniform>,
+    ) -> bool {
+        if let Some(effect) = self.get_mut(effect) {
+            effect.uniforms.values.insert(name.into(), value.into());
+            true
+        } else {
+            warn!(
+                "Setting a uniform on a missing post processing effect {}",
+                effect
+            );
+            false
+        }
+    }
+
+    pub fn set_uniform_f32(
+        &mut self,
+        effect: &str,
+        name: impl Into<String>,
+        value: f32,
+    ) -> bool {
+        self.set_uniform(effect, name, value)
+    }
+
+    pub fn set_uniform_vec2(
+        &mut self,
+        effect: &str,
+        name: impl Into<String>,
+        value: Vec2,
+    ) -> bool {
+        self.set_uniform(effect, name, value)
+    }
+
+    pub fn set_uniform_vec3(
+        &mut self,
+        effect: &str,
+        name: impl Into<String>,
+        value: Vec3,
+    ) -> bool {
+        self.set_uniform(effect, name, value)
+    }
+
+    pub fn set_uniform_vec4(
+        &mut self,
+        effect: &str,
+        name: impl Into<String>,
+        value: Vec4,
+    ) -> bool {
+        self.set_uniform(effect, name, value)
+    }
+
+    pub fn set_uniform_color(
+        &mut self,
+        effect: &str,
+        name: impl Into<String>,
+        value: Color,
+    ) -> bool {
+        self.set_uniform(effect, name, value)
+    }
+}
+
+/// Uniform values and GPU resources for the uniforms of a post processing
+/// shader, bound at `@group(2)`.
+#[derive(Default)]
+pub struct PostProcessingUniforms {
+    pub values: HashMap<String, Uniform>,
+    pub buffers: HashMap<String, wgpu::Buffer>,
+    pub layout: Option<wgpu::BindGroupLayout>,
+    pub bind_group: Option<wgpu::BindGroup>,
+}
+
+impl PostProcessingUniforms {
+    pub fn new(device: &wgpu::Device, shader: &Shader) -> Self {
+        if shader.bindings.is_empty() {
+            return Self::default();
+        }
+
+        let mut layout_entries = Vec::new();
+        let mut buffers = HashMap::new();
+
+        for (name, binding) in shader.bindings.iter() {
+            let uniform_def = shader.uniform_defs.get(name).unwrap();
+
+            if uniform_def.is_texture() {
+                layout_entries.push(wgpu::BindGroupLayoutEntry {
+                    binding: *binding,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float {
+                            filterable: true,
+                        },
+                    },
+                    count: None,
+                });
+
+                layout_entries.push(wgpu::BindGroupLayoutEntry {
+                    binding: *binding + 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(
+                        wgpu::SamplerBindingType::Filtering,
+                    ),
+                    count: None,
+                });
+
+                continue;
+            }
+
+            layout_entries.push(wgpu::BindGroupLayoutEntry {
+                binding: *binding,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            });
+
+            buffers.insert(
+                name.clone(),
+                device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some(&format!("Post Processing UB: {}", name)),
+                    size: uniform_def.size() as u64,
+                    usage: wgpu::BufferUsages::UNIFORM |
+                        wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                }),
+            );
+        }
+
+        let layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some(&format!("{} Uniform Layout", shader.name)),
+                entries: &layout_entries,
+            });
+
+        // Shaders with textures get their bind group in `texture_bind_group`
+        // since the textures can change at any time.
+        let bind_group = if shader.uniform_defs.values().any(|x| x.is_texture())
+        {
+            None
+        } else {
+            Some(create_user_bind_group(
+                device,
+                &layout,
+                shader,
+                &buffers,
+                &HashMap::new(),
+            ))
+        };
+
+        Self {
+            values: HashMap::new(),
+            buffers,
+            layout: Some(layout),
+            bind_group,
+        }
+    }
+
+    /// Creates a bind group with the current textures for shaders which have
+    /// texture uniforms, `None` otherwise.
+    pub fn texture_bind_group(
+        &self,
+        device: &wgpu::Device,
+        shader: &Shader,
+        textures: &TextureMap,
+        render_targets: &RenderTargetMap,
+    ) -> Option<wgpu::BindGroup> {
+        if !shader.uniform_defs.values().any(|x| x.is_texture()) {
+            return None;
+        }
+
+        Some(create_user_bind_group(
+            device,
+            self.layout.as_ref()?,
+            shader,
+            &self.buffers,
+            &resolve_user_textures(
+                shader,
+                Some(&self.values),
+                textures,
+                render_targets,
+            ),
+        ))
+    }
+
+    /// Uploads the current values, falling back to the defaults of the
+    /// shader's `UniformDef`s.
+    pub fn write(&self, queue: &wgpu::Queue, shader: &Shader) {
+        for (name, buffer) in self.buffers.iter() {
+            let Some(uniform_def) = shader.uniform_defs.get(name) else {
+                continue;
+            };
+
+            let Some(value) = self
+                .values
+                .get(name)
+                .cloned()
+                .or_else(|| uniform_def.default_value())
+            else {
+                error!(
+                    "No value or default for uniform {} in {}",
+                    name, shader.name
+                );
+                continue;
+            };
+
+            if let Some(data) = uniform_def.encode(&value) {
+                queue.write_buffer(buffer, 0, &data);
+            } else {
+                error!(
+                    "Uniform {} in {} got a value of the wrong type: {:?}",
+                    name, shader.name, value
+                );
+            }
+        }
+    }
+}
+
 pub struct PostProcessingEffect {
     pub id: ShaderId,
     pub name: String,
@@ -34,8 +453,11 @@ pub struct PostProcessingEffect {
     pub render_texture: Texture,
     pub bind_group: wgpu::BindGroup,
     pub pipeline: wgpu::RenderPipeline,
+    pub uniforms: PostProcessingUniforms,
+    /// Hash of the shader source the pipelines and uniforms were created
+    /// for, used to rebuild them after a hot reload.
+    pub source_hash: u64,
 }
-
 impl PostProcessingEffect {
     pub fn new_with_mip(
         name: String,
@@ -65,12 +487,17 @@ impl PostProcessingEffect {
         );
 
         let shader = shaders.get(shader_id).unwrap();
+        let uniforms = PostProcessingUniforms::new(device, shader);
+        let source_hash = default_hash(&shader.source);
+
+        let mut layouts = bind_group_layouts.to_vec();
+        layouts.extend(uniforms.layout.as_ref());
 
         let pipeline = create_post_processing_pipeline(
             &name,
             device,
             format,
-            bind_group_layouts,
+            &layouts,
             shader.clone(),
             blend,
         );
@@ -82,6 +509,8 @@ impl PostProcessingEffect {
             render_texture,
             bind_group,
             pipeline,
+            uniforms,
+            source_hash,
         }
     }
 
@@ -119,7 +548,9 @@ pub fn create_user_shader_module(
 ) -> wgpu::ShaderModule {
     let full_shader = format!(
         "{}{}{}",
-        CAMERA_BIND_GROUP_PREFIX, USER_SHADER_PREFIX, &shader.source
+        camera_bind_group_prefix(),
+        USER_SHADER_PREFIX,
+        &shader.source
     );
 
     let descriptor = wgpu::ShaderModuleDescriptor {
@@ -190,6 +621,7 @@ pub fn draw_post_processing_output(
     post_processing_pipeline: &wgpu::RenderPipeline,
     post_processing_bind_group: &wgpu::BindGroup,
     lighting_params_bind_group: &wgpu::BindGroup,
+    uniform_bind_group: Option<&wgpu::BindGroup>,
     target_view: &wgpu::TextureView,
     should_clear: bool,
     blend_constant: Option<f64>,
@@ -225,6 +657,10 @@ pub fn draw_post_processing_output(
     render_pass.set_bind_group(0, post_processing_bind_group, &[]);
     render_pass.set_bind_group(1, lighting_params_bind_group, &[]);
 
+    if let Some(uniform_bind_group) = uniform_bind_group {
+        render_pass.set_bind_group(2, uniform_bind_group, &[]);
+    }
+
     if let Some(blend) = blend_constant {
         render_pass.set_blend_constant(wgpu::Color {
             r: blend,
@@ -238,3 +674,71 @@ pub fn draw_post_processing_output(
 
     render_pass.pop_debug_group();
 }
+
+#[test]
+fn test_post_processing_order() {
+    fn name<'a>(x: &'a (&str, i32)) -> &'a str {
+        x.0
+    }
+
+    let names =
+        |items: &[(&'static str, i32)]| items.iter().map(|x| x.0).collect_vec();
+
+    let mut items = vec![];
+
+    assert!(!insert_by_name(&mut items, 0, ("copy", 0), name));
+    assert!(!insert_by_name(&mut items, 5, ("bloom", 0), name));
+    assert!(!insert_by_name(&mut items, 1, ("vignette", 0), name));
+    assert_eq!(names(&items), ["copy", "vignette", "bloom"]);
+
+    // The index of a replacement counts the old effect, whether it was
+    // before or after the index.
+    assert!(insert_by_name(&mut items, 3, ("copy", 1), name));
+    assert_eq!(names(&items), ["vignette", "bloom", "copy"]);
+    assert_eq!(items[2].1, 1);
+
+    assert!(insert_by_name(&mut items, 0, ("bloom", 1), name));
+    assert_eq!(names(&items), ["bloom", "vignette", "copy"]);
+
+    assert!(move_by_name(&mut items, "copy", "bloom", 0, name));
+    assert_eq!(names(&items), ["copy", "bloom", "vignette"]);
+
+    assert!(move_by_name(&mut items, "copy", "vignette", 1, name));
+    assert_eq!(names(&items), ["bloom", "vignette", "copy"]);
+
+    assert!(move_by_name(&mut items, "copy", "bloom", 1, name));
+    assert_eq!(names(&items), ["bloom", "copy", "vignette"]);
+
+    // Missing names and moving an effect relative to itself do nothing.
+    assert!(!move_by_name(&mut items, "missing", "bloom", 0, name));
+    assert!(!move_by_name(&mut items, "bloom", "missing", 1, name));
+    assert!(!move_by_name(&mut items, "bloom", "bloom", 1, name));
+    assert_eq!(names(&items), ["bloom", "copy", "vignette"]);
+}
+
+#[test]
+fn test_post_processing_pipeline_key_on_replace() {
+    fn name<'a>(x: &'a (&str, ShaderId, u64)) -> &'a str {
+        x.0
+    }
+
+    let format = wgpu::TextureFormat::Rgba16Float;
+    let key = |x: &(&str, ShaderId, u64)| pipeline_key(x.0, x.1, x.2, format);
+
+    let mut items = vec![("copy", ShaderId(1), 10), ("blur", ShaderId(2), 20)];
+    let old_key = key(&items[1]);
+
+    // Replacing the effect with another shader under the same name must not
+    // pick up the pipeline of the old one.
+    assert!(insert_by_name(&mut items, 1, ("blur", ShaderId(3), 30), name));
+    assert_ne!(key(&items[1]), old_key);
+
+    // Same for a hot reload which only changes the shader source.
+    assert_ne!(key(&("blur", ShaderId(3), 31)), key(&items[1]));
+
+    assert_eq!(key(&("blur", ShaderId(3), 30)), key(&items[1]));
+    assert_ne!(
+        key(&items[1]),
+        pipeline_key("blur", ShaderId(3), 30, wgpu::TextureFormat::Bgra8Unorm)
+    );
+}
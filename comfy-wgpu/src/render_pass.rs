@@ -5,11 +5,22 @@ pub struct MeshDrawData {
     pub texture: TextureHandle,
     pub shader: ShaderInstanceId,
     pub render_target: RenderTargetId,
+    pub clip: ClipId,
     pub data: Vec<Mesh>,
 }
 
 pub struct ParticleDrawData {
     pub blend_mode: BlendMode,
     pub texture: TextureHandle,
+    pub render_target: RenderTargetId,
+    pub clip: ClipId,
     pub data: Vec<ParticleDraw>,
 }
+
+pub struct SpriteInstanceDrawData {
+    pub blend_mode: BlendMode,
+    pub texture: TextureHandle,
+    pub render_target: RenderTargetId,
+    pub clip: ClipId,
+    pub data: Vec<SpriteInstance>,
+}
@@ -2,8 +2,6 @@ use std::sync::mpsc::{channel, Receiver, Sender};
 
 use crate::*;
 
-use image::Rgba;
-
 pub enum RenderPipeline<'a> {
     User(&'a UserRenderPipeline),
     Wgpu(&'a wgpu::RenderPipeline),
@@ -35,6 +33,47 @@ pub fn shader_to_wgpu(shader: &Shader) -> wgpu::ShaderModuleDescriptor<'_> {
     }
 }
 
+/// Entries of the camera bind group, the group 1 of all sprite and post
+/// processing shaders.
+pub fn camera_bind_group_entries<'a>(
+    camera_buffer: &'a wgpu::Buffer,
+    light_buffers: &'a LightBuffers,
+    global_lighting_params_buffer: &'a wgpu::Buffer,
+    frame_data_buffer: &'a wgpu::Buffer,
+    shadow_maps: &'a ShadowMaps,
+    light_cookies: &'a LightCookies,
+) -> Vec<wgpu::BindGroupEntry<'a>> {
+    let mut entries = vec![
+        wgpu::BindGroupEntry {
+            binding: 0,
+            resource: camera_buffer.as_entire_binding(),
+        },
+        wgpu::BindGroupEntry {
+            binding: 2,
+            resource: global_lighting_params_buffer.as_entire_binding(),
+        },
+        wgpu::BindGroupEntry {
+            binding: 5,
+            resource: frame_data_buffer.as_entire_binding(),
+        },
+        wgpu::BindGroupEntry {
+            binding: 6,
+            resource: wgpu::BindingResource::TextureView(&shadow_maps.view),
+        },
+        wgpu::BindGroupEntry {
+            binding: 9,
+            resource: wgpu::BindingResource::TextureView(&light_cookies.view),
+        },
+        wgpu::BindGroupEntry {
+            binding: 10,
+            resource: wgpu::BindingResource::Sampler(&light_cookies.sampler),
+        },
+    ];
+
+    entries.extend(light_buffers.bind_group_entries());
+    entries
+}
+
 // TODO: reducing number of Arc's?
 #[derive(Clone)]
 pub struct GraphicsContext {
@@ -50,6 +89,7 @@ pub struct GraphicsContext {
     pub texture_creator: Arc<AtomicRefCell<WgpuTextureCreator>>,
     // TODO: atomic refcell?
     pub textures: Arc<Mutex<TextureMap>>,
+    pub mipmap_generators: Arc<MipmapGenerators>,
 }
 
 pub struct WgpuRenderer {
@@ -70,6 +110,12 @@ pub struct WgpuRenderer {
     pub vertex_buffer: SizedBuffer,
     pub index_buffer: SizedBuffer,
 
+    pub instance_buffer: SizedBuffer,
+
+    pub tilemap_chunks: TilemapChunkMap,
+
+    pub bindless: Option<BindlessBatching>,
+
     pub quad_ubg: UniformBindGroup,
 
     pub texture_layout: Arc<wgpu::BindGroupLayout>,
@@ -79,16 +125,25 @@ pub struct WgpuRenderer {
     pub depth_texture: Arc<Texture>,
 
     pub first_pass_texture: BindableTexture,
+    /// Low resolution texture the world is drawn into in pixel perfect mode,
+    /// scaled up to the window before post processing.
+    pub pixel_perfect_texture: Option<BindableTexture>,
 
-    pub lights_buffer: wgpu::Buffer,
+    pub light_buffers: LightBuffers,
     pub global_lighting_params_buffer: wgpu::Buffer,
+    /// `FrameDataUniform` bound as `frame` for all sprite shaders.
+    pub frame_data_buffer: wgpu::Buffer,
 
     pub bloom: Bloom,
-    pub post_processing_effects: RefCell<Vec<PostProcessingEffect>>,
+    pub post_processing_effects: RefCell<PostProcessingStack>,
 
     pub render_texture_format: wgpu::TextureFormat,
 
     pub tonemapping_texture: BindableTexture,
+    pub color_grading: ColorGradingResources,
+    pub sprite_textures: SpriteTextureBindGroups,
+    pub shadow_maps: ShadowMaps,
+    pub light_cookies: LightCookies,
 
     pub camera_uniform: CameraUniform,
     pub camera_buffer: wgpu::Buffer,
@@ -98,6 +153,14 @@ pub struct WgpuRenderer {
     pub color: Color,
 
     pub enable_z_buffer: bool,
+    /// Set once the stencil buffer was cleared for the camera currently
+    /// being drawn.
+    pub stencil_cleared: bool,
+    /// Viewport of the camera currently being drawn.
+    pub camera_viewport: Viewport,
+    /// View projection matrix of the camera currently being drawn, which
+    /// world space clip rects are converted with.
+    pub camera_view_proj: Mat4,
 
     // TODO: remove this in favor of the context one
     pub texture_creator: Arc<AtomicRefCell<WgpuTextureCreator>>,
@@ -106,10 +169,17 @@ pub struct WgpuRenderer {
     pub thread_pool: rayon::ThreadPool,
     pub loaded_image_recv: Receiver<LoadedImage>,
     pub loaded_image_send: Sender<LoadedImage>,
+    /// `TextureSettings` changes which couldn't be applied yet because
+    /// their texture is still loading.
+    pub pending_texture_settings: HashMap<TextureHandle, TextureSettings>,
+    /// Textures set as normal maps which weren't loaded yet.
+    pub pending_normal_maps: HashSet<TextureHandle>,
 
     pub textures: Arc<Mutex<TextureMap>>,
 
     pub sprite_shader_id: ShaderId,
+    pub sprite_instanced_shader_id: ShaderId,
+    pub stencil_mask_shader_id: ShaderId,
     pub error_shader_id: ShaderId,
 
     pub screenshot_params: ScreenshotParams,
@@ -163,72 +233,82 @@ impl WgpuRenderer {
             },
         );
 
-        let camera_bind_group_layout = context.device.create_bind_group_layout(
-            &wgpu::BindGroupLayoutDescriptor {
-                entries: &[
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStages::VERTEX |
-                            wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Uniform,
-                            has_dynamic_offset: false,
-                            min_binding_size: None,
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 1,
-                        visibility: wgpu::ShaderStages::VERTEX |
-                            wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Uniform,
-                            has_dynamic_offset: false,
-                            min_binding_size: None,
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 2,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Uniform,
-                            has_dynamic_offset: false,
-                            min_binding_size: None,
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 3,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Texture {
-                            sample_type: wgpu::TextureSampleType::Float {
-                                filterable: true,
-                            },
-                            view_dimension: wgpu::TextureViewDimension::D2,
-                            multisampled: false,
-                        },
-                        count: None,
+        let light_buffers = LightBuffers::new(&context.device);
+
+        let camera_bind_group_layout_entries = [
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX |
+                    wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 5,
+                visibility: wgpu::ShaderStages::VERTEX |
+                    wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 6,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float {
+                        filterable: false,
                     },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 4,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Sampler(
-                            wgpu::SamplerBindingType::Filtering,
-                        ),
-                        count: None,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 9,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float {
+                        filterable: true,
                     },
-                ],
-                label: Some("camera_bind_group_layout"),
+                    view_dimension: wgpu::TextureViewDimension::D2Array,
+                    multisampled: false,
+                },
+                count: None,
             },
-        );
+            wgpu::BindGroupLayoutEntry {
+                binding: 10,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(
+                    wgpu::SamplerBindingType::Filtering,
+                ),
+                count: None,
+            },
+        ]
+        .into_iter()
+        .chain(light_buffers.layout_entries())
+        .collect_vec();
 
-        let lights_buffer = context.device.create_buffer_init(
-            &wgpu::util::BufferInitDescriptor {
-                label: Some("Lights Buffer"),
-                contents: bytemuck::cast_slice(&[LightUniform::default()]),
-                usage: wgpu::BufferUsages::UNIFORM |
-                    wgpu::BufferUsages::COPY_DST,
+        let camera_bind_group_layout = context.device.create_bind_group_layout(
+            &wgpu::BindGroupLayoutDescriptor {
+                entries: &camera_bind_group_layout_entries,
+                label: Some("camera_bind_group_layout"),
             },
         );
 
@@ -243,95 +323,31 @@ impl WgpuRenderer {
             },
         );
 
-        let lut_dim = 2;
-
-        let color_lut_texture = Texture::create_uninit(
-            &context.device,
-            lut_dim,
-            lut_dim,
-            Some("LUT"),
-        )
-        .unwrap();
-
-        let lut_image =
-            image::ImageBuffer::from_fn(lut_dim, lut_dim, |x, y| {
-                if x == 0 && y == 0 {
-                    Rgba([255, 0, 0, 255]) // Red
-                } else if x == 1 && y == 0 {
-                    Rgba([0, 255, 0, 255]) // Green
-                } else if x == 0 && y == 1 {
-                    Rgba([0, 0, 255, 255]) // Blue
-                } else {
-                    Rgba([255, 255, 255, 255]) // White
-                }
-            });
-
-        let lut_width = lut_image.width();
-        let lut_height = lut_image.height();
-
-        let color_lut_data: Vec<f32> = lut_image
-            .pixels()
-            .flat_map(|rgba| {
-                let r = rgba[0] as f32 / 255.0;
-                let g = rgba[1] as f32 / 255.0;
-                let b = rgba[2] as f32 / 255.0;
-                let a = rgba[3] as f32 / 255.0;
-                vec![r, g, b, a]
-            })
-            .collect();
-
-        context.queue.write_texture(
-            wgpu::ImageCopyTexture {
-                texture: &color_lut_texture.texture,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-                aspect: wgpu::TextureAspect::All,
-            },
-            bytemuck::cast_slice(color_lut_data.as_slice()),
-            wgpu::ImageDataLayout {
-                offset: 0,
-                bytes_per_row: Some(
-                    lut_width * 4 * std::mem::size_of::<f32>() as u32,
-                ),
-                rows_per_image: None,
-            },
-            wgpu::Extent3d {
-                width: lut_width,
-                height: lut_height,
-                depth_or_array_layers: 1,
+        let frame_data_buffer = context.device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Frame Data Buffer"),
+                contents: bytemuck::cast_slice(&[
+                    <FrameDataUniform as bytemuck::Zeroable>::zeroed(),
+                ]),
+                usage: wgpu::BufferUsages::UNIFORM |
+                    wgpu::BufferUsages::COPY_DST,
             },
         );
 
+        let shadow_maps = ShadowMaps::new(&context.device);
+        let light_cookies = LightCookies::new(&context.device, &context.queue);
+
         let camera_bind_group =
             context.device.create_bind_group(&wgpu::BindGroupDescriptor {
                 layout: &camera_bind_group_layout,
-                entries: &[
-                    wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: camera_buffer.as_entire_binding(),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 1,
-                        resource: lights_buffer.as_entire_binding(),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 2,
-                        resource: global_lighting_params_buffer
-                            .as_entire_binding(),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 3,
-                        resource: wgpu::BindingResource::TextureView(
-                            &color_lut_texture.view,
-                        ),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 4,
-                        resource: wgpu::BindingResource::Sampler(
-                            &color_lut_texture.sampler,
-                        ),
-                    },
-                ],
+                entries: &camera_bind_group_entries(
+                    &camera_buffer,
+                    &light_buffers,
+                    &global_lighting_params_buffer,
+                    &frame_data_buffer,
+                    &shadow_maps,
+                    &light_cookies,
+                ),
                 label: Some("camera_bind_group"),
             });
 
@@ -444,16 +460,21 @@ impl WgpuRenderer {
             BufferType::Index,
         );
 
+        let instance_buffer = SizedBuffer::new(
+            "Sprite Instance Buffer",
+            &context.device,
+            1024 * 1024,
+            BufferType::Instance,
+        );
 
         let (tx_texture, rx_texture) = channel::<LoadedImage>();
 
-        // TODO: resize
+        let pixel_perfect_texture = create_pixel_perfect_texture(&context);
 
-        let depth_texture = Texture::create_depth_texture(
-            &context.device,
-            &context.config.borrow(),
-            "Depth Texture",
-        );
+        let depth_texture = {
+            let config = context.config.borrow();
+            create_screen_depth_texture(&context.device, &config)
+        };
 
         let sprite_shader_id = create_shader(
             &mut shaders,
@@ -463,6 +484,34 @@ impl WgpuRenderer {
         )
         .unwrap();
 
+        let sprite_instanced_shader_id = create_shader(
+            &mut shaders,
+            "sprite-instanced",
+            &sprite_instanced_shader_from_fragment(engine_shader_source!(
+                "sprite"
+            )),
+            HashMap::new(),
+        )
+        .unwrap();
+
+        let stencil_mask_shader_id = create_shader(
+            &mut shaders,
+            "stencil-mask",
+            &sprite_shader_from_fragment(engine_shader_source!("stencil-mask")),
+            HashMap::new(),
+        )
+        .unwrap();
+
+        let bindless = if game_config().bindless_batching {
+            BindlessBatching::new(
+                &context.device,
+                &mut shaders,
+                engine_shader_source!("sprite"),
+            )
+        } else {
+            None
+        };
+
         let error_shader_id = create_shader(
             &mut shaders,
             "error",
@@ -478,10 +527,14 @@ impl WgpuRenderer {
             text: RefCell::new(TextRasterizer::new(context.clone())),
 
             sprite_shader_id,
+            sprite_instanced_shader_id,
+            stencil_mask_shader_id,
             error_shader_id,
 
             loaded_image_recv: rx_texture,
             loaded_image_send: tx_texture,
+            pending_texture_settings: HashMap::default(),
+            pending_normal_maps: HashSet::default(),
 
             depth_texture: Arc::new(depth_texture),
 
@@ -495,24 +548,47 @@ impl WgpuRenderer {
 
             vertex_buffer,
             index_buffer,
+            instance_buffer,
 
-            post_processing_effects: RefCell::new(Vec::new()),
+            tilemap_chunks: HashMap::new(),
+
+            bindless,
+
+            stencil_cleared: false,
+            camera_viewport: Viewport::FULL,
+            camera_view_proj: camera_uniform.view_proj(),
+
+            post_processing_effects: RefCell::new(
+                PostProcessingStack::default(),
+            ),
             bloom,
 
             egui_winit,
             egui_render_routine: RefCell::new(egui_render_routine),
 
             first_pass_texture,
+            pixel_perfect_texture,
 
-            lights_buffer,
+            light_buffers,
 
             quad_ubg,
 
             global_lighting_params_buffer,
+            frame_data_buffer,
 
             texture_layout: context.texture_layout.clone(),
 
             tonemapping_texture,
+            color_grading: ColorGradingResources::new(
+                &context.device,
+                &context.queue,
+            ),
+            sprite_textures: SpriteTextureBindGroups::new(
+                &context.device,
+                &context.queue,
+            ),
+            shadow_maps,
+            light_cookies,
 
             camera_uniform,
             camera_buffer,
@@ -554,6 +630,45 @@ impl WgpuRenderer {
         &self.window
     }
 
+    /// Gives access to the post processing stack for reordering, toggling
+    /// and setting uniforms of effects.
+    pub fn post_processing(
+        &self,
+    ) -> std::cell::RefMut<'_, PostProcessingStack> {
+        self.post_processing_effects.borrow_mut()
+    }
+
+    /// Rebuilds the uniforms of effects whose shader was hot reloaded,
+    /// keeping the uniform values that were set, and drops their old
+    /// pipelines.
+    fn reload_post_processing_effects(&mut self) {
+        let shaders = self.shaders.borrow();
+        let surface_texture_format = self.context.config.borrow().format;
+
+        for effect in self.post_processing_effects.borrow_mut().iter_mut() {
+            let Some(shader) = shaders.get(effect.id) else {
+                continue;
+            };
+
+            let source_hash = default_hash(&shader.source);
+
+            if effect.source_hash == source_hash {
+                continue;
+            }
+
+            for format in [self.render_texture_format, surface_texture_format] {
+                self.pipelines
+                    .remove(&post_processing_pipeline_key(effect, format));
+            }
+
+            let values = std::mem::take(&mut effect.uniforms.values);
+            effect.uniforms =
+                PostProcessingUniforms::new(&self.context.device, shader);
+            effect.uniforms.values = values;
+            effect.source_hash = source_hash;
+        }
+    }
+
     pub fn render_post_processing(
         &mut self,
         screen_view: &wgpu::TextureView,
@@ -578,8 +693,12 @@ impl WgpuRenderer {
         let post_processing_effects = self.post_processing_effects.borrow();
         let surface_texture_format = self.context.config.borrow().format;
 
+        let grading = color_grading();
+        let grading_enabled = grading.is_active();
+
         let (last_effect_view, last_effect_format) = if game_config
-            .tonemapping_enabled
+            .tonemapping_enabled ||
+            grading_enabled
         {
             (&self.tonemapping_texture.texture.view, self.render_texture_format)
         } else {
@@ -589,6 +708,8 @@ impl WgpuRenderer {
         let enabled_effects =
             post_processing_effects.iter().filter(|x| x.enabled).collect_vec();
 
+        let shaders = self.shaders.borrow();
+
         for (i, effect) in enabled_effects.iter().enumerate() {
             let (output_texture_view, output_texture_format) =
                 if i == enabled_effects.len() - 1 {
@@ -597,55 +718,65 @@ impl WgpuRenderer {
                     (&effect.render_texture.view, self.render_texture_format)
                 };
 
+            let Some(shader) = shaders.get(effect.id) else {
+                warn!(
+                    "NO SHADER FOR EFFECT: {} ... {}",
+                    effect.name, effect.id
+                );
+                continue;
+            };
+
             let pipeline_key =
-                format!("{}-{:?}", effect.name, output_texture_format);
+                post_processing_pipeline_key(effect, output_texture_format);
+
+            let pipeline =
+                self.pipelines.entry(pipeline_key).or_insert_with(|| {
+                    info!("Loading EFFECT: {}", effect.name);
 
-            let maybe_pipeline = if self.pipelines.contains_key(&pipeline_key) {
-                Some(self.pipelines.get(&pipeline_key).unwrap())
-            } else {
-                info!("Loading EFFECT: {}", effect.name);
+                    let mut layouts = vec![
+                        &*self.texture_layout,
+                        &self.camera_bind_group_layout,
+                    ];
+                    layouts.extend(effect.uniforms.layout.as_ref());
 
-                if let Some(shader) = self.shaders.borrow().get(effect.id) {
-                    let pipeline = create_post_processing_pipeline(
+                    create_post_processing_pipeline(
                         &effect.name,
                         &self.context.device,
                         output_texture_format,
-                        &[&self.texture_layout, &self.camera_bind_group_layout],
+                        &layouts,
                         shader.clone(),
-                        // &effect.shader,
                         wgpu::BlendState::REPLACE,
-                    );
+                    )
+                });
 
-                    self.pipelines.insert(pipeline_key.clone(), pipeline);
-                    self.pipelines.get(&pipeline_key)
-                } else {
-                    warn!(
-                        "NO SHADER FOR EFFECT: {} ... {}",
-                        effect.name, effect.id
-                    );
-                    None
-                }
-            };
+            effect.uniforms.write(&self.context.queue, shader);
 
-            if let Some(pipeline) = maybe_pipeline {
-                draw_post_processing_output(
-                    &effect.name,
-                    &mut encoder,
-                    pipeline,
-                    input_bind_group,
-                    &self.camera_bind_group,
-                    // &effect.bind_group,
-                    output_texture_view,
-                    true,
-                    None,
-                );
+            let texture_bind_group = effect.uniforms.texture_bind_group(
+                &self.context.device,
+                shader,
+                &self.textures.lock(),
+                &self.render_targets.borrow(),
+            );
 
-                input_bind_group = &effect.bind_group;
-            } else {
-                error!("Missing pipeline for {}", effect.name);
-            }
+            draw_post_processing_output(
+                &effect.name,
+                &mut encoder,
+                pipeline,
+                input_bind_group,
+                &self.camera_bind_group,
+                texture_bind_group
+                    .as_ref()
+                    .or(effect.uniforms.bind_group.as_ref()),
+                output_texture_view,
+                true,
+                None,
+            );
+
+            input_bind_group = &effect.bind_group;
         }
 
+        drop(shaders);
+
         if game_config.bloom_enabled {
             self.bloom.blit_final(
                 &mut encoder,
@@ -657,34 +788,59 @@ impl WgpuRenderer {
             );
         }
 
-        if game_config.tonemapping_enabled {
-            let tonemapping_pipeline = self
-                .pipelines
-                .entry("tonemapping".into())
-                .or_insert_with(|| {
+        // The last pass tonemaps and color grades, or only color grades when
+        // tonemapping is disabled.
+        if game_config.tonemapping_enabled || grading_enabled {
+            let (name, label, fragment_source) =
+                if game_config.tonemapping_enabled {
+                    (
+                        "tonemapping",
+                        "Tonemapping",
+                        engine_shader_source!("tonemapping"),
+                    )
+                } else {
+                    (
+                        "color-grading",
+                        "Color Grading",
+                        engine_shader_source!("color-grading"),
+                    )
+                };
+
+            let tonemapping_pipeline =
+                self.pipelines.entry(name.into()).or_insert_with(|| {
                     // TODO: texture format?
                     let shaders = &mut self.shaders.borrow_mut();
 
                     create_post_processing_pipeline(
-                        "Tonemapping",
+                        label,
                         &self.context.device,
                         self.context.config.borrow().format,
-                        &[&self.texture_layout, &self.camera_bind_group_layout],
-                        create_engine_post_processing_shader!(
+                        &[
+                            &self.texture_layout,
+                            &self.camera_bind_group_layout,
+                            &self.color_grading.layout,
+                        ],
+                        create_color_grading_shader(
                             shaders,
-                            "tonemapping"
+                            name,
+                            fragment_source,
                         ),
                         wgpu::BlendState::REPLACE,
                     )
                 });
 
+            let color_grading_bind_group = self.color_grading.bind_group(
+                &self.context.device,
+                &self.context.queue,
+                &grading,
+            );
 
             {
                 let should_clear = false;
 
                 let mut render_pass =
                     encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                        label: Some("Tonemapping"),
+                        label: Some(label),
                         color_attachments: &[Some(
                             wgpu::RenderPassColorAttachment {
                                 view: screen_view,
@@ -716,6 +872,7 @@ impl WgpuRenderer {
                     &[],
                 );
                 render_pass.set_bind_group(1, &self.camera_bind_group, &[]);
+                render_pass.set_bind_group(2, &color_grading_bind_group, &[]);
 
                 render_pass.draw(0..3, 0..1);
             }
@@ -807,16 +964,34 @@ impl WgpuRenderer {
                 let context = self.context.clone();
                 let textures = self.textures.clone();
                 let layout = self.texture_layout.clone();
+                let settings = texture_settings(loaded_image.handle);
+                let is_normal_map = is_normal_map(loaded_image.handle);
 
                 let load_image_texture = move || {
-                    let texture = Texture::from_image(
-                        &context.device,
-                        &context.queue,
-                        &loaded_image.image,
-                        Some(&loaded_image.path),
-                        false,
-                    )
-                    .unwrap();
+                    let texture = match settings {
+                        // Normal maps can't be sRGB, which textures with
+                        // settings (and their mipmaps) are.
+                        Some(settings) if !is_normal_map => {
+                            Texture::from_image_with_settings(
+                                &context.device,
+                                &context.queue,
+                                &context.mipmap_generators,
+                                &loaded_image.image,
+                                Some(&loaded_image.path),
+                                settings,
+                            )
+                        }
+                        _ => {
+                            Texture::from_image(
+                                &context.device,
+                                &context.queue,
+                                &loaded_image.image,
+                                Some(&loaded_image.path),
+                                is_normal_map,
+                            )
+                            .unwrap()
+                        }
+                    };
 
                     let bind_group = context.device.simple_bind_group(
                         Some(&format!("{}_bind_group", loaded_image.path)),
@@ -836,6 +1011,45 @@ impl WgpuRenderer {
                 #[cfg(not(target_arch = "wasm32"))]
                 self.thread_pool.spawn(load_image_texture);
             }
+
+            // Textures still loading on the thread pool may have been
+            // created with older settings, which are re-applied once the
+            // texture is inserted.
+            self.pending_texture_settings
+                .extend(consume_texture_settings_changes());
+
+            if !self.pending_texture_settings.is_empty() {
+                let mut textures = self.textures.lock();
+
+                self.pending_texture_settings.retain(|handle, settings| {
+                    !apply_texture_settings(
+                        &self.context,
+                        &mut textures,
+                        *handle,
+                        *settings,
+                    )
+                });
+            }
+
+            self.pending_normal_maps.extend(consume_normal_map_changes());
+
+            if !self.pending_normal_maps.is_empty() {
+                let mut textures = self.textures.lock();
+
+                self.pending_normal_maps.retain(|handle| {
+                    !ensure_linear_normal_map(
+                        &self.context,
+                        &mut textures,
+                        *handle,
+                    )
+                });
+            }
+        }
+
+        for (id, params) in consume_render_target_creations() {
+            self.render_targets
+                .borrow_mut()
+                .insert(id, create_user_render_target(&self.context, &params));
         }
 
         if changed_recording_mode {
@@ -873,12 +1087,26 @@ impl WgpuRenderer {
         }
 
         #[cfg(not(any(feature = "ci-release", target_arch = "wasm32")))]
-        maybe_reload_shaders(&mut self.shaders.borrow_mut());
+        if maybe_reload_shaders(&mut self.shaders.borrow_mut()) {
+            self.reload_post_processing_effects();
+        }
 
         self.camera_uniform.update_view_proj(&main_camera());
 
         params.config.lighting.time = get_time() as f32;
 
+        self.color_grading
+            .upload_new_luts(&self.context.device, &self.context.queue);
+
+        {
+            let grading = color_grading();
+            let lighting = &mut params.config.lighting;
+
+            lighting.use_lut = grading.is_active() as u32;
+            lighting.lut_weight = grading.weight;
+            lighting.lut_crossfade = grading.crossfade;
+        }
+
         {
             let camera = main_camera();
             params.config.lighting.chromatic_aberration =
@@ -898,27 +1126,145 @@ impl WgpuRenderer {
             bytemuck::cast_slice(&[self.camera_uniform]),
         );
 
-        let mut light_uniform = LightUniform::default();
+        let frame_data = FrameDataUniform {
+            projection: params.projection.to_cols_array(),
+            mouse_world: mouse_world().to_array(),
+            mouse_screen: mouse_screen().to_array(),
+            time: params.frame.time,
+            delta: params.frame.delta,
+            frame: params.frame.frame as i32,
+            fps: get_fps() as f32,
+            aspect_ratio: params.aspect_ratio,
+            _padding: [0.0; 3],
+        };
 
-        for (i, light) in params.lights.iter().enumerate() {
-            if i >= 128 {
-                break;
-            }
+        self.context.queue.write_buffer(
+            &self.frame_data_buffer,
+            0,
+            bytemuck::cast_slice(&[frame_data]),
+        );
+
+        let mut lights = params.lights.clone();
+
+        if !storage_lights_enabled() && lights.len() > MAX_UNIFORM_LIGHTS {
+            let center = main_camera().center;
+
+            lights.sort_by(|a, b| {
+                let a = Vec2::from(a.world_position).distance_squared(center);
+                let b = Vec2::from(b.world_position).distance_squared(center);
 
-            light_uniform.lights[i] = *light;
-            light_uniform.num_lights += 1;
+                a.total_cmp(&b)
+            });
+
+            lights.truncate(MAX_UNIFORM_LIGHTS);
         }
 
-        self.context.queue.write_buffer(
-            &self.lights_buffer,
-            0,
-            bytemuck::cast_slice(&[light_uniform]),
+        self.shadow_maps.update(
+            &self.context.queue,
+            &mut lights,
+            &params.occluders,
+        );
+
+        if self.light_buffers.update(
+            &self.context.device,
+            &self.context.queue,
+            &lights,
+            self.visible_world_bounds(),
+        ) {
+            self.camera_bind_group =
+                Arc::new(self.context.device.create_bind_group(
+                    &wgpu::BindGroupDescriptor {
+                        layout: &self.camera_bind_group_layout,
+                        entries: &camera_bind_group_entries(
+                            &self.camera_buffer,
+                            &self.light_buffers,
+                            &self.global_lighting_params_buffer,
+                            &self.frame_data_buffer,
+                            &self.shadow_maps,
+                            &self.light_cookies,
+                        ),
+                        label: Some("camera_bind_group"),
+                    },
+                ));
+
+            // Bloom binds the camera bind group for the lighting params.
+            self.bloom.lighting_params = self.camera_bind_group.clone();
+        }
+
+        // Cookie indices are assigned while uploading the lights.
+        self.light_cookies.update(
+            &self.context.device,
+            &self.context.queue,
+            &self.context.mipmap_generators,
+            &self.textures.lock(),
         );
     }
 
+    /// World space area seen by the main camera, the cameras added with
+    /// `add_camera` and the cameras of render targets in this frame.
+    fn visible_world_bounds(&self) -> AABB {
+        {
+            let main = main_camera();
+
+            // A custom matrix could be a perspective projection, which sees
+            // more than its orthographic bounds.
+            if main.matrix_fn.is_some() && main.use_matrix_fn {
+                return AABB::new(
+                    splat(f32::NEG_INFINITY),
+                    splat(f32::INFINITY),
+                );
+            }
+        }
+
+        let screen_size = {
+            let config = self.context.config.borrow();
+            render_resolution(vec2(config.width as f32, config.height as f32))
+        };
+
+        let cameras = cameras()
+            .into_iter()
+            .filter(|(_, camera)| camera.enabled)
+            .map(|(_, camera)| {
+                camera.build_view_projection_matrix_in(screen_size)
+            });
+
+        let render_target_cameras =
+            render_targets().into_iter().filter_map(|(_, params)| {
+                params.camera.map(|camera| {
+                    Camera { viewport: Viewport::FULL, ..camera }
+                        .build_view_projection_matrix_in(params.size.as_vec2())
+                })
+            });
+
+        std::iter::once(self.camera_uniform.view_proj())
+            .chain(cameras)
+            .chain(render_target_cameras)
+            .map(orthographic_world_bounds)
+            .reduce(|a, b| AABB::new(a.min.min(b.min), a.max.max(b.max)))
+            .unwrap()
+    }
+
     pub fn draw(&mut self, params: DrawParams, egui: &egui::Context) {
         span_with_timing!("render");
 
+        // Changing the pixel perfect resolution recreates the textures that
+        // follow it, same as resizing the window.
+        let pixel_perfect_size = self.pixel_perfect_texture.as_ref().map(|x| {
+            let size = x.texture.texture.size();
+            uvec2(size.width, size.height)
+        });
+
+        if pixel_perfect_size !=
+            game_config().pixel_perfect.map(|x| x.resolution)
+        {
+            let size = {
+                let config = self.context.config.borrow();
+                uvec2(config.width, config.height)
+            };
+
+            self.resize(size);
+        }
+
         let output = {
             let _span = span!("get current surface");
 
@@ -945,6 +1291,8 @@ impl WgpuRenderer {
             self.error_shader_id,
         );
 
+        upscale_pixel_perfect(self, &surface_view);
+
         self.render_post_processing(&surface_view, &config);
         self.render_egui(&surface_view, egui);
 
@@ -1027,6 +1375,28 @@ impl WgpuRenderer {
             config.height = size.height;
 
             self.context.surface.configure(&self.context.device, &config);
+
+            // All of these are attachments of the same render passes as the
+            // surface, so they have to follow its size (or the current pixel
+            // perfect resolution).
+            self.pixel_perfect_texture =
+                create_pixel_perfect_texture(&self.context);
+
+            self.depth_texture = Arc::new(create_screen_depth_texture(
+                &self.context.device,
+                &config,
+            ));
+
+            self.first_pass_texture = BindableTexture::new(
+                &self.context.device,
+                &self.context.texture_layout,
+                &TextureCreationParams {
+                    label: Some("First Pass Texture"),
+                    width: config.width,
+                    height: config.height,
+                    ..Default::default()
+                },
+            );
         }
 
         self.egui_render_routine.borrow_mut().resize(
@@ -1049,6 +1419,40 @@ impl WgpuRenderer {
     pub fn end_frame(&mut self) {}
 }
 
+/// Low resolution texture the world is drawn into in pixel perfect mode,
+/// before it's upscaled to the screen. `None` when the mode is disabled.
+fn create_pixel_perfect_texture(
+    context: &GraphicsContext,
+) -> Option<BindableTexture> {
+    game_config().pixel_perfect.map(|config| {
+        BindableTexture::new(
+            &context.device,
+            &context.texture_layout,
+            &TextureCreationParams {
+                label: Some("Pixel Perfect Texture"),
+                width: config.resolution.x,
+                height: config.resolution.y,
+                filter_mode: wgpu::FilterMode::Nearest,
+                ..Default::default()
+            },
+        )
+    })
+}
+
+/// Depth texture used when drawing to the screen. The world is drawn in the
+/// low resolution in pixel perfect mode, so the depth buffer has to match it.
+fn create_screen_depth_texture(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+) -> Texture {
+    let size = match game_config().pixel_perfect {
+        Some(pixel_perfect) => pixel_perfect.resolution,
+        None => uvec2(config.width, config.height),
+    };
+
+    Texture::create_depth_texture_with_size(device, size, "Depth Texture")
+}
+
 pub fn depth_stencil_attachment(
     enabled: bool,
     view: &wgpu::TextureView,
@@ -1070,3 +1474,211 @@ pub fn depth_stencil_attachment(
         None
     }
 }
+
+/// Like `depth_stencil_attachment`, but also attaches the stencil buffer
+/// for draws using a stencil mask.
+pub fn clip_depth_stencil_attachment(
+    enable_z_buffer: bool,
+    view: &wgpu::TextureView,
+    is_first: bool,
+    stencil: StencilMode,
+    clear_stencil: bool,
+) -> Option<wgpu::RenderPassDepthStencilAttachment<'_>> {
+    if stencil == StencilMode::None {
+        return depth_stencil_attachment(enable_z_buffer, view, is_first);
+    }
+
+    let clear_depth =
+        if is_first { wgpu::LoadOp::Clear(1.0) } else { wgpu::LoadOp::Load };
+
+    let clear_stencil =
+        if clear_stencil { wgpu::LoadOp::Clear(0) } else { wgpu::LoadOp::Load };
+
+    Some(wgpu::RenderPassDepthStencilAttachment {
+        view,
+        depth_ops: enable_z_buffer.then_some(wgpu::Operations {
+            load: clear_depth,
+            store: wgpu::StoreOp::Store,
+        }),
+        stencil_ops: Some(wgpu::Operations {
+            load: clear_stencil,
+            store: wgpu::StoreOp::Store,
+        }),
+    })
+}
+
+/// Returns `true` for the first pass using a stencil mask in the frame,
+/// which has to clear the stencil buffer.
+pub fn take_stencil_clear(c: &mut WgpuRenderer, stencil: StencilMode) -> bool {
+    let clear = stencil != StencilMode::None && !c.stencil_cleared;
+    c.stencil_cleared |= clear;
+    clear
+}
+
+/// Clears the depth and stencil buffers of the render target while keeping
+/// its color, so that a camera doesn't depth test against or get masked by
+/// what was drawn before it.
+pub fn clear_depth_stencil(
+    c: &mut WgpuRenderer,
+    render_target: RenderTargetId,
+) {
+    let mut encoder = c.context.device.simple_encoder("Depth Clear Encoder");
+
+    {
+        let render_targets = c.render_targets.borrow();
+
+        let depth_view = if render_target.0 > 0 {
+            &render_targets
+                .get(&render_target)
+                .expect("user render target must exist when used")
+                .depth_texture
+                .view
+        } else {
+            &c.depth_texture.view
+        };
+
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Depth Clear Pass"),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(
+                wgpu::RenderPassDepthStencilAttachment {
+                    view: depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                },
+            ),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+    }
+
+    c.context.queue.submit(std::iter::once(encoder.finish()));
+    c.stencil_cleared = true;
+}
+
+/// Size of the texture the given render target draws into.
+pub fn render_target_size(
+    c: &WgpuRenderer,
+    render_target: RenderTargetId,
+) -> UVec2 {
+    if render_target.0 > 0 {
+        c.render_targets
+            .borrow()
+            .get(&render_target)
+            .map(|x| x.creation_params.size)
+            .unwrap_or(UVec2::ONE)
+    } else if let Some(texture) = &c.pixel_perfect_texture {
+        let size = texture.texture.texture.size();
+        uvec2(size.width, size.height)
+    } else if c.post_processing_effects.borrow().iter().any(|x| x.enabled) {
+        let size = c.first_pass_texture.texture.texture.size();
+        uvec2(size.width, size.height)
+    } else {
+        let config = c.context.config.borrow();
+        uvec2(config.width, config.height)
+    }
+}
+
+/// Color and depth/stencil views of the image the given render target draws
+/// into. Every render target has a depth texture of its own size.
+pub fn render_target_views<'a>(
+    c: &'a WgpuRenderer,
+    render_targets: &'a RenderTargetMap,
+    surface_view: &'a wgpu::TextureView,
+    render_target: RenderTargetId,
+) -> (&'a wgpu::TextureView, &'a wgpu::TextureView) {
+    if render_target.0 > 0 {
+        let target = render_targets
+            .get(&render_target)
+            .expect("user render target must exist when used");
+
+        (&target.view, &target.depth_texture.view)
+    } else if let Some(texture) = &c.pixel_perfect_texture {
+        (&texture.texture.view, &c.depth_texture.view)
+    } else if c.post_processing_effects.borrow().iter().any(|x| x.enabled) {
+        (&c.first_pass_texture.texture.view, &c.depth_texture.view)
+    } else {
+        (surface_view, &c.depth_texture.view)
+    }
+}
+
+/// Restricts the render pass to the viewport of the camera currently being
+/// drawn. Draws into user render targets always cover the whole target.
+pub fn apply_camera_viewport(
+    render_pass: &mut wgpu::RenderPass,
+    viewport: Viewport,
+    render_target: RenderTargetId,
+    target_size: UVec2,
+) {
+    if viewport == Viewport::FULL || render_target.0 > 0 {
+        return;
+    }
+
+    let target_size = target_size.as_vec2();
+    let rect = viewport.to_pixels(target_size);
+
+    let min = rect.min.clamp(Vec2::ZERO, target_size - 1.0);
+    let size = (rect.max.min(target_size) - min).max(Vec2::ONE);
+
+    render_pass.set_viewport(min.x, min.y, size.x, size.y, 0.0, 1.0);
+}
+
+/// Format of the texture the given render target draws into, pipelines
+/// drawing into it have to use the same format.
+pub fn render_target_format(
+    c: &WgpuRenderer,
+    render_target: RenderTargetId,
+) -> wgpu::TextureFormat {
+    if render_target.0 > 0 {
+        c.render_targets
+            .borrow()
+            .get(&render_target)
+            .map(|x| render_target_format_to_wgpu(x.creation_params.format))
+            .unwrap_or(wgpu::TextureFormat::Rgba16Float)
+    } else {
+        wgpu::TextureFormat::Rgba16Float
+    }
+}
+
+/// Sets the scissor rect and stencil reference of the clip state on the
+/// render pass. World clip rects are converted with the camera currently
+/// being drawn, screen clip rects are scaled to the target size and offset
+/// by the camera's viewport.
+pub fn apply_clip_state(
+    render_pass: &mut wgpu::RenderPass,
+    clip: &ClipState,
+    target_size: UVec2,
+    view_proj: Mat4,
+    viewport: Viewport,
+) {
+    let target = target_size.as_vec2();
+    let viewport = viewport.to_pixels(target);
+    let window_size = vec2(screen_width(), screen_height());
+    let screen_scale = target / render_resolution(window_size);
+
+    // Screen rects are relative to the camera's viewport, so that the same
+    // rect clips the same part of every split screen view.
+    if let Some(rect) = clip.target_rect(view_proj, viewport, |point| {
+        viewport.min + window_to_render(point, window_size) * screen_scale
+    }) {
+        let min = rect.min.round().as_uvec2().min(target_size);
+        let max = rect.max.round().as_uvec2().min(target_size).max(min);
+
+        render_pass.set_scissor_rect(
+            min.x,
+            min.y,
+            max.x - min.x,
+            max.y - min.y,
+        );
+    }
+
+    if clip.stencil != StencilMode::None {
+        render_pass.set_stencil_reference(clip.mask as u32);
+    }
+}
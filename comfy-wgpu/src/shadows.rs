@@ -0,0 +1,91 @@
+use crate::*;
+
+/// Shadow maps of the shadow casting lights, one row of
+/// `SHADOW_MAP_RESOLUTION` distances per light.
+pub struct ShadowMaps {
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+}
+
+impl ShadowMaps {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Shadow Maps"),
+            size: wgpu::Extent3d {
+                width: SHADOW_MAP_RESOLUTION as u32,
+                height: MAX_SHADOW_LIGHTS as u32,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R32Float,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING |
+                wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        Self { texture, view }
+    }
+
+    /// Computes the shadow maps of the first `MAX_SHADOW_LIGHTS` lights with
+    /// `casts_shadows` and sets the `shadow_index` of every light.
+    pub fn update(
+        &self,
+        queue: &wgpu::Queue,
+        lights: &mut [Light],
+        occluders: &[Occluder],
+    ) {
+        let _span = span!("shadow maps");
+
+        let mut data = Vec::new();
+        let mut rows = 0;
+
+        for light in lights.iter_mut() {
+            light.shadow_index = -1;
+
+            if light.casts_shadows == 0 ||
+                occluders.is_empty() ||
+                rows >= MAX_SHADOW_LIGHTS
+            {
+                continue;
+            }
+
+            data.extend(compute_shadow_map(
+                Vec2::from(light.world_position),
+                light.extent(),
+                occluders,
+                SHADOW_MAP_RESOLUTION,
+            ));
+
+            light.shadow_index = rows as i32;
+            rows += 1;
+        }
+
+        if rows == 0 {
+            return;
+        }
+
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            bytemuck::cast_slice(&data),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * SHADOW_MAP_RESOLUTION as u32),
+                rows_per_image: Some(rows as u32),
+            },
+            wgpu::Extent3d {
+                width: SHADOW_MAP_RESOLUTION as u32,
+                height: rows as u32,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+}
@@ -62,7 +62,7 @@ pub struct Texture {
 
 impl Texture {
     pub const DEPTH_FORMAT: wgpu::TextureFormat =
-        wgpu::TextureFormat::Depth32Float;
+        wgpu::TextureFormat::Depth24PlusStencil8;
 
     pub fn handle(&self) -> TextureHandle {
         TextureHandle::Raw(default_hash(&self.texture.global_id()))
@@ -72,10 +72,24 @@ impl Texture {
         device: &wgpu::Device,
         config: &wgpu::SurfaceConfiguration,
         label: &str,
+    ) -> Self {
+        Self::create_depth_texture_with_size(
+            device,
+            uvec2(config.width, config.height),
+            label,
+        )
+    }
+
+    /// Depth/stencil texture for drawing into a target of the given size,
+    /// which has to match the size of the color attachment.
+    pub fn create_depth_texture_with_size(
+        device: &wgpu::Device,
+        size: UVec2,
+        label: &str,
     ) -> Self {
         let size = wgpu::Extent3d {
-            width: config.width,
-            height: config.height,
+            width: size.x.max(1),
+            height: size.y.max(1),
             depth_or_array_layers: 1,
         };
 
@@ -329,6 +343,137 @@ impl Texture {
         Ok(Self { texture, view, sampler })
     }
 
+    /// Creates an sRGB texture using the given sampler settings, generating
+    /// its mip chain if `settings.mipmaps` is set.
+    pub fn from_image_with_settings(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        mipmap_generators: &MipmapGenerators,
+        img: &image::DynamicImage,
+        label: Option<&str>,
+        settings: TextureSettings,
+    ) -> Self {
+        Self::from_image_with_settings_and_format(
+            device,
+            queue,
+            mipmap_generators,
+            img,
+            label,
+            settings,
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+        )
+    }
+
+    /// Same as `from_image_with_settings`, but with the given RGBA8 format,
+    /// e.g. `Rgba8Unorm` for normal maps.
+    pub fn from_image_with_settings_and_format(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        mipmap_generators: &MipmapGenerators,
+        img: &image::DynamicImage,
+        label: Option<&str>,
+        settings: TextureSettings,
+        format: wgpu::TextureFormat,
+    ) -> Self {
+        let img = img.flipv();
+        let rgba = img.to_rgba8();
+        let dimensions = img.dimensions();
+
+        let size = wgpu::Extent3d {
+            width: dimensions.0,
+            height: dimensions.1,
+            depth_or_array_layers: 1,
+        };
+
+        let mip_count = if settings.mipmaps {
+            mip_level_count(dimensions.0, dimensions.1)
+        } else {
+            1
+        };
+
+        let mut usage = wgpu::TextureUsages::TEXTURE_BINDING |
+            wgpu::TextureUsages::COPY_DST;
+
+        if mip_count > 1 {
+            usage |= wgpu::TextureUsages::RENDER_ATTACHMENT;
+        }
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label,
+            size,
+            mip_level_count: mip_count,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage,
+            view_formats: &[],
+        });
+
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                aspect: wgpu::TextureAspect::All,
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            &rgba,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * dimensions.0),
+                rows_per_image: Some(dimensions.1),
+            },
+            size,
+        );
+
+        if mip_count > 1 {
+            let mut encoder = device.simple_encoder("Texture Mipmap Encoder");
+
+            mipmap_generators.get(device, format).generate_mipmaps(
+                &mut encoder,
+                device,
+                &texture,
+                mip_count,
+            );
+
+            queue.submit(std::iter::once(encoder.finish()));
+        }
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = Self::create_sampler(device, label, settings);
+
+        Self { texture, view, sampler }
+    }
+
+    pub fn create_sampler(
+        device: &wgpu::Device,
+        label: Option<&str>,
+        settings: TextureSettings,
+    ) -> wgpu::Sampler {
+        let address_mode = match settings.wrap {
+            TextureWrap::Clamp => wgpu::AddressMode::ClampToEdge,
+            TextureWrap::Repeat => wgpu::AddressMode::Repeat,
+            TextureWrap::MirrorRepeat => wgpu::AddressMode::MirrorRepeat,
+        };
+
+        let filter = match settings.filter {
+            TextureFilter::Nearest => wgpu::FilterMode::Nearest,
+            TextureFilter::Linear => wgpu::FilterMode::Linear,
+        };
+
+        let sampler_label = label.map(|x| format!("{} Sampler", x));
+
+        device.create_sampler(&wgpu::SamplerDescriptor {
+            label: sampler_label.as_deref(),
+            address_mode_u: address_mode,
+            address_mode_v: address_mode,
+            address_mode_w: address_mode,
+            mag_filter: filter,
+            min_filter: filter,
+            mipmap_filter: filter,
+            ..Default::default()
+        })
+    }
+
     pub fn from_image_uninit(
         device: &wgpu::Device,
         img: &image::DynamicImage,
@@ -375,4 +520,61 @@ impl Texture {
 
         Ok(Self { texture, view, sampler })
     }
+
+    /// Uploads a `ColorLut` as a 3D texture with linear filtering.
+    pub fn from_color_lut(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        lut: &ColorLut,
+        label: Option<&str>,
+    ) -> Self {
+        let size = wgpu::Extent3d {
+            width: lut.size,
+            height: lut.size,
+            depth_or_array_layers: lut.size,
+        };
+
+        // LUT values are used as they are, without an sRGB conversion.
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label,
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D3,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING |
+                wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            bytemuck::cast_slice(&lut.data),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * lut.size),
+                rows_per_image: Some(lut.size),
+            },
+            size,
+        );
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Self { texture, view, sampler }
+    }
 }
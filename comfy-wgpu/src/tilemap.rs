@@ -0,0 +1,207 @@
+use crate::*;
+
+/// GPU buffers of a single tilemap chunk, kept between frames and only
+/// recreated when the chunk's version changes.
+pub struct TilemapChunkBuffers {
+    pub version: u64,
+    pub vertex_buffer: wgpu::Buffer,
+    pub index_buffer: wgpu::Buffer,
+    pub index_count: u32,
+}
+
+pub type TilemapChunkMap = HashMap<TilemapChunkId, TilemapChunkBuffers>;
+
+fn ensure_chunk_buffers(
+    device: &wgpu::Device,
+    chunks: &mut TilemapChunkMap,
+    draw: &TilemapChunkDraw,
+) {
+    if chunks.get(&draw.id).is_some_and(|x| x.version == draw.version) {
+        return;
+    }
+
+    perf_counter_inc("tilemap chunk uploads", 1);
+
+    let vertex_buffer =
+        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Tilemap Chunk Vertex Buffer"),
+            contents: bytemuck::cast_slice(&draw.mesh.vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+
+    let index_buffer =
+        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Tilemap Chunk Index Buffer"),
+            contents: bytemuck::cast_slice(&draw.mesh.indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+    chunks.insert(draw.id, TilemapChunkBuffers {
+        version: draw.version,
+        vertex_buffer,
+        index_buffer,
+        index_count: draw.mesh.indices.len() as u32,
+    });
+}
+
+/// Frees chunk buffers of tilemaps that were dropped.
+pub fn release_tilemap_chunks(c: &mut WgpuRenderer) {
+    let released = consume_released_tilemaps();
+
+    if !released.is_empty() {
+        c.tilemap_chunks.retain(|id, _| !released.contains(&id.tilemap));
+    }
+}
+
+/// Renders tilemap chunks of a single z-index using their cached buffers,
+/// with one render pass per blend mode/shader/render target/clip
+/// combination.
+pub fn render_tilemap_chunks(
+    c: &mut WgpuRenderer,
+    is_first: bool,
+    clear_color: Color,
+    draws: Vec<TilemapChunkDraw>,
+    surface_view: &wgpu::TextureView,
+    sprite_shader_id: ShaderId,
+) {
+    let _span = span!("render_tilemap_chunks");
+
+    for draw in draws.iter() {
+        ensure_chunk_buffers(&c.context.device, &mut c.tilemap_chunks, draw);
+    }
+
+    let groups = draws
+        .into_iter()
+        .sorted_by_key(|x| (x.blend_mode, x.shader, x.render_target, x.clip))
+        .group_by(|x| (x.blend_mode, x.shader, x.render_target, x.clip));
+
+    let mut is_first = is_first;
+
+    for ((blend_mode, shader, render_target, clip_id), group) in
+        groups.into_iter()
+    {
+        let group = group.collect_vec();
+
+        let pass_data = MeshDrawData {
+            blend_mode,
+            texture: group[0].texture,
+            shader,
+            render_target,
+            clip: clip_id,
+            data: Vec::new(),
+        };
+
+        let pipeline_name =
+            ensure_pipeline_exists(c, &pass_data, sprite_shader_id);
+
+        let clip = get_clip_state(clip_id);
+        let clear_stencil = take_stencil_clear(c, clip.stencil);
+        let target_size = render_target_size(c, render_target);
+
+        perf_counter_inc("batch-count", 1);
+
+        let textures = c.textures.lock();
+        let render_targets = c.render_targets.borrow();
+
+        let tex_bind_groups = group
+            .iter()
+            .map(|draw| {
+                c.sprite_textures.get(
+                    &c.context.device,
+                    draw.texture,
+                    &textures,
+                    &render_targets,
+                )
+            })
+            .collect_vec();
+
+        let mut encoder =
+            c.context.device.simple_encoder("Tilemap Render Encoder");
+
+        {
+            let clear_color = if is_first { Some(clear_color) } else { None };
+
+            let (target_view, depth_view) = render_target_views(
+                c,
+                &render_targets,
+                surface_view,
+                render_target,
+            );
+
+            let mut render_pass =
+                encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("Tilemap Render Pass"),
+                    color_attachments: &[Some(
+                        wgpu::RenderPassColorAttachment {
+                            view: target_view,
+                            resolve_target: None,
+                            ops: wgpu::Operations {
+                                load: color_to_clear_op(clear_color),
+                                store: wgpu::StoreOp::Store,
+                            },
+                        },
+                    )],
+                    depth_stencil_attachment: clip_depth_stencil_attachment(
+                        c.enable_z_buffer,
+                        depth_view,
+                        is_first,
+                        clip.stencil,
+                        clear_stencil,
+                    ),
+                    timestamp_writes: None,
+                    occlusion_query_set: None,
+                });
+
+            match c
+                .user_pipelines
+                .get(&pipeline_name)
+                .map(RenderPipeline::User)
+                .or_else(|| {
+                    c.pipelines.get(&pipeline_name).map(RenderPipeline::Wgpu)
+                })
+                .expect("ensured pipeline must exist within the same frame")
+            {
+                RenderPipeline::User(pipeline) => {
+                    render_pass.set_pipeline(&pipeline.pipeline);
+                    render_pass.set_bind_group(2, &pipeline.bind_group, &[]);
+                }
+                RenderPipeline::Wgpu(pipeline) => {
+                    render_pass.set_pipeline(pipeline);
+                }
+            }
+
+            render_pass.set_bind_group(1, &c.camera_bind_group, &[]);
+
+            apply_clip_state(
+                &mut render_pass,
+                &clip,
+                target_size,
+                c.camera_view_proj,
+                c.camera_viewport,
+            );
+            apply_camera_viewport(
+                &mut render_pass,
+                c.camera_viewport,
+                render_target,
+                target_size,
+            );
+
+            for (draw, tex_bind_group) in group.iter().zip(&tex_bind_groups) {
+                let buffers = &c.tilemap_chunks[&draw.id];
+
+                render_pass.set_bind_group(0, tex_bind_group, &[]);
+                render_pass
+                    .set_vertex_buffer(0, buffers.vertex_buffer.slice(..));
+                render_pass.set_index_buffer(
+                    buffers.index_buffer.slice(..),
+                    wgpu::IndexFormat::Uint32,
+                );
+                render_pass.draw_indexed(0..buffers.index_count, 0, 0..1);
+            }
+        }
+
+        c.context.queue.submit(std::iter::once(encoder.finish()));
+
+        is_first = false;
+    }
+}
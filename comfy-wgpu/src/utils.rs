@@ -1,15 +1,57 @@
 use crate::*;
 
+pub const SPRITE_VERTEX_PREFIX: &str = include_str!(concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/shaders/sprite-vertex-prefix.wgsl"
+));
+
 pub const FRAG_SHADER_PREFIX: &str = include_str!(concat!(
     env!("CARGO_MANIFEST_DIR"),
     "/shaders/frag-shader-prefix.wgsl"
 ));
 
+pub const SPRITE_INSTANCED_PREFIX: &str = include_str!(concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/shaders/sprite-instanced-prefix.wgsl"
+));
+
+pub const SPRITE_BINDLESS_PREFIX: &str = include_str!(concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/shaders/sprite-bindless-prefix.wgsl"
+));
+
+pub const SPRITE_BINDLESS_INSTANCED_PREFIX: &str = include_str!(concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/shaders/sprite-bindless-instanced-prefix.wgsl"
+));
+
 pub const CAMERA_BIND_GROUP_PREFIX: &str = include_str!(concat!(
     env!("CARGO_MANIFEST_DIR"),
     "/shaders/camera-bind-group.wgsl"
 ));
 
+pub const LIGHTS_STORAGE_PREFIX: &str = include_str!(concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/shaders/lights-storage.wgsl"
+));
+
+pub const LIGHTS_UNIFORM_PREFIX: &str = include_str!(concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/shaders/lights-uniform.wgsl"
+));
+
+/// `CAMERA_BIND_GROUP_PREFIX` together with the declaration of the lights
+/// matching the device, see `storage_lights_enabled`.
+pub fn camera_bind_group_prefix() -> String {
+    let lights = if storage_lights_enabled() {
+        LIGHTS_STORAGE_PREFIX
+    } else {
+        LIGHTS_UNIFORM_PREFIX
+    };
+
+    format!("{}{}", CAMERA_BIND_GROUP_PREFIX, lights)
+}
+
 pub const SHADER_POST_PROCESSING_VERTEX: &str = include_str!(concat!(
     env!("CARGO_MANIFEST_DIR"),
     "/shaders/post_processing_vertex.wgsl"
@@ -33,13 +75,57 @@ macro_rules! engine_shader_source {
 }
 
 pub fn sprite_shader_from_fragment(source: &str) -> String {
-    format!("{}{}{}", CAMERA_BIND_GROUP_PREFIX, FRAG_SHADER_PREFIX, source)
+    sprite_shader_from_vertex(FRAG_SHADER_PREFIX, Some(source))
+}
+
+/// Builds a sprite shader with a custom vertex stage.
+///
+/// `vertex_source` must define `@vertex fn vs_main(model: VertexInput) ->
+/// VertexOutput`. The following is available to it:
+///
+/// - `VertexInput` with the `SpriteVertex` attributes `position` (world
+///   space), `tex_coords` and `color`.
+/// - `camera.view_proj`, the view projection of the camera currently being
+///   drawn (including render target cameras).
+/// - `frame`, the `FrameDataUniform` with `time`, `delta`, `frame`, `fps`,
+///   `aspect_ratio`, `mouse_world` and `mouse_screen`.
+/// - `sprite_vertex(model)`, the default transform, which can be called first
+///   and then adjusted.
+/// - Any uniforms passed to `create_shader`.
+///
+/// The returned `VertexOutput` is what the fragment shader receives. When
+/// `fragment_source` is `None` the default sprite fragment shader is used,
+/// meaning the result is lit just like regular sprites.
+pub fn sprite_shader_from_vertex(
+    vertex_source: &str,
+    fragment_source: Option<&str>,
+) -> String {
+    format!(
+        "{}{}{}\n{}",
+        camera_bind_group_prefix(),
+        SPRITE_VERTEX_PREFIX,
+        vertex_source,
+        fragment_source.unwrap_or(engine_shader_source!("sprite"))
+    )
+}
+
+/// Same as `sprite_shader_from_fragment`, but with a vertex shader expanding
+/// `SpriteInstance`s into quads.
+pub fn sprite_instanced_shader_from_fragment(source: &str) -> String {
+    format!(
+        "{}{}{}",
+        camera_bind_group_prefix(),
+        SPRITE_INSTANCED_PREFIX,
+        source
+    )
 }
 
 pub fn post_process_shader_from_fragment(source: &str) -> String {
     format!(
         "{}{}{}",
-        CAMERA_BIND_GROUP_PREFIX, SHADER_POST_PROCESSING_VERTEX, source
+        camera_bind_group_prefix(),
+        SHADER_POST_PROCESSING_VERTEX,
+        source
     )
 }
 
@@ -65,15 +151,31 @@ pub fn load_texture_from_engine_bytes(
     address_mode: wgpu::AddressMode,
 ) {
     let img = image::load_from_memory(bytes).expect("must be valid image");
-    let texture = Texture::from_image_ex(
-        &context.device,
-        &context.queue,
-        &img,
-        Some(name),
-        false,
-        address_mode,
-    )
-    .unwrap();
+    let is_normal_map = is_normal_map(texture_path(name));
+
+    let texture = match texture_settings(texture_path(name)) {
+        Some(settings) if !is_normal_map => {
+            Texture::from_image_with_settings(
+                &context.device,
+                &context.queue,
+                &context.mipmap_generators,
+                &img,
+                Some(name),
+                settings,
+            )
+        }
+        _ => {
+            Texture::from_image_ex(
+                &context.device,
+                &context.queue,
+                &img,
+                Some(name),
+                is_normal_map,
+                address_mode,
+            )
+            .unwrap()
+        }
+    };
 
     load_texture_with_image(context, name, img, texture, textures);
 }
@@ -107,6 +209,133 @@ pub fn load_texture_with_image(
     textures.insert(handle, BindableTexture { bind_group, texture });
 }
 
+/// Applies changed `TextureSettings` to an already loaded texture. Only the
+/// sampler is replaced, unless mipmaps were toggled, in which case the
+/// texture is re-created from its image data.
+///
+/// Returns `false` if the texture isn't loaded yet, in which case the caller
+/// should try again once it is.
+pub fn apply_texture_settings(
+    context: &GraphicsContext,
+    textures: &mut TextureMap,
+    handle: TextureHandle,
+    settings: TextureSettings,
+) -> bool {
+    let Some(bindable) = textures.get_mut(&handle) else {
+        return false;
+    };
+
+    let name = Assets::handle_name(handle);
+    let has_mipmaps = bindable.texture.texture.mip_level_count() > 1;
+
+    if settings.mipmaps != has_mipmaps {
+        let image =
+            ASSETS.borrow().texture_image_map.lock().get(&handle).cloned();
+
+        let Some(image) = image else {
+            warn!(
+                "Can't toggle mipmaps of {:?} without its image data",
+                handle
+            );
+            return true;
+        };
+
+        // Keeps linear textures like normal maps linear.
+        bindable.texture = Texture::from_image_with_settings_and_format(
+            &context.device,
+            &context.queue,
+            &context.mipmap_generators,
+            &DynamicImage::ImageRgba8((*image).clone()),
+            name.as_deref(),
+            settings,
+            bindable.texture.texture.format(),
+        );
+    } else {
+        bindable.texture.sampler =
+            Texture::create_sampler(&context.device, name.as_deref(), settings);
+    }
+
+    bindable.bind_group = context.device.simple_bind_group(
+        name.map(|x| format!("{}_bind_group", x)).as_deref(),
+        &bindable.texture,
+        &context.texture_layout,
+    );
+
+    true
+}
+
+/// Re-creates a texture which was loaded as sRGB before it was set as a
+/// normal map, since normal maps have to be linear. Returns `false` if the
+/// texture isn't loaded yet.
+pub fn ensure_linear_normal_map(
+    context: &GraphicsContext,
+    textures: &mut TextureMap,
+    handle: TextureHandle,
+) -> bool {
+    let Some(bindable) = textures.get_mut(&handle) else {
+        return false;
+    };
+
+    if !bindable.texture.texture.format().is_srgb() {
+        return true;
+    }
+
+    let image = ASSETS.borrow().texture_image_map.lock().get(&handle).cloned();
+
+    let Some(image) = image else {
+        warn!(
+            "Can't re-create normal map {:?} as linear without its image data",
+            handle
+        );
+        return true;
+    };
+
+    let name = Assets::handle_name(handle);
+
+    match Texture::from_image(
+        &context.device,
+        &context.queue,
+        &DynamicImage::ImageRgba8((*image).clone()),
+        name.as_deref(),
+        true,
+    ) {
+        Ok(texture) => bindable.texture = texture,
+        Err(err) => {
+            error!("Failed to re-create normal map {:?}: {}", handle, err);
+            return true;
+        }
+    }
+
+    bindable.bind_group = context.device.simple_bind_group(
+        name.map(|x| format!("{}_bind_group", x)).as_deref(),
+        &bindable.texture,
+        &context.texture_layout,
+    );
+
+    true
+}
+
+/// `MipmapGenerator`s for every texture format mipmaps were generated for,
+/// created when a format is first used.
+#[derive(Default)]
+pub struct MipmapGenerators {
+    generators: Mutex<HashMap<wgpu::TextureFormat, Arc<MipmapGenerator>>>,
+}
+
+impl MipmapGenerators {
+    pub fn get(
+        &self,
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+    ) -> Arc<MipmapGenerator> {
+        self.generators
+            .lock()
+            .entry(format)
+            .or_insert_with(|| Arc::new(MipmapGenerator::new(device, format)))
+            .clone()
+    }
+}
+
 pub struct MipmapGenerator {
     pub format: wgpu::TextureFormat,
     pub blit_pipeline: wgpu::RenderPipeline,
@@ -0,0 +1,45 @@
+use comfy::*;
+
+simple_game!("Clip Rects & Stencil Masks", setup, update);
+
+fn setup(c: &mut EngineContext) {
+    c.load_texture_from_bytes(
+        "dot",
+        include_bytes!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../assets/dot.png"
+        )),
+    );
+}
+
+fn update(_c: &mut EngineContext) {
+    clear_background(BLACK);
+
+    let t = get_time() as f32;
+
+    // A scrolling list restricted to a world space panel.
+    let panel = AABB::from_center_size(vec2(-5.0, 0.0), vec2(6.0, 6.0));
+    draw_rect_outline(panel.center(), panel.size(), 0.1, WHITE, 10);
+
+    push_clip_rect(ClipRect::World(panel));
+
+    for i in 0..12 {
+        let y = 4.0 - i as f32 * 1.5 + (t * 1.5) % 3.0;
+        draw_rect(vec2(-5.0, y), vec2(5.0, 1.0), ORANGE.alpha(0.8), 0);
+    }
+
+    pop_clip_rect();
+
+    // A porthole that follows the mouse, revealing what's behind the fog.
+    let mask = begin_stencil_mask();
+    draw_sprite(texture_id("dot"), mouse_world(), WHITE, 0, splat(4.0));
+    end_stencil_mask();
+
+    for x in 0..8 {
+        draw_circle(vec2(x as f32 * 1.5, 0.0), 0.5, RED, 1);
+    }
+
+    push_inverted_stencil_mask(mask);
+    draw_rect(vec2(5.0, 0.0), vec2(12.0, 8.0), DARKGRAY, 2);
+    pop_stencil_mask();
+}
@@ -0,0 +1,75 @@
+use comfy::*;
+
+simple_game!("Color Grading", GameState, setup, update);
+
+pub struct GameState {
+    pub day: Option<ColorLutHandle>,
+    pub night: Option<ColorLutHandle>,
+    pub weight: f32,
+}
+
+impl GameState {
+    pub fn new(_c: &mut EngineState) -> Self {
+        Self { day: None, night: None, weight: 1.0 }
+    }
+}
+
+// LUTs are usually loaded with `ColorLut::from_cube` or
+// `ColorLut::from_image_bytes`, here they're generated from an identity LUT.
+fn graded_lut(f: impl Fn(Vec3) -> Vec3) -> ColorLut {
+    let mut lut = ColorLut::identity(16);
+
+    for texel in lut.data.iter_mut() {
+        let color =
+            Vec3::new(texel[0] as f32, texel[1] as f32, texel[2] as f32) /
+                255.0;
+        let graded = (f(color).clamp(Vec3::ZERO, Vec3::ONE) * 255.0).round();
+
+        *texel = [graded.x as u8, graded.y as u8, graded.z as u8, 255];
+    }
+
+    lut
+}
+
+fn setup(state: &mut GameState, _c: &mut EngineContext) {
+    let day = graded_lut(|c| c * vec3(1.1, 1.0, 0.85));
+
+    let night = graded_lut(|c| {
+        let luma = c.dot(vec3(0.299, 0.587, 0.114));
+        Vec3::splat(luma).lerp(c, 0.4) * vec3(0.6, 0.75, 1.1)
+    });
+
+    state.day = Some(load_color_lut(day));
+    state.night = Some(load_color_lut(night));
+
+    set_color_lut(state.day);
+}
+
+fn update(state: &mut GameState, _c: &mut EngineContext) {
+    draw_comfy(vec2(-2.0, 0.0), WHITE, 0, splat(3.0));
+    draw_circle(vec2(2.0, 0.0), 1.5, ORANGE, 0);
+    draw_rect(vec2(0.0, -3.0), vec2(8.0, 1.0), GREEN, 0);
+
+    if is_key_pressed(KeyCode::Num1) {
+        crossfade_color_lut(state.day, 2.0);
+    }
+
+    if is_key_pressed(KeyCode::Num2) {
+        crossfade_color_lut(state.night, 2.0);
+    }
+
+    if is_key_pressed(KeyCode::Num3) {
+        crossfade_color_lut(None, 2.0);
+    }
+
+    egui::Window::new("Color Grading")
+        .anchor(egui::Align2::LEFT_TOP, egui::vec2(20.0, 20.0))
+        .show(egui(), |ui| {
+            ui.label("Press 1 for day, 2 for night and 3 for no grading");
+            ui.add(
+                egui::Slider::new(&mut state.weight, 0.0..=1.0).text("Weight"),
+            );
+        });
+
+    set_color_lut_weight(state.weight);
+}
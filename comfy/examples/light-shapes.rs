@@ -0,0 +1,98 @@
+use comfy::*;
+
+simple_game!("Light Shapes", setup, update);
+
+fn load_generated_texture(
+    c: &mut EngineContext,
+    name: &str,
+    image: image::RgbaImage,
+) {
+    let img = DynamicImage::ImageRgba8(image);
+
+    let texture = Texture::from_image_ex(
+        &c.renderer.context.device,
+        &c.renderer.context.queue,
+        &img,
+        Some(name),
+        false,
+        wgpu::AddressMode::ClampToEdge,
+    )
+    .unwrap();
+
+    load_texture_with_image(
+        &c.renderer.context,
+        name,
+        img,
+        texture,
+        &mut c.renderer.textures.lock(),
+    );
+}
+
+fn setup(c: &mut EngineContext) {
+    // Rings of a flashlight's reflector.
+    let flashlight = image::RgbaImage::from_fn(128, 128, |x, y| {
+        let d = vec2(x as f32 - 64.0, y as f32 - 64.0).length() / 64.0;
+        let v = (190.0 + 65.0 * (d * 40.0).cos()) as u8;
+
+        image::Rgba([v, v, v, 255])
+    });
+
+    // Four colored panes separated by a lead frame.
+    let stained_glass = image::RgbaImage::from_fn(128, 128, |x, y| {
+        let frame = x % 64 < 4 || y % 64 < 4;
+
+        match (frame, x < 64, y < 64) {
+            (true, _, _) => image::Rgba([0, 0, 0, 255]),
+            (_, true, true) => image::Rgba([230, 40, 40, 255]),
+            (_, false, true) => image::Rgba([40, 90, 230, 255]),
+            (_, true, false) => image::Rgba([240, 200, 40, 255]),
+            (_, false, false) => image::Rgba([40, 200, 90, 255]),
+        }
+    });
+
+    load_generated_texture(c, "flashlight", flashlight);
+    load_generated_texture(c, "stained-glass", stained_glass);
+
+    game_config_mut().lighting.ambient_light_intensity = 0.05;
+}
+
+fn update(_c: &mut EngineContext) {
+    draw_rect(Vec2::ZERO, splat(30.0), DARKGRAY, 0);
+
+    let player = vec2(-4.0, -2.0);
+    draw_circle(player, 0.3, WHITE, 1);
+
+    // A spot light pointing towards the mouse, with a cookie.
+    draw_light(
+        Light::simple(player, 12.0, 2.0)
+            .with_spot(mouse_world() - player, 0.2, 0.4)
+            .with_cookie(texture_id("flashlight")),
+    );
+
+    // A window lighting the floor through stained glass.
+    let window = vec2(5.0, 3.0);
+    draw_rect_outline_rot(window, vec2(4.0, 3.0), 0.2, 0.1, GRAY, 1);
+
+    draw_light(
+        Light::simple(window, 2.0, 1.5)
+            .with_area(vec2(4.0, 3.0))
+            .with_rotation(0.2)
+            .with_cookie(texture_id("stained-glass")),
+    );
+
+    // A plain area light, e.g. a neon tube.
+    let t = get_time() as f32;
+
+    draw_light(
+        Light::simple(vec2(0.0, -7.0), 1.5, 1.0 + 0.2 * (t * 20.0).sin())
+            .with_area(vec2(8.0, 0.2))
+            .with_color(PINK),
+    );
+
+    draw_text(
+        "The flashlight points towards the mouse",
+        vec2(0.0, -9.0),
+        WHITE,
+        TextAlign::Center,
+    );
+}
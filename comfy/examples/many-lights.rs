@@ -0,0 +1,82 @@
+use comfy::*;
+
+simple_game!("Many Lights", GameState, setup, update);
+
+const BOUNDS: Vec2 = Vec2::new(16.0, 9.0);
+const COLORS: [Color; 5] = [ORANGE, YELLOW, RED, BLUE, PURPLE];
+
+pub struct Spark {
+    pub position: Vec2,
+    pub velocity: Vec2,
+    pub color: Color,
+}
+
+pub struct GameState {
+    pub sparks: Vec<Spark>,
+    pub spark_count: usize,
+}
+
+impl GameState {
+    pub fn new(_c: &mut EngineState) -> Self {
+        Self { sparks: vec![], spark_count: 2000 }
+    }
+}
+
+fn setup(_state: &mut GameState, _c: &mut EngineContext) {
+    game_config_mut().lighting.ambient_light_intensity = 0.05;
+}
+
+fn update(state: &mut GameState, _c: &mut EngineContext) {
+    while state.sparks.len() < state.spark_count {
+        state.sparks.push(Spark {
+            position: random_box(Vec2::ZERO, BOUNDS * 2.0),
+            velocity: random_dir() * random_range(1.0, 4.0),
+            color: COLORS[gen_range(0, COLORS.len())],
+        });
+    }
+
+    state.sparks.truncate(state.spark_count);
+
+    draw_rect(Vec2::ZERO, BOUNDS * 2.0, DARKGRAY, 0);
+
+    for spark in state.sparks.iter_mut() {
+        spark.position += spark.velocity * delta();
+
+        if spark.position.x.abs() > BOUNDS.x {
+            spark.velocity.x =
+                -spark.position.x.signum() * spark.velocity.x.abs();
+        }
+
+        if spark.position.y.abs() > BOUNDS.y {
+            spark.velocity.y =
+                -spark.position.y.signum() * spark.velocity.y.abs();
+        }
+
+        draw_circle(spark.position, 0.05, spark.color, 1);
+
+        // Every pixel only goes through the lights whose radius reaches it,
+        // which keeps thousands of small lights cheap.
+        draw_light(
+            Light::simple(spark.position, 0.8, 0.6).with_color(spark.color),
+        );
+    }
+
+    egui::Window::new("Many Lights")
+        .anchor(egui::Align2::LEFT_TOP, egui::vec2(20.0, 20.0))
+        .show(egui(), |ui| {
+            ui.label(format!("Lights: {}", light_count()));
+
+            if !storage_lights_enabled() {
+                ui.label(format!(
+                    "Storage buffers are not supported, only the {} lights \
+                     closest to the camera are drawn",
+                    MAX_UNIFORM_LIGHTS
+                ));
+            }
+
+            ui.add(
+                egui::Slider::new(&mut state.spark_count, 0..=10000)
+                    .text("Sparks"),
+            );
+        });
+}
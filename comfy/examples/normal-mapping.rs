@@ -0,0 +1,117 @@
+use comfy::*;
+
+simple_game!("Normal Mapping", GameState, setup, update);
+
+pub struct GameState {
+    pub light_height: f32,
+}
+
+impl GameState {
+    pub fn new(_c: &mut EngineState) -> Self {
+        Self { light_height: 1.0 }
+    }
+}
+
+const SIZE: u32 = 128;
+
+// Height of a brick wall, bricks are bevelled towards the mortar between
+// them.
+fn brick_height(x: i32, y: i32) -> f32 {
+    let row = y.div_euclid(16);
+    let bx = (x + row.rem_euclid(2) * 16).rem_euclid(32);
+    let by = y.rem_euclid(16);
+
+    let edge = bx.min(31 - bx).min(by).min(15 - by);
+
+    (edge as f32 / 3.0).min(1.0)
+}
+
+fn load_generated_texture(
+    c: &mut EngineContext,
+    name: &str,
+    image: image::RgbaImage,
+    is_normal_map: bool,
+) {
+    let img = DynamicImage::ImageRgba8(image);
+
+    let texture = Texture::from_image_ex(
+        &c.renderer.context.device,
+        &c.renderer.context.queue,
+        &img,
+        Some(name),
+        is_normal_map,
+        wgpu::AddressMode::Repeat,
+    )
+    .unwrap();
+
+    load_texture_with_image(
+        &c.renderer.context,
+        name,
+        img,
+        texture,
+        &mut c.renderer.textures.lock(),
+    );
+}
+
+fn setup(_state: &mut GameState, c: &mut EngineContext) {
+    // Textures from files can be loaded together with their normal map using
+    // `load_texture_with_normal_map("bricks", "bricks.png")`, which also
+    // loads `bricks_n.png`. Here both are generated instead.
+    let diffuse = image::RgbaImage::from_fn(SIZE, SIZE, |x, y| {
+        if brick_height(x as i32, y as i32) > 0.0 {
+            image::Rgba([150, 70, 50, 255])
+        } else {
+            image::Rgba([90, 90, 90, 255])
+        }
+    });
+
+    let normal_map = image::RgbaImage::from_fn(SIZE, SIZE, |x, y| {
+        let (x, y) = (x as i32, y as i32);
+
+        let dx = brick_height(x + 1, y) - brick_height(x - 1, y);
+        // Image rows go down while the normal map's Y axis goes up.
+        let dy = brick_height(x, y + 1) - brick_height(x, y - 1);
+
+        let n = vec3(-dx, dy, 0.5).normalize() * 0.5 + 0.5;
+
+        image::Rgba([
+            (n.x * 255.0) as u8,
+            (n.y * 255.0) as u8,
+            (n.z * 255.0) as u8,
+            255,
+        ])
+    });
+
+    load_generated_texture(c, "bricks", diffuse, false);
+    load_generated_texture(c, "bricks_n", normal_map, true);
+
+    set_normal_map(texture_id("bricks"), Some(texture_id("bricks_n")));
+
+    game_config_mut().lighting.ambient_light_intensity = 0.1;
+}
+
+fn update(state: &mut GameState, _c: &mut EngineContext) {
+    draw_sprite(texture_id("bricks"), Vec2::ZERO, WHITE, 0, splat(10.0));
+
+    draw_light(
+        Light::simple(mouse_world(), 6.0, 2.0).with_height(state.light_height),
+    );
+
+    let t = get_time() as f32;
+
+    draw_light(
+        Light::simple(4.0 * vec2(t.cos(), t.sin()), 4.0, 1.5)
+            .with_color(ORANGE)
+            .with_height(state.light_height),
+    );
+
+    egui::Window::new("Normal Mapping")
+        .anchor(egui::Align2::LEFT_TOP, egui::vec2(20.0, 20.0))
+        .show(egui(), |ui| {
+            ui.label("The white light follows the mouse");
+            ui.add(
+                egui::Slider::new(&mut state.light_height, 0.1..=4.0)
+                    .text("Light height"),
+            );
+        });
+}
@@ -0,0 +1,55 @@
+use comfy::*;
+
+simple_game!("Pixel Perfect", GameState, config, setup, update);
+
+fn config(config: GameConfig) -> GameConfig {
+    GameConfig {
+        pixel_perfect: Some(PixelPerfectConfig::new(uvec2(320, 180))),
+        ..config
+    }
+}
+
+pub struct GameState {
+    pub player: Vec2,
+}
+
+impl GameState {
+    pub fn new(_c: &mut EngineState) -> Self {
+        Self { player: Vec2::ZERO }
+    }
+}
+
+fn setup(_state: &mut GameState, _c: &mut EngineContext) {
+    main_camera_mut().zoom = 20.0;
+}
+
+fn update(state: &mut GameState, _c: &mut EngineContext) {
+    clear_background(DARKBLUE);
+
+    let speed = 3.0 * delta();
+
+    if is_key_down(KeyCode::W) {
+        state.player.y += speed;
+    }
+    if is_key_down(KeyCode::S) {
+        state.player.y -= speed;
+    }
+    if is_key_down(KeyCode::A) {
+        state.player.x -= speed;
+    }
+    if is_key_down(KeyCode::D) {
+        state.player.x += speed;
+    }
+
+    // The camera moves smoothly, but what's drawn stays on whole pixels.
+    main_camera_mut().center = state.player;
+
+    for x in -5..=5 {
+        for y in -3..=3 {
+            draw_comfy(vec2(x as f32, y as f32) * 3.0, WHITE, 0, splat(1.0));
+        }
+    }
+
+    draw_circle(state.player, 0.5, RED, 1);
+    draw_circle(mouse_world(), 0.25, YELLOW, 2);
+}
@@ -0,0 +1,111 @@
+use comfy::*;
+
+simple_game!("Built-in Post Processing Effects", GameState, setup, update);
+
+pub struct GameState {
+    pub crt: bool,
+    pub vignette: bool,
+    pub chromatic_aberration: bool,
+    pub pixelate: bool,
+    pub dither: bool,
+    pub palette: bool,
+    pub film_grain: bool,
+    pub pixel_size: f32,
+    pub dither_levels: u32,
+}
+
+impl GameState {
+    pub fn new(_c: &mut EngineState) -> Self {
+        Self {
+            crt: true,
+            vignette: true,
+            chromatic_aberration: false,
+            pixelate: false,
+            dither: false,
+            palette: false,
+            film_grain: false,
+            pixel_size: 4.0,
+            dither_levels: 4,
+        }
+    }
+}
+
+fn setup(_state: &mut GameState, c: &mut EngineContext) {
+    // The palette effect reads its colors from the first row of a texture.
+    let colors = [
+        [34, 32, 52, 255],
+        [69, 40, 60, 255],
+        [102, 57, 49, 255],
+        [223, 113, 38, 255],
+        [251, 242, 54, 255],
+        [106, 190, 48, 255],
+        [91, 110, 225, 255],
+        [255, 255, 255, 255],
+    ];
+
+    let img = DynamicImage::ImageRgba8(image::RgbaImage::from_fn(
+        colors.len() as u32,
+        1,
+        |x, _| image::Rgba(colors[x as usize]),
+    ));
+
+    let texture = Texture::from_image_ex(
+        &c.renderer.context.device,
+        &c.renderer.context.queue,
+        &img,
+        Some("palette"),
+        false,
+        wgpu::AddressMode::ClampToEdge,
+    )
+    .unwrap();
+
+    load_texture_with_image(
+        &c.renderer.context,
+        "palette",
+        img,
+        texture,
+        &mut c.renderer.textures.lock(),
+    );
+}
+
+fn update(state: &mut GameState, c: &mut EngineContext) {
+    draw_comfy(vec2(-2.0, 0.0), WHITE, 0, splat(3.0));
+    draw_circle(vec2(2.0, 0.0), 1.5, ORANGE, 0);
+
+    egui::Window::new("PostFx")
+        .anchor(egui::Align2::LEFT_TOP, egui::vec2(20.0, 20.0))
+        .show(egui(), |ui| {
+            ui.checkbox(&mut state.crt, "CRT");
+            ui.checkbox(&mut state.vignette, "Vignette");
+            ui.checkbox(
+                &mut state.chromatic_aberration,
+                "Chromatic aberration",
+            );
+            ui.checkbox(&mut state.pixelate, "Pixelate");
+            ui.add(egui::Slider::new(&mut state.pixel_size, 1.0..=16.0));
+            ui.checkbox(&mut state.dither, "Dither");
+            ui.add(egui::Slider::new(&mut state.dither_levels, 2..=8));
+            ui.checkbox(&mut state.palette, "Palette");
+            ui.checkbox(&mut state.film_grain, "Film grain");
+        });
+
+    // Effects are applied in the order they were first added, calling
+    // `add_post_fx` again only updates the parameters.
+    let effects = [
+        (state.pixelate, PostFx::Pixelate { pixel_size: state.pixel_size }),
+        (state.palette, PostFx::palette(texture_id("palette"))),
+        (state.dither, PostFx::Dither { levels: state.dither_levels }),
+        (state.chromatic_aberration, PostFx::chromatic_aberration()),
+        (state.film_grain, PostFx::film_grain()),
+        (state.crt, PostFx::crt()),
+        (state.vignette, PostFx::vignette()),
+    ];
+
+    for (enabled, fx) in effects {
+        if enabled {
+            add_post_fx(c.renderer, fx);
+        } else {
+            c.renderer.post_processing().set_enabled(fx.name(), false);
+        }
+    }
+}
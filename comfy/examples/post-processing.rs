@@ -0,0 +1,101 @@
+use comfy::*;
+
+simple_game!("Post Processing Stack", GameState, setup, update);
+
+pub struct GameState {
+    pub crt_enabled: bool,
+    pub crt_first: bool,
+    pub curvature: f32,
+    pub scanline_strength: f32,
+    pub tint: Color,
+}
+
+impl GameState {
+    pub fn new(_c: &mut EngineState) -> Self {
+        Self {
+            crt_enabled: true,
+            crt_first: false,
+            curvature: 0.1,
+            scanline_strength: 0.3,
+            tint: WHITE,
+        }
+    }
+}
+
+fn setup(_state: &mut GameState, c: &mut EngineContext) {
+    // Editing `crt.wgsl` while the game runs reloads the effect.
+    let crt = create_reloadable_post_processing_shader(
+        &mut c.renderer.shaders.borrow_mut(),
+        "crt",
+        ReloadableShaderSource {
+            static_source: include_str!("crt.wgsl").to_string(),
+            path: "comfy/examples/crt.wgsl".to_string(),
+        },
+        hashmap! {
+            "curvature".to_string() => UniformDef::F32(Some(0.1)),
+            "scanline_strength".to_string() => UniformDef::F32(Some(0.3)),
+            "tint".to_string() => UniformDef::Color(Some(WHITE)),
+        },
+    )
+    .unwrap();
+
+    let invert = create_shader(
+        &mut c.renderer.shaders.borrow_mut(),
+        "invert",
+        &post_process_shader_from_fragment(INVERT_SHADER),
+        HashMap::new(),
+    )
+    .unwrap();
+
+    add_post_processing_effect(c.renderer, "crt", crt);
+    add_post_processing_effect(c.renderer, "invert", invert);
+
+    c.renderer.post_processing().set_enabled("invert", false);
+}
+
+const INVERT_SHADER: &str = "
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(r_color, r_sampler, in.tex_coords);
+    return vec4<f32>(1.0 - color.rgb, color.a);
+}
+";
+
+fn update(state: &mut GameState, c: &mut EngineContext) {
+    draw_comfy(vec2(0.0, 0.0), WHITE, 0, splat(4.0));
+
+    let mut invert_enabled =
+        c.renderer.post_processing().get("invert").unwrap().enabled;
+
+    egui::Window::new("Post Processing")
+        .anchor(egui::Align2::LEFT_TOP, egui::vec2(20.0, 20.0))
+        .show(egui(), |ui| {
+            ui.checkbox(&mut state.crt_enabled, "CRT");
+            ui.checkbox(&mut invert_enabled, "Invert");
+            ui.checkbox(&mut state.crt_first, "CRT before invert");
+
+            ui.label("Curvature");
+            ui.add(egui::Slider::new(&mut state.curvature, 0.0..=0.5));
+            ui.label("Scanlines");
+            ui.add(egui::Slider::new(&mut state.scanline_strength, 0.0..=1.0));
+
+            if ui.button("Green tint").clicked() {
+                state.tint = Color::rgb(0.6, 1.0, 0.6);
+            }
+        });
+
+    let mut stack = c.renderer.post_processing();
+
+    stack.set_enabled("crt", state.crt_enabled);
+    stack.set_enabled("invert", invert_enabled);
+
+    if state.crt_first {
+        stack.move_before("crt", "invert");
+    } else {
+        stack.move_after("crt", "invert");
+    }
+
+    stack.set_uniform_f32("crt", "curvature", state.curvature);
+    stack.set_uniform_f32("crt", "scanline_strength", state.scanline_strength);
+    stack.set_uniform_color("crt", "tint", state.tint);
+}
@@ -0,0 +1,79 @@
+use comfy::*;
+
+simple_game!("Render Target Camera", GameState, setup, update);
+
+pub struct GameState {
+    pub player: Vec2,
+    pub minimap: RenderTargetId,
+    pub minimap_texture: TextureHandle,
+}
+
+impl GameState {
+    pub fn new(_c: &mut EngineState) -> Self {
+        // The minimap sees the whole level from further away, and is drawn
+        // into a texture that is then shown like any other sprite.
+        let (minimap, minimap_texture) = create_render_target_ex(
+            RenderTargetParams::new("minimap", uvec2(256, 256))
+                .with_camera(Camera::new(Vec2::ZERO, 50.0, Viewport::FULL))
+                .with_clear_color(BLACK.alpha(0.6)),
+        );
+
+        Self { player: Vec2::ZERO, minimap, minimap_texture }
+    }
+}
+
+fn setup(_state: &mut GameState, _c: &mut EngineContext) {
+    main_camera_mut().zoom = 20.0;
+}
+
+fn update(state: &mut GameState, _c: &mut EngineContext) {
+    clear_background(BLACK);
+
+    let speed = 8.0 * delta();
+
+    if is_key_down(KeyCode::W) {
+        state.player.y += speed;
+    }
+    if is_key_down(KeyCode::S) {
+        state.player.y -= speed;
+    }
+    if is_key_down(KeyCode::A) {
+        state.player.x -= speed;
+    }
+    if is_key_down(KeyCode::D) {
+        state.player.x += speed;
+    }
+
+    main_camera_mut().center = state.player;
+
+    for x in -10..=10 {
+        for y in -10..=10 {
+            let color = if (x + y) % 2 == 0 { DARKGRAY } else { GRAY };
+            draw_rect(vec2(x as f32, y as f32) * 2.0, splat(2.0), color, 0);
+        }
+    }
+
+    draw_circle(state.player, 0.5, RED, 1);
+
+    // Sprites sampling the minimap are only drawn on the screen, the minimap
+    // doesn't draw itself.
+    draw_sprite(
+        state.minimap_texture,
+        state.player + vec2(7.0, 3.5),
+        WHITE,
+        10,
+        splat(5.0),
+    );
+
+    if is_key_pressed(KeyCode::Space) {
+        let params = render_target_params(state.minimap).unwrap();
+        let camera = params.camera.map(|camera| {
+            Camera {
+                zoom: if camera.zoom > 30.0 { 20.0 } else { 50.0 },
+                ..camera
+            }
+        });
+
+        set_render_target_camera(state.minimap, camera);
+    }
+}
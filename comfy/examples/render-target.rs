@@ -42,12 +42,12 @@ fn update(state: &mut GameState, c: &mut EngineContext) {
             .unwrap(),
         );
 
-        state.my_render_target =
-            Some(create_render_target(c.renderer, &RenderTargetParams {
-                label: "my-render-target".to_string(),
-                size: uvec2(128, 128),
-                filter_mode: wgpu::FilterMode::Nearest,
-            }));
+        let (render_target, _) = create_render_target_ex(
+            RenderTargetParams::new("my-render-target", uvec2(128, 128))
+                .with_clear_color(TRANSPARENT),
+        );
+
+        state.my_render_target = Some(render_target);
     }
 
     let shader_id = state.my_shader_id.unwrap();
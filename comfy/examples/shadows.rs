@@ -0,0 +1,65 @@
+use comfy::*;
+
+simple_game!("2D Shadows", setup, update);
+
+const PILLARS: [Vec2; 4] = [
+    Vec2::new(-4.0, 3.0),
+    Vec2::new(4.0, 3.0),
+    Vec2::new(-4.0, -3.0),
+    Vec2::new(4.0, -3.0),
+];
+
+fn setup(c: &mut EngineContext) {
+    c.load_texture_from_bytes(
+        "comfy",
+        include_bytes!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../assets/comfy.png"
+        )),
+    );
+
+    // Sprites tagged with `ShadowCaster` block light with their rectangle.
+    commands().spawn((
+        Sprite::new("comfy", splat(2.0), 1, WHITE),
+        Transform::position(vec2(0.0, 0.0)),
+        ShadowCaster,
+    ));
+
+    game_config_mut().lighting.ambient_light_intensity = 0.05;
+}
+
+fn update(_c: &mut EngineContext) {
+    draw_rect(Vec2::ZERO, splat(30.0), DARKGRAY, 0);
+
+    // Other shapes are drawn as occluders every frame, just like lights.
+    for pillar in PILLARS {
+        draw_rect(pillar, splat(1.0), GRAY, 1);
+        draw_occluder(Occluder::rect(pillar, splat(1.0)));
+    }
+
+    draw_circle(vec2(0.0, -6.0), 0.8, GRAY, 1);
+    draw_occluder(Occluder::circle(vec2(0.0, -6.0), 0.8));
+
+    let t = get_time() as f32;
+    let flicker = 1.0 + (t * 13.0).sin() * 0.05;
+
+    // The torch follows the mouse.
+    draw_light(
+        Light::simple(mouse_world(), 12.0 * flicker, 1.5)
+            .with_color(ORANGE)
+            .with_shadows(true),
+    );
+
+    draw_light(
+        Light::simple(vec2(t.cos(), t.sin()) * 8.0, 8.0, 1.0)
+            .with_color(BLUE)
+            .with_shadows(true),
+    );
+
+    draw_text(
+        "Move the mouse to move the torch",
+        vec2(0.0, -9.0),
+        WHITE,
+        TextAlign::Center,
+    );
+}
@@ -0,0 +1,98 @@
+use comfy::*;
+
+simple_game!("Split-Screen & Minimap", GameState, setup, update);
+
+/// Only the minimap sees this layer, the players' cameras ignore it.
+const MAP_ICONS: u8 = 1;
+
+pub struct GameState {
+    pub players: [Vec2; 2],
+    pub second_camera: CameraId,
+    pub minimap: CameraId,
+}
+
+impl GameState {
+    pub fn new(_c: &mut EngineState) -> Self {
+        Self {
+            players: [vec2(-3.0, 0.0), vec2(3.0, 0.0)],
+            second_camera: add_camera(
+                Camera::new(Vec2::ZERO, 20.0, Viewport::RIGHT_HALF)
+                    .with_layers(RenderLayers::ALL.without(MAP_ICONS)),
+            ),
+            minimap: add_camera(
+                Camera::new(
+                    Vec2::ZERO,
+                    60.0,
+                    Viewport::new(0.4, 0.02, 0.2, 0.2),
+                )
+                .with_layers(RenderLayers::layer(MAP_ICONS))
+                .with_order(1),
+            ),
+        }
+    }
+}
+
+fn setup(_state: &mut GameState, _c: &mut EngineContext) {
+    let mut camera = main_camera_mut();
+    camera.viewport = Viewport::LEFT_HALF;
+    camera.layers = RenderLayers::ALL.without(MAP_ICONS);
+    camera.zoom = 20.0;
+}
+
+fn update(state: &mut GameState, _c: &mut EngineContext) {
+    clear_background(BLACK);
+
+    let speed = 8.0 * delta();
+
+    let controls = [[KeyCode::W, KeyCode::S, KeyCode::A, KeyCode::D], [
+        KeyCode::Up,
+        KeyCode::Down,
+        KeyCode::Left,
+        KeyCode::Right,
+    ]];
+
+    for (player, [up, down, left, right]) in
+        state.players.iter_mut().zip(controls)
+    {
+        if is_key_down(up) {
+            player.y += speed;
+        }
+        if is_key_down(down) {
+            player.y -= speed;
+        }
+        if is_key_down(left) {
+            player.x -= speed;
+        }
+        if is_key_down(right) {
+            player.x += speed;
+        }
+    }
+
+    main_camera_mut().center = state.players[0];
+
+    if let Some(mut camera) = camera(state.second_camera) {
+        camera.center = state.players[1];
+        set_camera(state.second_camera, camera);
+    }
+
+    if let Some(mut minimap) = camera(state.minimap) {
+        minimap.center = (state.players[0] + state.players[1]) / 2.0;
+        set_camera(state.minimap, minimap);
+    }
+
+    for x in -10..=10 {
+        for y in -10..=10 {
+            let color = if (x + y) % 2 == 0 { DARKGRAY } else { GRAY };
+            draw_rect(vec2(x as f32, y as f32) * 2.0, splat(2.0), color, 0);
+        }
+    }
+
+    draw_circle(state.players[0], 0.5, RED, 1);
+    draw_circle(state.players[1], 0.5, BLUE, 1);
+
+    use_render_layers(RenderLayers::layer(MAP_ICONS));
+    draw_rect(Vec2::ZERO, splat(44.0), DARKGRAY.alpha(0.5), 0);
+    draw_circle(state.players[0], 2.0, RED, 1);
+    draw_circle(state.players[1], 2.0, BLUE, 1);
+    use_default_render_layers();
+}
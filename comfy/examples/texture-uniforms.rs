@@ -0,0 +1,64 @@
+use comfy::*;
+
+simple_game!("Texture Uniforms", GameState, setup, update);
+
+pub struct GameState {
+    pub shader: Option<ShaderId>,
+}
+
+impl GameState {
+    pub fn new(_c: &mut EngineState) -> Self {
+        Self { shader: None }
+    }
+}
+
+fn setup(_state: &mut GameState, c: &mut EngineContext) {
+    c.load_texture_from_bytes(
+        "gradients",
+        include_bytes!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../assets/gradients.png"
+        )),
+    );
+}
+
+// Every texture uniform comes with a sampler of the same name with a
+// `_sampler` suffix.
+const SHADER: &str = "
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let tex = textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    let noise = textureSample(mask, mask_sampler, in.tex_coords).r;
+
+    if noise < threshold {
+        discard;
+    }
+
+    return tex * in.color;
+}
+";
+
+fn update(state: &mut GameState, c: &mut EngineContext) {
+    let shader = *state.shader.get_or_insert_with(|| {
+        create_shader(
+            &mut c.renderer.shaders.borrow_mut(),
+            "dissolve",
+            &sprite_shader_from_fragment(SHADER),
+            hashmap! {
+                "threshold".to_string() => UniformDef::F32(Some(0.5)),
+                "mask".to_string() => UniformDef::Texture(None),
+            },
+        )
+        .unwrap()
+    });
+
+    let threshold = (get_time() as f32).sin() * 0.5 + 0.5;
+
+    use_shader(shader);
+    set_uniform_texture("mask", texture_id("gradients"));
+    set_uniform_f32("threshold", threshold);
+
+    draw_comfy(vec2(0.0, 0.0), WHITE, 0, splat(4.0));
+
+    use_default_shader();
+}
@@ -0,0 +1,64 @@
+use comfy::*;
+
+simple_game!("Typed Uniforms", GameState, setup, update);
+
+pub struct GameState {
+    pub shader: Option<ShaderId>,
+}
+
+impl GameState {
+    pub fn new(_c: &mut EngineState) -> Self {
+        Self { shader: None }
+    }
+}
+
+fn setup(_state: &mut GameState, _c: &mut EngineContext) {}
+
+// Arrays of `f32` are declared as `array<vec4<f32>, N>`, which is why the
+// weights are read from the `x` component.
+const SHADER: &str = "
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let tex = textureSample(t_diffuse, s_diffuse, in.tex_coords);
+
+    let offset = (in.tex_coords - 0.5) * wave;
+    let band = u32(clamp(in.tex_coords.y * 4.0, 0.0, 3.0));
+
+    var color = tex * in.color * tint * weights[band].x;
+    color.r = color.r + offset.x;
+    color.b = color.b + offset.y;
+
+    return color;
+}
+";
+
+fn update(state: &mut GameState, c: &mut EngineContext) {
+    let shader = *state.shader.get_or_insert_with(|| {
+        create_shader(
+            &mut c.renderer.shaders.borrow_mut(),
+            "typed-uniforms",
+            &sprite_shader_from_fragment(SHADER),
+            hashmap! {
+                "tint".to_string() => UniformDef::Color(Some(WHITE)),
+                "wave".to_string() => UniformDef::Vec2(Some(Vec2::ZERO)),
+                "weights".to_string() => UniformDef::Array(
+                    Box::new(UniformDef::F32(Some(1.0))),
+                    4,
+                ),
+            },
+        )
+        .unwrap()
+    });
+
+    let t = get_time() as f32;
+
+    use_shader(shader);
+
+    set_uniform_color("tint", ORANGE);
+    set_uniform_vec2("wave", vec2(t.sin(), t.cos()) * 0.5);
+    set_uniform_array("weights", [0.25f32, 0.5, 0.75, 1.0]);
+
+    draw_comfy(vec2(0.0, 0.0), WHITE, 0, splat(4.0));
+
+    use_default_shader();
+}
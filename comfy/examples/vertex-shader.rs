@@ -0,0 +1,63 @@
+use comfy::*;
+
+simple_game!("Vertex Shader", GameState, setup, update);
+
+pub struct GameState {
+    pub shader: Option<ShaderId>,
+}
+
+impl GameState {
+    pub fn new(_c: &mut EngineState) -> Self {
+        Self { shader: None }
+    }
+}
+
+fn setup(_state: &mut GameState, _c: &mut EngineContext) {}
+
+// Sways the top of each quad based on its world position, leaving the bottom
+// vertices in place. The fragment part is left out, which means the default
+// sprite fragment shader is used.
+const VERTEX_SHADER: &str = "
+@vertex
+fn vs_main(model: VertexInput) -> VertexOutput {
+    var moved = model;
+
+    let top = 1.0 - model.tex_coords.y;
+    let phase = frame.time * 2.0 + model.position.x * 0.7;
+
+    moved.position.x = model.position.x + sin(phase) * strength * top;
+
+    return sprite_vertex(moved);
+}
+";
+
+fn update(state: &mut GameState, c: &mut EngineContext) {
+    let shader = *state.shader.get_or_insert_with(|| {
+        create_shader(
+            &mut c.renderer.shaders.borrow_mut(),
+            "grass",
+            &sprite_shader_from_vertex(VERTEX_SHADER, None),
+            hashmap! {
+                "strength".to_string() => UniformDef::F32(Some(0.3)),
+            },
+        )
+        .unwrap()
+    });
+
+    use_shader(shader);
+
+    set_uniform_f32("strength", 0.2 + mouse_world().y.abs() * 0.1);
+
+    for i in -5..=5 {
+        draw_rect(vec2(i as f32, 0.0), vec2(0.3, 2.0), GREEN, 0);
+    }
+
+    use_default_shader();
+
+    draw_text(
+        "Move the mouse up and down to change the wind strength",
+        vec2(0.0, -3.0),
+        WHITE,
+        TextAlign::Center,
+    );
+}
@@ -22,6 +22,11 @@ pub struct ParticleSystem {
     pub is_enabled: bool,
     pub start_when_texture_loaded: bool,
     pub z_index: i32,
+    /// Render layers active when the system was created, see `with_layers`.
+    pub layers: RenderLayers,
+    /// Clip rects and stencil masks active when the system was created, see
+    /// `with_clip`.
+    pub clip: ClipState,
 
     spawn_timer: f32,
     next_particle: usize,
@@ -46,6 +51,8 @@ impl ParticleSystem {
             spawn_timer: 0.0,
             next_particle: 0,
             z_index: 0,
+            layers: get_current_render_layers(),
+            clip: get_current_clip_state(),
             particle_builder,
             spawn_on_death: false,
             is_enabled: true,
@@ -71,6 +78,8 @@ impl ParticleSystem {
             spawn_timer: 0.0,
             next_particle: 0,
             z_index: 0,
+            layers: get_current_render_layers(),
+            clip: get_current_clip_state(),
             particle_builder: Box::new(particle_builder),
             spawn_on_death: true,
             is_enabled: true,
@@ -89,6 +98,16 @@ impl ParticleSystem {
         system
     }
 
+    pub fn with_layers(self, layers: RenderLayers) -> Self {
+        Self { layers, ..self }
+    }
+
+    /// Clips the particles, e.g. with the state returned by
+    /// `get_current_clip_state` while a clip rect is pushed.
+    pub fn with_clip(self, clip: ClipState) -> Self {
+        Self { clip, ..self }
+    }
+
     pub fn start_when_texture_loaded(mut self, value: bool) -> Self {
         self.start_when_texture_loaded = value;
         self
@@ -243,6 +262,14 @@ pub struct Particle {
     pub fade_type: FadeType,
 
     pub blend_mode: BlendMode,
+    /// Render layers of particles spawned with `spawn_particle`, defaults to
+    /// the ones active when the particle is created. Particle systems use
+    /// `ParticleSystem::layers` instead.
+    pub layers: RenderLayers,
+    /// Clip state of particles spawned with `spawn_particle`, defaults to
+    /// the one active when the particle is created. Particle systems use
+    /// `ParticleSystem::clip` instead.
+    pub clip: ClipState,
 
     pub update: Option<fn(&mut Particle)>,
     pub trail: TrailRef,
@@ -538,6 +565,8 @@ impl Default for Particle {
 
             fade_type: FadeType::Size,
             blend_mode: BlendMode::None,
+            layers: get_current_render_layers(),
+            clip: get_current_clip_state(),
             trail: TrailRef::None,
 
             update: None,
@@ -83,6 +83,11 @@ impl ToQuadDraw for Sprite {
     }
 }
 
+/// Makes an entity with a `Sprite` and a `Transform` block the light of
+/// lights with `casts_shadows`, using the sprite's rectangle as the occluder.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ShadowCaster;
+
 pub fn fhd_ratio() -> f32 {
     1920.0 / 1080.0
 }
@@ -30,6 +30,35 @@ pub fn create_reloadable_sprite_shader(
     Ok(id)
 }
 
+/// Same as `create_reloadable_sprite_shader`, but for post processing effects.
+/// The source is wrapped with `post_process_shader_from_fragment`, which means
+/// it should only contain the `fs_main` fragment function.
+///
+/// The resulting shader can be added to the post processing stack with
+/// `add_post_processing_effect`, see the `post-processing` example.
+pub fn create_reloadable_post_processing_shader(
+    shaders: &mut ShaderMap,
+    name: &str,
+    reloadable_source: ReloadableShaderSource,
+    uniform_defs: UniformDefs,
+) -> Result<ShaderId> {
+    let id = create_shader(
+        shaders,
+        name,
+        &post_process_shader_from_fragment(&reloadable_source.static_source),
+        uniform_defs,
+    )?;
+
+    #[cfg(not(target_arch = "wasm32"))]
+    watch_shader_path_with(
+        &reloadable_source.path,
+        id,
+        post_process_shader_from_fragment,
+    )?;
+
+    Ok(id)
+}
+
 // use crate::*;
 // use notify::{event::AccessKind, Event, EventKind, RecursiveMode, Watcher};
 // use std::{path::Path, sync::mpsc::Receiver};
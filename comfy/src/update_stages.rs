@@ -54,6 +54,7 @@ fn run_mid_update_stages(c: &mut EngineContext) {
 
     pause_system(c);
     point_lights_system();
+    shadow_casters_system();
 
     if is_key_pressed(KeyCode::F6) {
         GlobalParams::toggle_flag("debug");
@@ -109,6 +110,7 @@ pub(crate) fn run_late_update_stages(c: &mut EngineContext, delta: f32) {
     commands().run_on(&mut world_mut());
     world_mut().flush();
     clear_shader_uniform_table();
+    clear_clip_states();
 }
 
 fn dev_hotkeys(_c: &EngineContext) {
@@ -188,10 +190,16 @@ fn render_text(c: &mut EngineContext) {
     let assets = ASSETS.borrow();
     let px = px();
 
+    // Glyphs are drawn as sprites on the text's layers, the user's current
+    // layers are restored afterwards.
+    let user_layers = get_current_render_layers();
+
     for text in consume_text_queue().into_iter() {
         if let Some(pro_params) = text.pro_params {
             let mut t = c.renderer.text.borrow_mut();
 
+            use_render_layers(text.layers);
+
             let font_handle = pro_params.font;
             let font = assets.fonts.get(&font_handle).unwrap();
             let font_size = pro_params.font_size;
@@ -335,6 +343,12 @@ fn render_text(c: &mut EngineContext) {
                 // );
             }
         } else {
+            // egui text is drawn on top of the whole screen, only the main
+            // camera's layers decide if it's visible.
+            if !main_camera().layers.intersects(text.layers) {
+                continue;
+            }
+
             let align = match text.align {
                 TextAlign::TopLeft => egui::Align2::LEFT_TOP,
                 TextAlign::Center => egui::Align2::CENTER_CENTER,
@@ -360,6 +374,8 @@ fn render_text(c: &mut EngineContext) {
             }
         }
     }
+
+    use_render_layers(user_layers);
 }
 
 fn update_blood_canvas(_c: &mut EngineContext) {
@@ -394,18 +410,23 @@ fn update_camera(c: &mut EngineContext) {
     let width = c.renderer.width();
     let height = c.renderer.height();
 
-    camera.aspect_ratio = width / height;
     global_state.screen_size = vec2(width, height);
 
+    // In pixel perfect mode the camera draws into the low resolution, which
+    // is then scaled up to the window.
+    let render_size = render_resolution(global_state.screen_size);
+    camera.aspect_ratio = camera.viewport.aspect_ratio(render_size);
+
     let viewport = camera.world_viewport();
+    let rect = camera.viewport.to_pixels(render_size);
 
-    let flipped_mouse_pos = vec2(
-        global_state.mouse_position.x,
-        global_state.screen_size.y - global_state.mouse_position.y,
-    );
+    let mouse_position =
+        window_to_render(global_state.mouse_position, global_state.screen_size);
+    let flipped_mouse_pos =
+        vec2(mouse_position.x - rect.min.x, rect.max.y - mouse_position.y);
 
-    let normalized = flipped_mouse_pos / global_state.screen_size * viewport -
-        viewport / 2.0;
+    let normalized = flipped_mouse_pos / rect.size().max(Vec2::ONE);
+    let normalized = normalized * viewport - viewport / 2.0;
 
     if !global_state.mouse_locked {
         global_state.mouse_world = normalized + camera.center;
@@ -462,6 +483,18 @@ fn point_lights_system() {
     }
 }
 
+fn shadow_casters_system() {
+    for (_, (transform, sprite, _)) in
+        world_mut().query_mut::<(&Transform, &Sprite, &ShadowCaster)>()
+    {
+        draw_occluder(Occluder::rotated_rect(
+            transform.position,
+            sprite.size * transform.scale,
+            transform.rotation,
+        ));
+    }
+}
+
 fn update_animated_sprites(c: &mut EngineContext) {
     let mut call_queue = vec![];
 
@@ -978,6 +1011,8 @@ fn renderer_update(c: &mut EngineContext) {
             texture_id: p.texture,
             shader: ShaderInstanceId::default(),
             render_target: RenderTargetId::default(),
+            clip: clip_state_id(&particle_system.clip),
+            layers: particle_system.layers,
         };
 
         let err_texture = texture_id("error");
@@ -1011,6 +1046,8 @@ fn renderer_update(c: &mut EngineContext) {
                 texture_id: p.texture,
                 shader: ShaderInstanceId::default(),
                 render_target: RenderTargetId::default(),
+                clip: clip_state_id(&p.clip),
+                layers: p.layers,
             })
             .or_default()
             .push(p.to_draw());
@@ -1028,6 +1065,7 @@ fn renderer_update(c: &mut EngineContext) {
         clear_color,
         frame: frame_params,
         lights: LightingState::take_lights(),
+        occluders: LightingState::take_occluders(),
         particle_queues,
     };
 